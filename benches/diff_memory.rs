@@ -0,0 +1,86 @@
+//! Compares the memory used per generation by `GridDiff` and `CompactGridDiff` on random soups of
+//! various densities, along with the time it takes to evolve and diff each generation.
+//!
+//! Run with `cargo bench --bench diff_memory`.
+
+// Standard library
+use std::time::{Duration, Instant};
+
+// Local
+use cell::{
+    automaton::game_of_life::GameOfLife,
+    universe::{
+        grid2d::{
+            static_grid2d::{CompactGridDiff, GridDiff, StaticGrid2D},
+            Size2D,
+        },
+        CPUDiffUniverse, GenerationDifference, Storable,
+    },
+};
+
+type Grid = StaticGrid2D<GameOfLife>;
+
+fn main() {
+    let size = Size2D(SOUP_SIZE, SOUP_SIZE);
+    println!(
+        "{} generations of {}x{} soups, grid footprint: {} bytes",
+        N_GENS,
+        SOUP_SIZE,
+        SOUP_SIZE,
+        Grid::soup(size, 0.5, SEED, GameOfLife::Alive).footprint()
+    );
+    println!(
+        "{:>8} | {:>16} {:>10} | {:>16} {:>10} {:>8} {:>6}",
+        "density", "GridDiff B/gen", "us/gen", "Compact B/gen", "us/gen", "bitset", "xor"
+    );
+    for density in DENSITIES.iter() {
+        let start = Grid::soup(size, *density, SEED, GameOfLife::Alive);
+        let (grid_bytes, grid_time, _) = measure::<GridDiff<GameOfLife>>(start.clone(), |_| ());
+        let mut encodings = (0, 0);
+        let (compact_bytes, compact_time, _) =
+            measure::<CompactGridDiff<GameOfLife>>(start, |diff| {
+                encodings.0 += diff.is_bitset() as usize;
+                encodings.1 += diff.is_xor_delta() as usize;
+            });
+        println!(
+            "{:>8.2} | {:>16} {:>10.1} | {:>16} {:>10.1} {:>7}% {:>5}%",
+            density,
+            grid_bytes / N_GENS,
+            micros_per_gen(grid_time),
+            compact_bytes / N_GENS,
+            micros_per_gen(compact_time),
+            encodings.0 * 100 / N_GENS,
+            encodings.1 * 100 / N_GENS,
+        );
+    }
+}
+
+/// Evolves a soup while producing differences, and returns their total footprint and the total
+/// time spent evolving.
+fn measure<D>(start: Grid, mut inspect: impl FnMut(&D)) -> (usize, Duration, Grid)
+where
+    D: GenerationDifference<Universe = Grid> + Storable,
+    Grid: CPUDiffUniverse<D>,
+{
+    let mut grid = start;
+    let mut bytes = 0;
+    let mut elapsed = Duration::from_secs(0);
+    for _ in 0..N_GENS {
+        let now = Instant::now();
+        let (next, diff) = grid.cpu_evolve_once_diff();
+        elapsed += now.elapsed();
+        bytes += diff.footprint();
+        inspect(&diff);
+        grid = next;
+    }
+    (bytes, elapsed, grid)
+}
+
+fn micros_per_gen(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1e6 / N_GENS as f64
+}
+
+const SOUP_SIZE: usize = 256;
+const N_GENS: usize = 200;
+const DENSITIES: [f64; 4] = [0.1, 0.25, 0.37, 0.5];
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
@@ -13,9 +13,19 @@ pub trait AutomatonCell: Copy + Debug + Default + Eq + PartialEq + Send + Sync +
     type Encoded: Copy + Send + Sync;
 
     fn encode(&self) -> Self::Encoded;
-    fn decode(encoded: &Self::Encoded) -> Self;
+
+    /// Returns `None` if `encoded` isn't the encoding of any cell (e.g., in a corrupted file).
+    fn try_decode(encoded: &Self::Encoded) -> Option<Self>;
+
+    fn decode(encoded: &Self::Encoded) -> Self {
+        Self::try_decode(encoded).expect(ERR_DECODING)
+    }
 
     fn neighborhood() -> &'static [Self::Neighbor];
+
+    /// Identifies the automaton along with its rule (e.g., "B3/S23" for the Game of Life), so that
+    /// recorded runs and results can be traced back to it.
+    fn rule() -> &'static str;
 }
 
 pub trait CPUCell: AutomatonCell {
@@ -27,3 +37,5 @@ pub trait GPUCell: AutomatonCell {}
 pub trait TermDrawableAutomaton: AutomatonCell {
     fn style(&self) -> StyledContent<char>;
 }
+
+const ERR_DECODING: &str = "Decoding failed: unknown encoding.";
@@ -2,6 +2,7 @@
 use crossterm::style::{style, Attribute, Color, StyledContent};
 
 // Local
+pub mod catalogue;
 use crate::{
     automaton::{AutomatonCell, CPUCell, TermDrawableAutomaton},
     universe::{
@@ -38,17 +39,21 @@ impl AutomatonCell for GameOfLife {
         }
     }
 
-    fn decode(id: &Self::Encoded) -> Self {
+    fn try_decode(id: &Self::Encoded) -> Option<Self> {
         match id {
-            0 => GameOfLife::Dead,
-            1 => GameOfLife::Alive,
-            _ => panic!("Decoding failed: unkwnon encoding {}.", id),
+            0 => Some(GameOfLife::Dead),
+            1 => Some(GameOfLife::Alive),
+            _ => None,
         }
     }
 
     fn neighborhood() -> &'static [Self::Neighbor] {
         &MOORE_NEIGHBORHOOD
     }
+
+    fn rule() -> &'static str {
+        "B3/S23"
+    }
 }
 
 impl CPUCell for GameOfLife {
@@ -260,59 +265,14 @@ pub fn check_lwss(
     true
 }
 
-// pub fn gosper_glider_gun() -> Grid<GameOfLife> {
-//     let mut grid = Grid::new(Dimensions::new(100, 50));
-//     grid = cascade!(
-//         grid;
-//         ..set(Position::new(1, 5), GameOfLife::Alive);
-//         ..set(Position::new(1, 6), GameOfLife::Alive);
-//         ..set(Position::new(2, 5), GameOfLife::Alive);
-//         ..set(Position::new(2, 6), GameOfLife::Alive);
-//         ..set(Position::new(11, 5), GameOfLife::Alive);
-//         ..set(Position::new(11, 6), GameOfLife::Alive);
-//         ..set(Position::new(11, 7), GameOfLife::Alive);
-//         ..set(Position::new(12, 4), GameOfLife::Alive);
-//         ..set(Position::new(12, 8), GameOfLife::Alive);
-//         ..set(Position::new(13, 3), GameOfLife::Alive);
-//         ..set(Position::new(13, 9), GameOfLife::Alive);
-//         ..set(Position::new(14, 3), GameOfLife::Alive);
-//         ..set(Position::new(14, 9), GameOfLife::Alive);
-//         ..set(Position::new(15, 6), GameOfLife::Alive);
-//         ..set(Position::new(16, 4), GameOfLife::Alive);
-//         ..set(Position::new(16, 8), GameOfLife::Alive);
-//         ..set(Position::new(17, 5), GameOfLife::Alive);
-//         ..set(Position::new(17, 6), GameOfLife::Alive);
-//         ..set(Position::new(17, 7), GameOfLife::Alive);
-//         ..set(Position::new(18, 6), GameOfLife::Alive);
-//         ..set(Position::new(21, 3), GameOfLife::Alive);
-//         ..set(Position::new(21, 4), GameOfLife::Alive);
-//         ..set(Position::new(21, 5), GameOfLife::Alive);
-//         ..set(Position::new(22, 3), GameOfLife::Alive);
-//         ..set(Position::new(22, 4), GameOfLife::Alive);
-//         ..set(Position::new(22, 5), GameOfLife::Alive);
-//         ..set(Position::new(23, 2), GameOfLife::Alive);
-//         ..set(Position::new(23, 6), GameOfLife::Alive);
-//         ..set(Position::new(25, 1), GameOfLife::Alive);
-//         ..set(Position::new(25, 2), GameOfLife::Alive);
-//         ..set(Position::new(25, 6), GameOfLife::Alive);
-//         ..set(Position::new(25, 7), GameOfLife::Alive);
-//         ..set(Position::new(35, 3), GameOfLife::Alive);
-//         ..set(Position::new(35, 4), GameOfLife::Alive);
-//         ..set(Position::new(36, 3), GameOfLife::Alive);
-//         ..set(Position::new(36, 4), GameOfLife::Alive);
-//     );
-//     grid
-// }
+pub fn gosper_glider_gun() -> StaticGrid2D<GameOfLife> {
+    catalogue::find("Gosper glider gun")
+        .unwrap()
+        .to_static_grid(Size2D(100, 50), Coordinates2D(1, 1))
+}
 
-// pub fn r_pentomino() -> Grid<GameOfLife> {
-//     let mut grid = Grid::new(Dimensions::new(201, 201));
-//     grid = cascade!(
-//         grid;
-//         ..set(Position::new(100, 99), GameOfLife::Alive);
-//         ..set(Position::new(101, 99), GameOfLife::Alive);
-//         ..set(Position::new(99, 100), GameOfLife::Alive);
-//         ..set(Position::new(100, 100), GameOfLife::Alive);
-//         ..set(Position::new(100, 101), GameOfLife::Alive);
-//     );
-//     grid
-// }
+pub fn r_pentomino() -> StaticGrid2D<GameOfLife> {
+    catalogue::find("R-pentomino")
+        .unwrap()
+        .to_static_grid(Size2D(201, 201), Coordinates2D(99, 99))
+}
@@ -0,0 +1,661 @@
+// Standard library
+use std::collections::HashSet;
+
+// Local
+use super::GameOfLife;
+use crate::universe::{
+    grid2d::{
+        infinite_grid2d::InfiniteGrid2D, static_grid2d::StaticGrid2D, Coordinates2D,
+        SCoordinates2D, Size2D,
+    },
+    CPUUniverse, Universe,
+};
+
+/// PatternKind
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PatternKind {
+    StillLife,
+    Oscillator,
+    Spaceship,
+    Gun,
+    Puffer,
+    Methuselah,
+}
+
+/// Behavior
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Behavior {
+    /// The pattern never changes.
+    StillLife,
+    /// The pattern returns to its initial state every `period` generations.
+    Oscillator { period: usize },
+    /// The pattern returns to its initial state every `period` generations, translated by
+    /// `displacement` cells.
+    Spaceship {
+        period: usize,
+        displacement: (isize, isize),
+    },
+    /// The pattern returns to its initial state every `period` generations, and emits `emitted`
+    /// live cells (e.g., a glider) outside of its bounding box in the process.
+    Gun { period: usize, emitted: usize },
+    /// The pattern's front returns to its initial state every `period` generations, translated by
+    /// `displacement` cells, and leaves debris behind it.
+    Puffer {
+        period: usize,
+        displacement: (isize, isize),
+    },
+    /// The pattern stabilizes after `lifespan` generations with `final_population` live cells.
+    Methuselah {
+        lifespan: usize,
+        final_population: usize,
+    },
+}
+
+impl Behavior {
+    pub fn kind(&self) -> PatternKind {
+        match self {
+            Behavior::StillLife => PatternKind::StillLife,
+            Behavior::Oscillator { .. } => PatternKind::Oscillator,
+            Behavior::Spaceship { .. } => PatternKind::Spaceship,
+            Behavior::Gun { .. } => PatternKind::Gun,
+            Behavior::Puffer { .. } => PatternKind::Puffer,
+            Behavior::Methuselah { .. } => PatternKind::Methuselah,
+        }
+    }
+
+    pub fn period(&self) -> Option<usize> {
+        match self {
+            Behavior::StillLife => Some(1),
+            Behavior::Oscillator { period }
+            | Behavior::Spaceship { period, .. }
+            | Behavior::Gun { period, .. }
+            | Behavior::Puffer { period, .. } => Some(*period),
+            Behavior::Methuselah { .. } => None,
+        }
+    }
+
+    pub fn displacement(&self) -> (isize, isize) {
+        match self {
+            Behavior::Spaceship { displacement, .. } | Behavior::Puffer { displacement, .. } => {
+                *displacement
+            }
+            _ => (0, 0),
+        }
+    }
+
+    /// Returns the speed of a moving pattern as a (numerator, denominator) pair, expressed as a
+    /// fraction of the speed of light c (i.e., 1 cell per generation) along the dominant axis.
+    /// For example, a glider's speed is c/4, returned as (1, 4).
+    pub fn speed(&self) -> Option<(usize, usize)> {
+        match self {
+            Behavior::Spaceship {
+                period,
+                displacement,
+            }
+            | Behavior::Puffer {
+                period,
+                displacement,
+            } => {
+                let dist = displacement.0.abs().max(displacement.1.abs()) as usize;
+                let gcd = gcd(dist, *period);
+                Some((dist / gcd, *period / gcd))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Pattern
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub name: &'static str,
+    pub behavior: Behavior,
+    pub tags: &'static [&'static str],
+    rle: &'static str,
+}
+
+impl Pattern {
+    #[inline]
+    pub fn kind(&self) -> PatternKind {
+        self.behavior.kind()
+    }
+
+    /// Returns the coordinates of all live cells in the pattern, relative to the top-left corner
+    /// of the pattern's bounding box.
+    pub fn cells(&self) -> Vec<Coordinates2D> {
+        parse_rle(self.rle)
+    }
+
+    /// Returns the size of the pattern's bounding box.
+    pub fn size(&self) -> Size2D {
+        let cells = self.cells();
+        let columns = cells.iter().map(|c| c.x() + 1).max().unwrap_or(0);
+        let lines = cells.iter().map(|c| c.y() + 1).max().unwrap_or(0);
+        Size2D(columns, lines)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&tag)
+    }
+
+    /// Creates a static grid of the given size with the pattern's top-left corner placed at
+    /// `offset`.
+    pub fn to_static_grid(&self, size: Size2D, offset: Coordinates2D) -> StaticGrid2D<GameOfLife> {
+        let mut grid = StaticGrid2D::new_empty(size);
+        for c in self.cells() {
+            grid.set(
+                Coordinates2D(offset.x() + c.x(), offset.y() + c.y()),
+                GameOfLife::Alive,
+            );
+        }
+        grid
+    }
+
+    /// Adds the pattern to an infinite grid with the pattern's top-left corner placed at `offset`.
+    pub fn place(&self, grid: &mut InfiniteGrid2D<GameOfLife>, offset: SCoordinates2D) {
+        for c in self.cells() {
+            let cell_coords =
+                SCoordinates2D(offset.x() + c.x() as isize, offset.y() + c.y() as isize);
+            grid.set(cell_coords, GameOfLife::Alive);
+        }
+    }
+
+    /// Evolves the pattern on an infinite grid and checks that it behaves as declared by its
+    /// metadata (period, displacement, emitted cells, lifespan, ...).
+    pub fn verify(&self) -> Result<(), String> {
+        let mut grid = InfiniteGrid2D::new(VERIFY_CHUNK_SIZE_POW2);
+        self.place(&mut grid, SCoordinates2D(0, 0));
+        let initial = live_cells(&grid);
+
+        match self.behavior {
+            Behavior::StillLife => {
+                let next = live_cells(&grid.cpu_evolve_once());
+                if next != initial {
+                    return Err(format!("{} changed after 1 generation", self.name));
+                }
+            }
+            Behavior::Oscillator { period } | Behavior::Spaceship { period, .. } => {
+                let (dx, dy) = self.behavior.displacement();
+                let expected = translate(&initial, dx, dy);
+                for gen in 1..=period {
+                    grid = grid.cpu_evolve_once();
+                    let current = live_cells(&grid);
+                    if gen < period && (current == initial || current == expected) {
+                        return Err(format!(
+                            "{} repeated after {} generations, expected period {}",
+                            self.name, gen, period
+                        ));
+                    }
+                    if gen == period && current != expected {
+                        return Err(format!(
+                            "{} did not return to its initial state translated by ({}, {}) \
+                            after {} generations",
+                            self.name, dx, dy, period
+                        ));
+                    }
+                }
+            }
+            Behavior::Gun { period, emitted } => {
+                let (min, max) = bounding_box(&initial);
+                grid = grid.cpu_evolve(period);
+                let current = live_cells(&grid);
+                let (inside, outside): (HashSet<_>, HashSet<_>) =
+                    current.into_iter().partition(|c| {
+                        min.x() <= c.x() && c.x() <= max.x() && min.y() <= c.y() && c.y() <= max.y()
+                    });
+                if inside != initial {
+                    return Err(format!(
+                        "{} did not return to its initial state after {} generations",
+                        self.name, period
+                    ));
+                }
+                if outside.len() != emitted {
+                    return Err(format!(
+                        "{} emitted {} cells in {} generations, expected {}",
+                        self.name,
+                        outside.len(),
+                        period,
+                        emitted
+                    ));
+                }
+            }
+            Behavior::Puffer {
+                period,
+                displacement,
+            } => {
+                // The puffer's front is identified by the pattern's initial bounding box, which
+                // moves along with the pattern
+                let (min, max) = bounding_box(&initial);
+                let (dx, dy) = displacement;
+                let front = |cells: &HashSet<SCoordinates2D>, n: isize| {
+                    cells
+                        .iter()
+                        .filter(|c| {
+                            min.x() + n * dx <= c.x()
+                                && c.x() <= max.x() + n * dx
+                                && min.y() + n * dy <= c.y()
+                                && c.y() <= max.y() + n * dy
+                        })
+                        .copied()
+                        .collect::<HashSet<_>>()
+                };
+
+                // Let the puffer establish itself before checking for periodicity
+                grid = grid.cpu_evolve(period * PUFFER_WARMUP_PERIODS);
+                let reference = front(&live_cells(&grid), PUFFER_WARMUP_PERIODS as isize);
+                grid = grid.cpu_evolve(period);
+                let current = live_cells(&grid);
+                let current_front = front(&current, PUFFER_WARMUP_PERIODS as isize + 1);
+                if current_front != translate(&reference, dx, dy) {
+                    return Err(format!(
+                        "{}'s front did not move by ({}, {}) in {} generations",
+                        self.name, dx, dy, period
+                    ));
+                }
+                if current.len() <= current_front.len() {
+                    return Err(format!("{} left no debris behind", self.name));
+                }
+            }
+            Behavior::Methuselah {
+                lifespan,
+                final_population,
+            } => {
+                // The pattern must still be changing right before its declared lifespan, and must
+                // have stabilized to the declared population afterwards
+                grid = grid.cpu_evolve(lifespan - 1);
+                let before = live_cells(&grid);
+                grid = grid.cpu_evolve_once();
+                let at_lifespan = live_cells(&grid);
+                if at_lifespan.len() != final_population {
+                    return Err(format!(
+                        "{} has {} live cells at generation {}, expected {}",
+                        self.name,
+                        at_lifespan.len(),
+                        lifespan,
+                        final_population
+                    ));
+                }
+                if before == at_lifespan {
+                    return Err(format!(
+                        "{} stabilized before generation {}",
+                        self.name, lifespan
+                    ));
+                }
+                for gen in (lifespan + 1)..=(lifespan + METHUSELAH_STABILITY_CHECK) {
+                    grid = grid.cpu_evolve_once();
+                    if live_cells(&grid).len() != final_population {
+                        return Err(format!(
+                            "{} is still evolving at generation {}",
+                            self.name, gen
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the full pattern catalogue.
+pub fn catalogue() -> &'static [Pattern] {
+    &CATALOGUE
+}
+
+/// Finds a pattern in the catalogue by name (case insensitive).
+pub fn find(name: &str) -> Option<&'static Pattern> {
+    CATALOGUE
+        .iter()
+        .find(|pattern| pattern.name.eq_ignore_ascii_case(name))
+}
+
+/// Returns all patterns of a given kind.
+pub fn of_kind(kind: PatternKind) -> impl Iterator<Item = &'static Pattern> {
+    CATALOGUE
+        .iter()
+        .filter(move |pattern| pattern.kind() == kind)
+}
+
+/// Returns all patterns with a given tag.
+pub fn with_tag<'a>(tag: &'a str) -> impl Iterator<Item = &'static Pattern> + 'a {
+    CATALOGUE.iter().filter(move |pattern| pattern.has_tag(tag))
+}
+
+/// Parses a pattern in run length encoded format (without header line) into the list of live
+/// cells' coordinates.
+pub fn parse_rle(rle: &str) -> Vec<Coordinates2D> {
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    let mut count = 0;
+    for c in rle.chars() {
+        match c {
+            '0'..='9' => count = count * 10 + c.to_digit(10).unwrap() as usize,
+            'b' | '.' => {
+                x += count.max(1);
+                count = 0;
+            }
+            'o' | 'A' => {
+                for _ in 0..count.max(1) {
+                    cells.push(Coordinates2D(x, y));
+                    x += 1;
+                }
+                count = 0;
+            }
+            '$' => {
+                y += count.max(1);
+                x = 0;
+                count = 0;
+            }
+            '!' => break,
+            c if c.is_whitespace() => (),
+            _ => panic!("{} '{}'", ERR_RLE_CHARACTER, c),
+        }
+    }
+    cells
+}
+
+fn live_cells(grid: &InfiniteGrid2D<GameOfLife>) -> HashSet<SCoordinates2D> {
+    grid.non_default_cells()
+        .into_iter()
+        .map(|(coords, _)| coords)
+        .collect()
+}
+
+fn translate(cells: &HashSet<SCoordinates2D>, dx: isize, dy: isize) -> HashSet<SCoordinates2D> {
+    cells
+        .iter()
+        .map(|c| SCoordinates2D(c.x() + dx, c.y() + dy))
+        .collect()
+}
+
+fn bounding_box(cells: &HashSet<SCoordinates2D>) -> (SCoordinates2D, SCoordinates2D) {
+    let min_x = cells.iter().map(|c| c.x()).min().unwrap_or(0);
+    let max_x = cells.iter().map(|c| c.x()).max().unwrap_or(0);
+    let min_y = cells.iter().map(|c| c.y()).min().unwrap_or(0);
+    let max_y = cells.iter().map(|c| c.y()).max().unwrap_or(0);
+    (SCoordinates2D(min_x, min_y), SCoordinates2D(max_x, max_y))
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+const VERIFY_CHUNK_SIZE_POW2: usize = 4;
+const PUFFER_WARMUP_PERIODS: usize = 10;
+const METHUSELAH_STABILITY_CHECK: usize = 10;
+
+const CATALOGUE: [Pattern; 22] = [
+    // Still lifes
+    Pattern {
+        name: "Block",
+        behavior: Behavior::StillLife,
+        tags: &["still life", "common"],
+        rle: "2o$2o!",
+    },
+    Pattern {
+        name: "Beehive",
+        behavior: Behavior::StillLife,
+        tags: &["still life", "common"],
+        rle: "b2o$o2bo$b2o!",
+    },
+    Pattern {
+        name: "Loaf",
+        behavior: Behavior::StillLife,
+        tags: &["still life", "common"],
+        rle: "b2o$o2bo$bobo$2bo!",
+    },
+    Pattern {
+        name: "Boat",
+        behavior: Behavior::StillLife,
+        tags: &["still life", "common"],
+        rle: "2o$obo$bo!",
+    },
+    Pattern {
+        name: "Tub",
+        behavior: Behavior::StillLife,
+        tags: &["still life"],
+        rle: "bo$obo$bo!",
+    },
+    // Oscillators
+    Pattern {
+        name: "Blinker",
+        behavior: Behavior::Oscillator { period: 2 },
+        tags: &["oscillator", "common"],
+        rle: "3o!",
+    },
+    Pattern {
+        name: "Toad",
+        behavior: Behavior::Oscillator { period: 2 },
+        tags: &["oscillator", "common"],
+        rle: "b3o$3o!",
+    },
+    Pattern {
+        name: "Beacon",
+        behavior: Behavior::Oscillator { period: 2 },
+        tags: &["oscillator", "common"],
+        rle: "2o$o$3bo$2b2o!",
+    },
+    Pattern {
+        name: "Pulsar",
+        behavior: Behavior::Oscillator { period: 3 },
+        tags: &["oscillator"],
+        rle: "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+              o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    },
+    Pattern {
+        name: "Pentadecathlon",
+        behavior: Behavior::Oscillator { period: 15 },
+        tags: &["oscillator"],
+        rle: "2bo4bo$2ob4ob2o$2bo4bo!",
+    },
+    Pattern {
+        name: "Figure eight",
+        behavior: Behavior::Oscillator { period: 8 },
+        tags: &["oscillator"],
+        rle: "3o$3o$3o$3b3o$3b3o$3b3o!",
+    },
+    // Spaceships
+    Pattern {
+        name: "Glider",
+        behavior: Behavior::Spaceship {
+            period: 4,
+            displacement: (1, 1),
+        },
+        tags: &["spaceship", "diagonal", "common"],
+        rle: "bo$2bo$3o!",
+    },
+    Pattern {
+        name: "Lightweight spaceship",
+        behavior: Behavior::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        tags: &["spaceship", "orthogonal", "LWSS"],
+        rle: "bo2bo$o$o3bo$4o!",
+    },
+    Pattern {
+        name: "Middleweight spaceship",
+        behavior: Behavior::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        tags: &["spaceship", "orthogonal", "MWSS"],
+        rle: "3bo$bo3bo$o$o4bo$5o!",
+    },
+    Pattern {
+        name: "Heavyweight spaceship",
+        behavior: Behavior::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        tags: &["spaceship", "orthogonal", "HWSS"],
+        rle: "3b2o$bo4bo$o$o5bo$6o!",
+    },
+    // Guns
+    Pattern {
+        name: "Gosper glider gun",
+        behavior: Behavior::Gun {
+            period: 30,
+            emitted: 5,
+        },
+        tags: &["gun", "glider"],
+        rle: "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$\
+              10bo5bo7bo$11bo3bo$12b2o!",
+    },
+    // Puffers
+    Pattern {
+        name: "Puffer train",
+        behavior: Behavior::Puffer {
+            period: 20,
+            displacement: (10, 0),
+        },
+        tags: &["puffer", "orthogonal"],
+        rle: "3bo$4bo$o3bo$b4o4$o$b2o$2bo$2bo$bo3$3bo$4bo$o3bo$b4o!",
+    },
+    // Methuselahs
+    Pattern {
+        name: "R-pentomino",
+        behavior: Behavior::Methuselah {
+            lifespan: 1103,
+            final_population: 116,
+        },
+        tags: &["methuselah"],
+        rle: "b2o$2o$bo!",
+    },
+    Pattern {
+        name: "Diehard",
+        behavior: Behavior::Methuselah {
+            lifespan: 130,
+            final_population: 0,
+        },
+        tags: &["methuselah", "vanishing"],
+        rle: "6bo$2o$bo3b3o!",
+    },
+    Pattern {
+        name: "Acorn",
+        behavior: Behavior::Methuselah {
+            lifespan: 5206,
+            final_population: 633,
+        },
+        tags: &["methuselah"],
+        rle: "bo$3bo$2o2b3o!",
+    },
+    Pattern {
+        name: "Pi-heptomino",
+        behavior: Behavior::Methuselah {
+            lifespan: 173,
+            final_population: 55,
+        },
+        tags: &["methuselah"],
+        rle: "3o$obo$obo!",
+    },
+    Pattern {
+        name: "B-heptomino",
+        behavior: Behavior::Methuselah {
+            lifespan: 148,
+            final_population: 28,
+        },
+        tags: &["methuselah"],
+        rle: "ob2o$3o$bo!",
+    },
+];
+
+const ERR_RLE_CHARACTER: &str = "Unexpected character in RLE pattern:";
+
+#[cfg(test)]
+mod tests {
+    use super::{catalogue, find, of_kind, parse_rle, Behavior, PatternKind};
+    use crate::universe::grid2d::{Coordinates2D, Size2D};
+
+    fn verify_kind(kind: PatternKind) {
+        for pattern in of_kind(kind) {
+            if let Err(msg) = pattern.verify() {
+                panic!("{}", msg);
+            }
+        }
+    }
+
+    #[test]
+    fn rle() {
+        let glider = parse_rle("bo$2bo$3o!");
+        assert_eq!(
+            glider,
+            vec![
+                Coordinates2D(1, 0),
+                Coordinates2D(2, 1),
+                Coordinates2D(0, 2),
+                Coordinates2D(1, 2),
+                Coordinates2D(2, 2)
+            ]
+        );
+        assert_eq!(find("glider").unwrap().size(), Size2D(3, 3));
+    }
+
+    #[test]
+    fn metadata() {
+        // Names are unique
+        for (i, pattern) in catalogue().iter().enumerate() {
+            assert!(catalogue()[(i + 1)..]
+                .iter()
+                .all(|other| other.name != pattern.name));
+        }
+
+        let glider = find("Glider").unwrap();
+        assert_eq!(glider.kind(), PatternKind::Spaceship);
+        assert_eq!(glider.behavior.speed(), Some((1, 4)));
+        let lwss = find("Lightweight spaceship").unwrap();
+        assert_eq!(lwss.behavior.speed(), Some((1, 2)));
+        assert_eq!(find("Block").unwrap().behavior, Behavior::StillLife);
+        assert_eq!(find("Pulsar").unwrap().behavior.period(), Some(3));
+    }
+
+    #[test]
+    fn still_lifes() {
+        verify_kind(PatternKind::StillLife);
+    }
+
+    #[test]
+    fn oscillators() {
+        verify_kind(PatternKind::Oscillator);
+    }
+
+    #[test]
+    fn spaceships() {
+        verify_kind(PatternKind::Spaceship);
+    }
+
+    #[test]
+    fn guns() {
+        verify_kind(PatternKind::Gun);
+    }
+
+    #[test]
+    fn puffers() {
+        verify_kind(PatternKind::Puffer);
+    }
+
+    #[test]
+    fn short_lived_methuselahs() {
+        for name in &["Diehard", "Pi-heptomino", "B-heptomino"] {
+            if let Err(msg) = find(name).unwrap().verify() {
+                panic!("{}", msg);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn long_lived_methuselahs() {
+        for name in &["R-pentomino", "Acorn"] {
+            if let Err(msg) = find(name).unwrap().verify() {
+                panic!("{}", msg);
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Standard library
+use std::sync::Arc;
+
+// External libraries
+use crossterm::style::Color;
+
+// Local
+pub mod animation;
+pub mod image;
+pub mod statistics;
+pub mod svg;
+use crate::automaton::{AutomatonCell, TermDrawableAutomaton};
+
+/// Rgb
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    #[inline]
+    pub fn r(&self) -> u8 {
+        self.0
+    }
+
+    #[inline]
+    pub fn g(&self) -> u8 {
+        self.1
+    }
+
+    #[inline]
+    pub fn b(&self) -> u8 {
+        self.2
+    }
+
+    /// Converts a terminal color to its usual RGB representation. Returns `None` for
+    /// `Color::Reset`, which has no color of its own.
+    pub fn from_term_color(color: Color) -> Option<Self> {
+        match color {
+            Color::Reset => None,
+            Color::Black => Some(Rgb(0, 0, 0)),
+            Color::DarkGrey => Some(Rgb(128, 128, 128)),
+            Color::Red => Some(Rgb(255, 0, 0)),
+            Color::DarkRed => Some(Rgb(128, 0, 0)),
+            Color::Green => Some(Rgb(0, 255, 0)),
+            Color::DarkGreen => Some(Rgb(0, 128, 0)),
+            Color::Yellow => Some(Rgb(255, 255, 0)),
+            Color::DarkYellow => Some(Rgb(128, 128, 0)),
+            Color::Blue => Some(Rgb(0, 0, 255)),
+            Color::DarkBlue => Some(Rgb(0, 0, 128)),
+            Color::Magenta => Some(Rgb(255, 0, 255)),
+            Color::DarkMagenta => Some(Rgb(128, 0, 128)),
+            Color::Cyan => Some(Rgb(0, 255, 255)),
+            Color::DarkCyan => Some(Rgb(0, 128, 128)),
+            Color::White => Some(Rgb(255, 255, 255)),
+            Color::Grey => Some(Rgb(192, 192, 192)),
+            Color::Rgb { r, g, b } => Some(Rgb(r, g, b)),
+            Color::AnsiValue(val) => Some(Self::from_ansi_value(val)),
+        }
+    }
+
+    /// Returns the CSS representation of the color (e.g., "#00ff00").
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    fn from_ansi_value(val: u8) -> Self {
+        const BASIC: [Rgb; 16] = [
+            Rgb(0, 0, 0),
+            Rgb(128, 0, 0),
+            Rgb(0, 128, 0),
+            Rgb(128, 128, 0),
+            Rgb(0, 0, 128),
+            Rgb(128, 0, 128),
+            Rgb(0, 128, 128),
+            Rgb(192, 192, 192),
+            Rgb(128, 128, 128),
+            Rgb(255, 0, 0),
+            Rgb(0, 255, 0),
+            Rgb(255, 255, 0),
+            Rgb(0, 0, 255),
+            Rgb(255, 0, 255),
+            Rgb(0, 255, 255),
+            Rgb(255, 255, 255),
+        ];
+        let level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+        match val {
+            0..=15 => BASIC[val as usize],
+            16..=231 => {
+                let v = val - 16;
+                Rgb(level(v / 36), level((v / 6) % 6), level(v % 6))
+            }
+            _ => {
+                let grey = 8 + 10 * (val - 232);
+                Rgb(grey, grey, grey)
+            }
+        }
+    }
+}
+
+/// ColourMap
+
+#[derive(Clone)]
+pub struct ColourMap<C: AutomatonCell> {
+    colours: Vec<(C, Rgb)>,
+    fallback: Arc<dyn Fn(&C) -> Rgb + Send + Sync>,
+}
+
+impl<C: AutomatonCell> ColourMap<C> {
+    /// Creates a colour map that paints every state with the same colour until more specific
+    /// colours are assigned with `set`.
+    pub fn new(fallback: Rgb) -> Self {
+        Self {
+            colours: vec![],
+            fallback: Arc::new(move |_| fallback),
+        }
+    }
+
+    pub fn set(&mut self, cell: C, colour: Rgb) {
+        match self.colours.iter_mut().find(|(c, _)| *c == cell) {
+            Some((_, old_colour)) => *old_colour = colour,
+            None => self.colours.push((cell, colour)),
+        }
+    }
+
+    pub fn with(mut self, cell: C, colour: Rgb) -> Self {
+        self.set(cell, colour);
+        self
+    }
+
+    pub fn colour(&self, cell: &C) -> Rgb {
+        match self.colours.iter().find(|(c, _)| c == cell) {
+            Some((_, colour)) => *colour,
+            None => (self.fallback)(cell),
+        }
+    }
+}
+
+impl<C: TermDrawableAutomaton> ColourMap<C> {
+    /// Creates a colour map whose colours are derived from the states' terminal style (foreground
+    /// colour, or background colour if the former isn't set).
+    pub fn from_style() -> Self {
+        Self {
+            colours: vec![],
+            fallback: Arc::new(|cell: &C| {
+                let style = cell.style();
+                let style = style.style();
+                style
+                    .foreground_color
+                    .and_then(Rgb::from_term_color)
+                    .or_else(|| style.background_color.and_then(Rgb::from_term_color))
+                    .unwrap_or(DEFAULT_COLOUR)
+            }),
+        }
+    }
+}
+
+impl<C: TermDrawableAutomaton> Default for ColourMap<C> {
+    fn default() -> Self {
+        Self::from_style()
+    }
+}
+
+const DEFAULT_COLOUR: Rgb = Rgb(255, 255, 255);
@@ -0,0 +1,385 @@
+// Standard library
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::ops::Range;
+
+// External libraries
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+
+// Local
+use super::{ColourMap, Rgb};
+use crate::{
+    automaton::AutomatonCell,
+    simulator::Simulator,
+    universe::{
+        grid2d::{Coordinates2D, Difference2D, Universe2D, Window2D},
+        GenerationDifference, Universe,
+    },
+};
+
+/// Palette
+
+#[derive(Debug, Clone)]
+pub struct Palette<C: AutomatonCell> {
+    entries: Vec<(C, Rgb)>,
+}
+
+impl<C: AutomatonCell> Palette<C> {
+    pub fn new(entries: Vec<(C, Rgb)>) -> Self {
+        if entries.is_empty() || entries.len() > MAX_PALETTE_SIZE {
+            panic!("{}", ERR_PALETTE_SIZE);
+        }
+        Self { entries }
+    }
+
+    /// Creates a palette for the given states with colours picked from a colour map.
+    pub fn from_colour_map(colours: &ColourMap<C>, states: &[C]) -> Self {
+        Self::new(
+            states
+                .iter()
+                .map(|state| (*state, colours.colour(state)))
+                .collect(),
+        )
+    }
+
+    pub fn index(&self, cell: &C) -> u8 {
+        match self.entries.iter().position(|(c, _)| c == cell) {
+            Some(idx) => idx as u8,
+            None => panic!("{} {:?}", ERR_STATE_NOT_IN_PALETTE, cell),
+        }
+    }
+
+    /// Index reserved for transparent pixels, right after the last state's index.
+    #[inline]
+    pub fn transparent_index(&self) -> u8 {
+        self.entries.len() as u8
+    }
+
+    /// Returns the GIF representation of the palette (RGB triplets), padded to the next power of
+    /// two number of colours.
+    fn to_gif_palette(&self) -> Vec<u8> {
+        let n_colours = (self.entries.len() + 1).next_power_of_two().max(2);
+        let mut raw = Vec::with_capacity(n_colours * 3);
+        for (_, colour) in self.entries.iter() {
+            raw.extend_from_slice(&[colour.r(), colour.g(), colour.b()]);
+        }
+        raw.resize(n_colours * 3, 0);
+        raw
+    }
+}
+
+/// GifOptions
+
+#[derive(Debug, Clone)]
+pub struct GifOptions<C: AutomatonCell> {
+    pub palette: Palette<C>,
+    /// Width and height of a single cell, in pixels.
+    pub scale: usize,
+    /// Delay between frames, in hundredths of a second.
+    pub delay: u16,
+    /// Whether the animation loops forever.
+    pub repeat: bool,
+}
+
+impl<C: AutomatonCell> GifOptions<C> {
+    pub fn new(palette: Palette<C>) -> Self {
+        Self {
+            palette,
+            scale: DEFAULT_SCALE,
+            delay: DEFAULT_DELAY,
+            repeat: true,
+        }
+    }
+
+    pub fn scale(mut self, scale: usize) -> Self {
+        if scale == 0 {
+            panic!("{}", ERR_ZERO_SCALE);
+        }
+        self.scale = scale;
+        self
+    }
+
+    pub fn delay(mut self, delay: u16) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+}
+
+/// Writes an animated GIF showing every `stride`-th generation of `gens` within `window`.
+///
+/// Only the first frame is encoded in full. Every following frame only covers the bounding box
+/// of the cells that changed since the previous frame, as reported by the simulator's generation
+/// differences, and unchanged cells within that box are left transparent.
+pub fn write_gif<S: Simulator, W: Write>(
+    simulator: &S,
+    gens: Range<usize>,
+    stride: usize,
+    window: Window2D,
+    options: &GifOptions<<S::Universe as Universe>::Cell>,
+    writer: W,
+) -> io::Result<()>
+where
+    S::Universe: Universe2D,
+    S::Diff: Difference2D,
+{
+    if stride == 0 {
+        panic!("{}", ERR_ZERO_STRIDE);
+    }
+    if gens.start >= gens.end {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, ERR_EMPTY_RANGE));
+    }
+
+    let scale = options.scale;
+    let width = to_u16(window.size.columns() * scale)?;
+    let height = to_u16(window.size.lines() * scale)?;
+    let palette = &options.palette;
+
+    let mut encoder =
+        Encoder::new(writer, width, height, &palette.to_gif_palette()).map_err(gif_error)?;
+    if options.repeat {
+        encoder.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+    }
+
+    // First frame covers the whole window
+    let mut universe = get_generation(simulator, gens.start)?;
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..window.size.lines() {
+        let line: Vec<u8> = (0..window.size.columns())
+            .map(|x| palette.index(&universe.get_signed(window.to_absolute(Coordinates2D(x, y)))))
+            .collect();
+        push_scaled_line(&mut pixels, &line, scale);
+    }
+    encoder
+        .write_frame(&Frame {
+            delay: options.delay,
+            dispose: DisposalMethod::Keep,
+            width,
+            height,
+            buffer: Cow::Owned(pixels),
+            ..Frame::default()
+        })
+        .map_err(gif_error)?;
+
+    // Following frames only cover the region that changed
+    let mut prev_gen = gens.start;
+    for gen in gens.clone().step_by(stride).skip(1) {
+        let diff = simulator
+            .get_difference(prev_gen, gen)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, ERR_MISSING_GENERATION))?;
+        let next_universe = diff.apply_to(universe.clone());
+
+        // Collect cells whose state actually changed within the window
+        let changed: Vec<Coordinates2D> = diff
+            .modified_coords(&universe)
+            .into_iter()
+            .filter(|coords| {
+                window.contains(*coords)
+                    && universe.get_signed(*coords) != next_universe.get_signed(*coords)
+            })
+            .map(|coords| {
+                Coordinates2D(
+                    (coords.x() - window.origin.x()) as usize,
+                    (coords.y() - window.origin.y()) as usize,
+                )
+            })
+            .collect();
+
+        let frame = if changed.is_empty() {
+            // Nothing to redraw, but a frame is still needed to keep the animation's timing
+            Frame {
+                delay: options.delay,
+                dispose: DisposalMethod::Keep,
+                transparent: Some(palette.transparent_index()),
+                width: 1,
+                height: 1,
+                buffer: Cow::Owned(vec![palette.transparent_index()]),
+                ..Frame::default()
+            }
+        } else {
+            let min_x = changed.iter().map(|c| c.x()).min().unwrap();
+            let max_x = changed.iter().map(|c| c.x()).max().unwrap();
+            let min_y = changed.iter().map(|c| c.y()).min().unwrap();
+            let max_y = changed.iter().map(|c| c.y()).max().unwrap();
+            let (cols, lines) = (max_x - min_x + 1, max_y - min_y + 1);
+
+            // Every cell in the bounding box is transparent except the ones that changed
+            let mut cells = vec![palette.transparent_index(); cols * lines];
+            for c in changed.iter() {
+                let abs_coords = window.to_absolute(*c);
+                cells[(c.x() - min_x) + (c.y() - min_y) * cols] =
+                    palette.index(&next_universe.get_signed(abs_coords));
+            }
+            let mut pixels = Vec::with_capacity(cols * lines * scale * scale);
+            for line in cells.chunks(cols) {
+                push_scaled_line(&mut pixels, line, scale);
+            }
+
+            Frame {
+                delay: options.delay,
+                dispose: DisposalMethod::Keep,
+                transparent: Some(palette.transparent_index()),
+                left: to_u16(min_x * scale)?,
+                top: to_u16(min_y * scale)?,
+                width: to_u16(cols * scale)?,
+                height: to_u16(lines * scale)?,
+                buffer: Cow::Owned(pixels),
+                ..Frame::default()
+            }
+        };
+        encoder.write_frame(&frame).map_err(gif_error)?;
+
+        universe = next_universe;
+        prev_gen = gen;
+    }
+    Ok(())
+}
+
+fn get_generation<S: Simulator>(simulator: &S, gen: usize) -> io::Result<S::Universe> {
+    simulator
+        .get_generation(gen)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, ERR_MISSING_GENERATION))
+}
+
+fn push_scaled_line(pixels: &mut Vec<u8>, line: &[u8], scale: usize) {
+    for _ in 0..scale {
+        for idx in line {
+            for _ in 0..scale {
+                pixels.push(*idx);
+            }
+        }
+    }
+}
+
+fn to_u16(val: usize) -> io::Result<u16> {
+    if val > u16::MAX as usize {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, ERR_TOO_LARGE))
+    } else {
+        Ok(val as u16)
+    }
+}
+
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    io::Error::other(err)
+}
+
+const DEFAULT_SCALE: usize = 4;
+const DEFAULT_DELAY: u16 = 10;
+const MAX_PALETTE_SIZE: usize = 255;
+
+const ERR_PALETTE_SIZE: &str = "A GIF palette must contain between 1 and 255 states.";
+const ERR_STATE_NOT_IN_PALETTE: &str = "State is missing from the GIF palette:";
+const ERR_ZERO_SCALE: &str = "The rendering scale must be strictly positive.";
+const ERR_ZERO_STRIDE: &str = "The frame stride must be strictly positive.";
+const ERR_EMPTY_RANGE: &str = "The generation range is empty.";
+const ERR_MISSING_GENERATION: &str = "The simulator doesn't have the requested generation.";
+const ERR_TOO_LARGE: &str = "The animation is too large to be encoded as a GIF.";
+
+#[cfg(test)]
+mod tests {
+    use super::{write_gif, GifOptions, Palette};
+    use crate::{
+        automaton::game_of_life::{catalogue, GameOfLife},
+        export::Rgb,
+        simulator::{Simulator, SyncSimulator},
+        universe::{
+            grid2d::{
+                static_grid2d::{GridDiff, StaticGrid2D},
+                Coordinates2D, Size2D, Universe2D,
+            },
+            Universe,
+        },
+    };
+
+    fn decode_frames(data: &[u8]) -> Vec<(u16, u16, u16, u16, Vec<u8>)> {
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut reader = decoder.read_info(data).unwrap();
+        let mut frames = vec![];
+        while let Some(frame) = reader.read_next_frame().unwrap() {
+            frames.push((
+                frame.left,
+                frame.top,
+                frame.width,
+                frame.height,
+                frame.buffer.to_vec(),
+            ));
+        }
+        frames
+    }
+
+    #[test]
+    fn diff_encoded_frames() {
+        // A glider in the top-left corner and a block (static) in the bottom-right corner
+        let mut grid = catalogue::find("Glider")
+            .unwrap()
+            .to_static_grid(Size2D(20, 20), Coordinates2D(1, 1));
+        for c in catalogue::find("Block").unwrap().cells() {
+            grid.set(Coordinates2D(c.x() + 16, c.y() + 16), GameOfLife::Alive);
+        }
+        let window = grid.bounds();
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(grid, 10);
+        simulator.run(8);
+
+        let palette = Palette::new(vec![
+            (GameOfLife::Dead, Rgb(0, 0, 0)),
+            (GameOfLife::Alive, Rgb(255, 255, 255)),
+        ]);
+        let options = GifOptions::new(palette).scale(2);
+        let mut data = vec![];
+        write_gif(&simulator, 0..9, 4, window, &options, &mut data).unwrap();
+
+        let frames = decode_frames(&data);
+        assert_eq!(frames.len(), 3);
+
+        // First frame is complete
+        assert_eq!((frames[0].2, frames[0].3), (40, 40));
+        assert_eq!(frames[0].4.len(), 40 * 40);
+
+        // Following frames only cover the glider's area, never the block
+        for frame in &frames[1..] {
+            let (left, top, width, height, _) = frame;
+            assert!(left + width <= 16 * 2 && top + height <= 16 * 2);
+            assert!(*width <= 4 * 2 && *height <= 4 * 2);
+        }
+    }
+
+    #[test]
+    fn static_frames() {
+        let grid = catalogue::find("Block")
+            .unwrap()
+            .to_static_grid(Size2D(4, 4), Coordinates2D(1, 1));
+        let window = grid.bounds();
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(grid, 10);
+        simulator.run(3);
+
+        let palette = Palette::new(vec![
+            (GameOfLife::Dead, Rgb(0, 0, 0)),
+            (GameOfLife::Alive, Rgb(255, 255, 255)),
+        ]);
+        let mut data = vec![];
+        write_gif(
+            &simulator,
+            0..4,
+            1,
+            window,
+            &GifOptions::new(palette),
+            &mut data,
+        )
+        .unwrap();
+
+        // Unchanged generations are encoded as single transparent pixels
+        let frames = decode_frames(&data);
+        assert_eq!(frames.len(), 4);
+        for frame in &frames[1..] {
+            assert_eq!((frame.2, frame.3), (1, 1));
+            assert_eq!(frame.4, vec![2]);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+// Standard library
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// Local
+use super::{ColourMap, Rgb};
+use crate::{
+    automaton::{AutomatonCell, TermDrawableAutomaton},
+    simulator::Simulator,
+    universe::{
+        grid2d::{Coordinates2D, Universe2D, Window2D},
+        load_capacity, Universe,
+    },
+};
+
+/// ImageFormat
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// RenderOptions
+
+#[derive(Clone)]
+pub struct RenderOptions<C: AutomatonCell> {
+    /// Width and height of a single cell, in pixels.
+    pub scale: usize,
+    /// Colour of the lines drawn between cells, if any.
+    pub grid_lines: Option<Rgb>,
+    pub colours: ColourMap<C>,
+}
+
+impl<C: AutomatonCell> RenderOptions<C> {
+    pub fn new(colours: ColourMap<C>) -> Self {
+        Self {
+            scale: DEFAULT_SCALE,
+            grid_lines: None,
+            colours,
+        }
+    }
+
+    pub fn scale(mut self, scale: usize) -> Self {
+        if scale == 0 {
+            panic!("{}", ERR_ZERO_SCALE);
+        }
+        self.scale = scale;
+        self
+    }
+
+    pub fn grid_lines(mut self, colour: Rgb) -> Self {
+        self.grid_lines = Some(colour);
+        self
+    }
+}
+
+impl<C: TermDrawableAutomaton> Default for RenderOptions<C> {
+    fn default() -> Self {
+        Self::new(ColourMap::from_style())
+    }
+}
+
+/// Image
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for _ in 0..(width * height) {
+            pixels.extend_from_slice(&[background.r(), background.g(), background.b()]);
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the raw pixel data, as consecutive RGB triplets in row-major order.
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let idx = self.idx(x, y);
+        Rgb(self.pixels[idx], self.pixels[idx + 1], self.pixels[idx + 2])
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, colour: Rgb) {
+        let idx = self.idx(x, y);
+        self.pixels[idx] = colour.r();
+        self.pixels[idx + 1] = colour.g();
+        self.pixels[idx + 2] = colour.b();
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: Rgb) {
+        for j in y..(y + height) {
+            for i in x..(x + width) {
+                self.set_pixel(i, j, colour);
+            }
+        }
+    }
+
+    /// Writes the image in binary PPM (P6) format.
+    pub fn write_ppm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)?;
+        writer.flush()
+    }
+
+    /// Writes the image in 8-bit RGB PNG format.
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header()?;
+        png_writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    pub fn write<W: Write>(&self, writer: W, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(writer),
+            ImageFormat::Png => self.write_png(writer),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ImageFormat) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        self.write(file, format)
+    }
+
+    /// Reads an image in PPM format, either binary (P6) or plain (P3). Samples are rescaled to 8
+    /// bits if the file uses a different maximum value.
+    pub fn read_ppm<R: Read>(reader: R) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let magic = read_ppm_token(&mut reader)?;
+        let binary = match magic.as_str() {
+            "P6" => true,
+            "P3" => false,
+            _ => return Err(invalid_data(ERR_PPM_HEADER)),
+        };
+        let width = parse_ppm_number(&read_ppm_token(&mut reader)?)?;
+        let height = parse_ppm_number(&read_ppm_token(&mut reader)?)?;
+        let max_val = parse_ppm_number(&read_ppm_token(&mut reader)?)?;
+        if max_val == 0 || max_val > 65535 {
+            return Err(invalid_data(ERR_PPM_HEADER));
+        }
+
+        let n_samples = width
+            .checked_mul(height)
+            .and_then(|n_pixels| n_pixels.checked_mul(3))
+            .ok_or_else(|| invalid_data(ERR_PPM_HEADER))?;
+        let samples: Vec<usize> = if binary {
+            // A single whitespace character separates the header from the raster, and has already
+            // been consumed along with the last header token
+            let sample_len = if max_val < 256 { 1 } else { 2 };
+            let n_bytes = n_samples
+                .checked_mul(sample_len)
+                .ok_or_else(|| invalid_data(ERR_PPM_HEADER))?;
+            let mut raw = Vec::with_capacity(load_capacity(n_bytes));
+            if reader.by_ref().take(n_bytes as u64).read_to_end(&mut raw)? != n_bytes {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if sample_len == 1 {
+                raw.into_iter().map(|val| val as usize).collect()
+            } else {
+                raw.chunks(2)
+                    .map(|val| ((val[0] as usize) << 8) | val[1] as usize)
+                    .collect()
+            }
+        } else {
+            let mut samples = Vec::with_capacity(load_capacity(n_samples));
+            for _ in 0..n_samples {
+                samples.push(parse_ppm_number(&read_ppm_token(&mut reader)?)?);
+            }
+            samples
+        };
+
+        let mut pixels = Vec::with_capacity(samples.len());
+        for val in samples {
+            if val > max_val {
+                return Err(invalid_data(ERR_PPM_SAMPLE));
+            }
+            pixels.push(((val * 255 + max_val / 2) / max_val) as u8);
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Reads an image in PNG format. Every color type and bit depth is supported, but samples are
+    /// reduced to 8 bits and the alpha channel (if any) is discarded.
+    pub fn read_png<R: Read>(reader: R) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(reader);
+        // Palettes and low bit depths are expanded, and 16-bit samples keep their high byte
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut png_reader) = decoder.read_info()?;
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(invalid_data(ERR_PNG_DEPTH));
+        }
+        let mut data = vec![0; info.buffer_size()];
+        png_reader.next_frame(&mut data)?;
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for line in data.chunks(info.line_size).take(height) {
+            let line = &line[..(width * info.color_type.samples())];
+            match info.color_type {
+                png::ColorType::RGB => pixels.extend_from_slice(line),
+                png::ColorType::RGBA => {
+                    for px in line.chunks(4) {
+                        pixels.extend_from_slice(&px[..3]);
+                    }
+                }
+                png::ColorType::Grayscale => {
+                    for &grey in line {
+                        pixels.extend_from_slice(&[grey, grey, grey]);
+                    }
+                }
+                png::ColorType::GrayscaleAlpha => {
+                    for px in line.chunks(2) {
+                        pixels.extend_from_slice(&[px[0], px[0], px[0]]);
+                    }
+                }
+                // Palettes are expanded to RGB(A) by the decoder
+                png::ColorType::Indexed => return Err(invalid_data(ERR_PNG_INDEXED)),
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn read<R: Read>(reader: R, format: ImageFormat) -> io::Result<Self> {
+        match format {
+            ImageFormat::Ppm => Self::read_ppm(reader),
+            ImageFormat::Png => Self::read_png(reader),
+        }
+    }
+
+    /// Opens an image file, whose format is detected from its first bytes.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let format = if file.fill_buf()?.starts_with(PNG_SIGNATURE) {
+            ImageFormat::Png
+        } else {
+            ImageFormat::Ppm
+        };
+        Self::read(file, format)
+    }
+
+    #[inline]
+    fn idx(&self, x: usize, y: usize) -> usize {
+        if !(x < self.width && y < self.height) {
+            panic!("{}", ERR_PIXEL_OUT_OF_BOUNDS);
+        }
+        (x + y * self.width) * 3
+    }
+}
+
+/// Renders a window of a 2D universe to an image.
+pub fn render<U: Universe2D>(
+    universe: &U,
+    window: Window2D,
+    options: &RenderOptions<U::Cell>,
+) -> Image {
+    let scale = options.scale;
+    let line_width = if options.grid_lines.is_some() { 1 } else { 0 };
+    let pitch = scale + line_width;
+    let width = window.size.columns() * pitch + line_width;
+    let height = window.size.lines() * pitch + line_width;
+
+    let background = options
+        .grid_lines
+        .unwrap_or(options.colours.colour(&U::Cell::default()));
+    let mut image = Image::new(width, height, background);
+    for y in 0..window.size.lines() {
+        for x in 0..window.size.columns() {
+            let cell = universe.get_signed(window.to_absolute(Coordinates2D(x, y)));
+            let colour = options.colours.colour(&cell);
+            image.fill_rect(
+                x * pitch + line_width,
+                y * pitch + line_width,
+                scale,
+                scale,
+                colour,
+            );
+        }
+    }
+    image
+}
+
+/// Renders a range of generations from a simulator and saves each one as a separate image in
+/// `dir`. Returns the paths of all created files, in generation order.
+pub fn export_generations<S: Simulator>(
+    simulator: &S,
+    gens: Range<usize>,
+    window: Window2D,
+    options: &RenderOptions<<S::Universe as Universe>::Cell>,
+    format: ImageFormat,
+    dir: &Path,
+) -> io::Result<Vec<PathBuf>>
+where
+    S::Universe: Universe2D,
+{
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::with_capacity(gens.len());
+    for gen in gens {
+        let universe = simulator
+            .get_generation(gen)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, ERR_MISSING_GENERATION))?;
+        let path = dir.join(format!("gen_{:06}.{}", gen, format.extension()));
+        render(&universe, window, options).save(&path, format)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Reads the next whitespace-separated token of a PPM header, skipping comments. The whitespace
+/// character following the token is consumed as well.
+fn read_ppm_token<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut token = String::new();
+    let mut in_comment = false;
+    let mut byte = [0];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            if token.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, ERR_PPM_HEADER));
+            }
+            return Ok(token);
+        }
+        let c = byte[0] as char;
+        if in_comment {
+            in_comment = c != '\n';
+        } else if c == '#' {
+            in_comment = true;
+        } else if c.is_ascii_whitespace() {
+            if !token.is_empty() {
+                return Ok(token);
+            }
+        } else {
+            token.push(c);
+        }
+    }
+}
+
+fn parse_ppm_number(token: &str) -> io::Result<usize> {
+    token.parse().map_err(|_| invalid_data(ERR_PPM_HEADER))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+const DEFAULT_SCALE: usize = 8;
+const PNG_SIGNATURE: &[u8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+
+const ERR_ZERO_SCALE: &str = "The rendering scale must be strictly positive.";
+const ERR_PIXEL_OUT_OF_BOUNDS: &str = "Pixel coordinates are outside of the image.";
+const ERR_MISSING_GENERATION: &str = "The simulator doesn't have the requested generation.";
+const ERR_PPM_HEADER: &str = "Invalid PPM header.";
+const ERR_PPM_SAMPLE: &str = "PPM sample exceeds the maximum value declared in the header.";
+const ERR_PNG_INDEXED: &str = "Indexed PNG images should have been expanded by the decoder.";
+const ERR_PNG_DEPTH: &str = "PNG samples should have been reduced to 8 bits by the decoder.";
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use super::{export_generations, render, Image, ImageFormat, RenderOptions};
+    use crate::{
+        automaton::game_of_life::{self, catalogue, GameOfLife},
+        export::{ColourMap, Rgb},
+        simulator::{Simulator, SyncSimulator},
+        universe::grid2d::{
+            infinite_grid2d::InfiniteGrid2D,
+            static_grid2d::{GridDiff, StaticGrid2D},
+            SCoordinates2D, Size2D, Universe2D, Window2D,
+        },
+    };
+
+    #[test]
+    fn render_static() {
+        let blinker = game_of_life::blinker();
+        let options = RenderOptions::default().scale(2).grid_lines(Rgb(1, 2, 3));
+        let image = render(&blinker, blinker.bounds(), &options);
+
+        // 5 cells of 2 pixels each, plus 6 separating lines
+        assert_eq!((image.width(), image.height()), (16, 16));
+        assert_eq!(image.pixel(0, 0), Rgb(1, 2, 3));
+        assert_eq!(image.pixel(3, 1), Rgb(1, 2, 3));
+
+        // Colours are derived from the terminal style by default
+        let alive = Rgb(0, 255, 0);
+        let dead = Rgb(192, 192, 192);
+        assert_eq!(image.pixel(1, 1), dead);
+        assert_eq!(image.pixel(4, 7), alive);
+        assert_eq!(image.pixel(5, 8), alive);
+        assert_eq!(image.pixel(10, 7), alive);
+        assert_eq!(image.pixel(13, 7), dead);
+    }
+
+    #[test]
+    fn render_infinite_window() {
+        let mut grid = InfiniteGrid2D::new(3);
+        catalogue::find("Glider")
+            .unwrap()
+            .place(&mut grid, SCoordinates2D(-10, -10));
+        let colours = ColourMap::new(Rgb(0, 0, 0)).with(GameOfLife::Alive, Rgb(255, 255, 255));
+        let window = grid.bounds();
+        assert_eq!(
+            window,
+            Window2D::new(SCoordinates2D(-10, -10), Size2D(3, 3))
+        );
+
+        let image = render(&grid, window, &RenderOptions::new(colours).scale(1));
+        assert_eq!((image.width(), image.height()), (3, 3));
+        assert_eq!(image.pixel(0, 0), Rgb(0, 0, 0));
+        assert_eq!(image.pixel(1, 0), Rgb(255, 255, 255));
+        assert_eq!(image.pixel(0, 2), Rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn batch_export() {
+        let blinker = game_of_life::blinker();
+        let window = blinker.bounds();
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(blinker, 10);
+        simulator.run(2);
+
+        let dir = std::env::temp_dir().join("cell_image_batch_export");
+        let options = RenderOptions::default().scale(1);
+        let paths =
+            export_generations(&simulator, 0..3, window, &options, ImageFormat::Ppm, &dir).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[1].ends_with("gen_000001.ppm"));
+        let data = fs::read(&paths[0]).unwrap();
+        assert!(data.starts_with(b"P6\n5 5\n255\n"));
+        assert_eq!(data.len(), 11 + 5 * 5 * 3);
+
+        // Generations that don't exist yet are reported as errors
+        assert!(
+            export_generations(&simulator, 2..4, window, &options, ImageFormat::Ppm, &dir).is_err()
+        );
+
+        // PNG output can be decoded back
+        let paths =
+            export_generations(&simulator, 1..2, window, &options, ImageFormat::Png, &dir).unwrap();
+        let decoder = png::Decoder::new(File::open(&paths[0]).unwrap());
+        let (info, _) = decoder.read_info().unwrap();
+        assert_eq!((info.width, info.height), (5, 5));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_back() {
+        let mut image = Image::new(3, 2, Rgb(10, 20, 30));
+        image.set_pixel(2, 1, Rgb(255, 0, 128));
+        for &format in [ImageFormat::Ppm, ImageFormat::Png].iter() {
+            let mut data = vec![];
+            image.write(&mut data, format).unwrap();
+            assert_eq!(Image::read(&data[..], format).unwrap(), image);
+        }
+
+        // Plain PPM with comments and a different maximum value
+        let plain = b"P3 # comment\n3 1\n# another comment\n15\n0 0 0  15 15 15\n5 10 15\n";
+        let image = Image::read_ppm(&plain[..]).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 1));
+        assert_eq!(image.pixel(0, 0), Rgb(0, 0, 0));
+        assert_eq!(image.pixel(1, 0), Rgb(255, 255, 255));
+        assert_eq!(image.pixel(2, 0), Rgb(85, 170, 255));
+
+        // Truncated files are reported as errors
+        assert!(Image::read_ppm(&b"P6\n2 2\n255\n\0\0\0"[..]).is_err());
+
+        // Huge dimensions fail on the missing data or the overflow, rather than when allocating
+        assert!(Image::read_ppm(&b"P6\n100000 100000\n255\n\0\0\0"[..]).is_err());
+        assert!(Image::read_ppm(&b"P3\n100000 100000\n255\n0 0 0"[..]).is_err());
+        let overflowing = format!("P6\n{} {}\n255\n", usize::MAX, 2);
+        assert!(Image::read_ppm(overflowing.as_bytes()).is_err());
+        assert!(Image::read_png(&b"\x89PNG"[..]).is_err());
+    }
+
+    #[test]
+    fn read_png_16_bits() {
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, 2, 1);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let mut png_writer = encoder.write_header().unwrap();
+        let samples: [u16; 6] = [0xffff, 0x8000, 0x0000, 0x1234, 0xabcd, 0x00ff];
+        let bytes = samples
+            .iter()
+            .flat_map(|sample| sample.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+        png_writer.write_image_data(&bytes).unwrap();
+        drop(png_writer);
+
+        let image = Image::read_png(&data[..]).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Rgb(0xff, 0x80, 0x00));
+        assert_eq!(image.pixel(1, 0), Rgb(0x12, 0xab, 0x00));
+    }
+}
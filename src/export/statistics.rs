@@ -0,0 +1,159 @@
+// Standard library
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+// Local
+use crate::{automaton::AutomatonCell, simulator::GenerationStats};
+
+/// Renders a time series of statistics as CSV, with one line per generation. States get one
+/// population column each (named after their debug representation), in the order in which they
+/// first appear in the series. Fields that don't apply (e.g., the bounding box of an empty
+/// universe) are left empty.
+pub fn render_csv<C: AutomatonCell>(series: &[GenerationStats<C>]) -> String {
+    let states = states(series);
+    let mut csv = String::from("generation");
+    for state in states.iter() {
+        write!(csv, ",population_{:?}", state).unwrap();
+    }
+    csv.push_str(",births,deaths,bbox_x,bbox_y,bbox_width,bbox_height,centre_x,centre_y\n");
+
+    for stats in series {
+        write!(csv, "{}", stats.generation).unwrap();
+        for state in states.iter() {
+            write!(csv, ",{}", stats.count(*state)).unwrap();
+        }
+        write!(csv, ",{},{}", stats.births, stats.deaths).unwrap();
+        match stats.bounding_box {
+            Some(window) => write!(
+                csv,
+                ",{},{},{},{}",
+                window.origin.x(),
+                window.origin.y(),
+                window.size.columns(),
+                window.size.lines()
+            )
+            .unwrap(),
+            None => csv.push_str(",,,,"),
+        }
+        match stats.centre_of_mass {
+            Some((x, y)) => writeln!(csv, ",{},{}", x, y).unwrap(),
+            None => csv.push_str(",,\n"),
+        }
+    }
+    csv
+}
+
+/// Renders a time series of statistics as a JSON array, with one object per generation.
+/// Populations are objects keyed by the states' debug representations.
+pub fn render_json<C: AutomatonCell>(series: &[GenerationStats<C>]) -> String {
+    let mut json = String::from("[");
+    for (i, stats) in series.iter().enumerate() {
+        if 0 < i {
+            json.push(',');
+        }
+        write!(
+            json,
+            "\n  {{\"generation\": {}, \"population\": {{",
+            stats.generation
+        )
+        .unwrap();
+        for (j, (state, count)) in stats.population.iter().enumerate() {
+            if 0 < j {
+                json.push_str(", ");
+            }
+            write!(json, "\"{}\": {}", escape(&format!("{:?}", state)), count).unwrap();
+        }
+        write!(
+            json,
+            "}}, \"births\": {}, \"deaths\": {}, \"bounding_box\": ",
+            stats.births, stats.deaths
+        )
+        .unwrap();
+        match stats.bounding_box {
+            Some(window) => write!(
+                json,
+                "{{\"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}}}",
+                window.origin.x(),
+                window.origin.y(),
+                window.size.columns(),
+                window.size.lines()
+            )
+            .unwrap(),
+            None => json.push_str("null"),
+        }
+        json.push_str(", \"centre_of_mass\": ");
+        match stats.centre_of_mass {
+            Some((x, y)) => write!(json, "[{}, {}]}}", x, y).unwrap(),
+            None => json.push_str("null}"),
+        }
+    }
+    json.push_str("\n]\n");
+    json
+}
+
+pub fn write_csv<C: AutomatonCell, W: Write>(
+    series: &[GenerationStats<C>],
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(render_csv(series).as_bytes())?;
+    writer.flush()
+}
+
+pub fn write_json<C: AutomatonCell, W: Write>(
+    series: &[GenerationStats<C>],
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(render_json(series).as_bytes())?;
+    writer.flush()
+}
+
+/// Returns every state that appears in a series, in order of first appearance.
+fn states<C: AutomatonCell>(series: &[GenerationStats<C>]) -> Vec<C> {
+    let mut states = vec![];
+    for (state, _) in series.iter().flat_map(|stats| stats.population.iter()) {
+        if !states.contains(state) {
+            states.push(*state);
+        }
+    }
+    states
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_csv, render_json};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::StatisticsCollector,
+        universe::grid2d::static_grid2d::{GridDiff, StaticGrid2D},
+    };
+
+    #[test]
+    fn csv_and_json() {
+        let mut collector: StatisticsCollector<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            StatisticsCollector::new();
+        collector.start(0, &game_of_life::blinker());
+        let empty = StaticGrid2D::new_empty(*game_of_life::blinker().size());
+        collector.observe(1, &empty, None);
+
+        assert_eq!(
+            render_csv(collector.series()),
+            "generation,population_Dead,population_Alive,births,deaths,\
+             bbox_x,bbox_y,bbox_width,bbox_height,centre_x,centre_y\n\
+             0,22,3,0,0,1,2,3,1,2,2\n\
+             1,25,0,0,3,,,,,,\n"
+        );
+        assert_eq!(
+            render_json(collector.series()),
+            "[\n  {\"generation\": 0, \"population\": {\"Dead\": 22, \"Alive\": 3}, \
+             \"births\": 0, \"deaths\": 0, \
+             \"bounding_box\": {\"x\": 1, \"y\": 2, \"width\": 3, \"height\": 1}, \
+             \"centre_of_mass\": [2, 2]},\
+             \n  {\"generation\": 1, \"population\": {\"Dead\": 25}, \"births\": 0, \
+             \"deaths\": 3, \"bounding_box\": null, \"centre_of_mass\": null}\n]\n"
+        );
+    }
+}
@@ -0,0 +1,615 @@
+// Standard library
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+// Local
+use super::{ColourMap, Rgb};
+use crate::{
+    automaton::{AutomatonCell, TermDrawableAutomaton},
+    universe::grid2d::{Coordinates2D, SCoordinates2D, Universe2D, Window2D},
+};
+
+/// FillStyle
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FillStyle {
+    pub fill: Rgb,
+    pub opacity: f64,
+    /// Outline colour and width (in pixels) of each region of cells in the state, if any.
+    pub stroke: Option<(Rgb, f64)>,
+}
+
+impl FillStyle {
+    pub fn new(fill: Rgb) -> Self {
+        Self {
+            fill,
+            opacity: 1.0,
+            stroke: None,
+        }
+    }
+
+    pub fn opacity(mut self, opacity: f64) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn stroke(mut self, colour: Rgb, width: f64) -> Self {
+        self.stroke = Some((colour, width));
+        self
+    }
+}
+
+/// Overlay
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Overlay {
+    /// A rectangle drawn around a window of the universe.
+    BoundingBox { window: Window2D, colour: Rgb },
+    /// An arrow between two points expressed in universe coordinates (a cell's top-left corner is
+    /// at its integer coordinates, and its center is offset by 0.5 on both axes).
+    Arrow {
+        from: (f64, f64),
+        to: (f64, f64),
+        colour: Rgb,
+    },
+}
+
+impl Overlay {
+    /// Creates an arrow starting at the center of `window` and pointing in the direction of
+    /// `displacement`, for example the displacement of a spaceship over its period. The arrow's
+    /// length is `length` cells.
+    pub fn direction(
+        window: Window2D,
+        displacement: (isize, isize),
+        length: f64,
+        colour: Rgb,
+    ) -> Self {
+        let from = (
+            window.origin.x() as f64 + window.size.columns() as f64 / 2.0,
+            window.origin.y() as f64 + window.size.lines() as f64 / 2.0,
+        );
+        let (dx, dy) = (displacement.0 as f64, displacement.1 as f64);
+        let norm = (dx * dx + dy * dy).sqrt();
+        let to = if norm == 0.0 {
+            from
+        } else {
+            (from.0 + dx / norm * length, from.1 + dy / norm * length)
+        };
+        Overlay::Arrow { from, to, colour }
+    }
+}
+
+/// SvgOptions
+
+#[derive(Clone)]
+pub struct SvgOptions<C: AutomatonCell> {
+    /// Width and height of a single cell, in pixels.
+    pub scale: f64,
+    /// Colour of the lines drawn between cells, if any.
+    pub grid: Option<Rgb>,
+    /// Spacing (in cells) between ticks on the coordinate axes, if axes should be drawn.
+    pub axes: Option<usize>,
+    /// Whether to paint cells in the default state. When disabled, the background is left
+    /// transparent.
+    pub background: bool,
+    pub overlays: Vec<Overlay>,
+    styles: Vec<(C, FillStyle)>,
+    colours: ColourMap<C>,
+}
+
+impl<C: AutomatonCell> SvgOptions<C> {
+    pub fn new(colours: ColourMap<C>) -> Self {
+        Self {
+            scale: DEFAULT_SCALE,
+            grid: None,
+            axes: None,
+            background: true,
+            overlays: vec![],
+            styles: vec![],
+            colours,
+        }
+    }
+
+    pub fn scale(mut self, scale: f64) -> Self {
+        if scale <= 0.0 {
+            panic!("{}", ERR_NON_POSITIVE_SCALE);
+        }
+        self.scale = scale;
+        self
+    }
+
+    pub fn grid(mut self, colour: Rgb) -> Self {
+        self.grid = Some(colour);
+        self
+    }
+
+    pub fn axes(mut self, tick_spacing: usize) -> Self {
+        if tick_spacing == 0 {
+            panic!("{}", ERR_ZERO_TICK_SPACING);
+        }
+        self.axes = Some(tick_spacing);
+        self
+    }
+
+    pub fn background(mut self, background: bool) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn overlay(mut self, overlay: Overlay) -> Self {
+        self.overlays.push(overlay);
+        self
+    }
+
+    /// Overrides the fill style of a state, which otherwise is a plain fill with the state's
+    /// colour in the colour map.
+    pub fn style(mut self, cell: C, style: FillStyle) -> Self {
+        match self.styles.iter_mut().find(|(c, _)| *c == cell) {
+            Some((_, old_style)) => *old_style = style,
+            None => self.styles.push((cell, style)),
+        }
+        self
+    }
+
+    pub fn fill_style(&self, cell: &C) -> FillStyle {
+        match self.styles.iter().find(|(c, _)| c == cell) {
+            Some((_, style)) => *style,
+            None => FillStyle::new(self.colours.colour(cell)),
+        }
+    }
+}
+
+impl<C: TermDrawableAutomaton> Default for SvgOptions<C> {
+    fn default() -> Self {
+        Self::new(ColourMap::from_style())
+    }
+}
+
+/// Renders a window of a 2D universe as an SVG document. Adjacent cells in the same state are
+/// merged into a single path.
+pub fn render_svg<U: Universe2D>(
+    universe: &U,
+    window: Window2D,
+    options: &SvgOptions<U::Cell>,
+) -> String {
+    let scale = options.scale;
+    let margin = if options.axes.is_some() {
+        AXES_MARGIN
+    } else {
+        0.0
+    };
+    let width = window.size.columns() as f64 * scale;
+    let height = window.size.lines() as f64 * scale;
+
+    // Converts universe coordinates to pixels
+    let px_x = |x: f64| margin + (x - window.origin.x() as f64) * scale;
+    let px_y = |y: f64| margin + (y - window.origin.y() as f64) * scale;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+        viewBox=\"0 0 {w} {h}\">",
+        w = fmt_num(width + margin),
+        h = fmt_num(height + margin)
+    );
+
+    // Background (cells in the default state)
+    let default_cell = U::Cell::default();
+    if options.background {
+        let style = options.fill_style(&default_cell);
+        let _ = writeln!(
+            svg,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+            fmt_num(margin),
+            fmt_num(margin),
+            fmt_num(width),
+            fmt_num(height),
+            style_attributes(&style)
+        );
+    }
+
+    // Group cells by state
+    let mut states: Vec<(U::Cell, Vec<Coordinates2D>)> = vec![];
+    for y in 0..window.size.lines() {
+        for x in 0..window.size.columns() {
+            let cell = universe.get_signed(window.to_absolute(Coordinates2D(x, y)));
+            if cell == default_cell {
+                continue;
+            }
+            match states.iter_mut().find(|(c, _)| *c == cell) {
+                Some((_, cells)) => cells.push(Coordinates2D(x, y)),
+                None => states.push((cell, vec![Coordinates2D(x, y)])),
+            }
+        }
+    }
+
+    // One path per state
+    for (state, cells) in states.iter() {
+        let mut path = String::new();
+        for outline in outlines(cells) {
+            for (i, (x, y)) in outline.iter().enumerate() {
+                let _ = write!(
+                    path,
+                    "{}{} {} ",
+                    if i == 0 { "M" } else { "L" },
+                    fmt_num(margin + *x as f64 * scale),
+                    fmt_num(margin + *y as f64 * scale)
+                );
+            }
+            path.push('Z');
+        }
+        let _ = writeln!(
+            svg,
+            "  <path fill-rule=\"evenodd\" {} d=\"{}\"/>",
+            style_attributes(&options.fill_style(state)),
+            path
+        );
+    }
+
+    // Grid
+    if let Some(colour) = options.grid {
+        let _ = writeln!(
+            svg,
+            "  <g stroke=\"{}\" stroke-width=\"{}\">",
+            colour.to_hex(),
+            GRID_WIDTH
+        );
+        for x in 0..=window.size.columns() {
+            let pos = fmt_num(margin + x as f64 * scale);
+            let _ = writeln!(
+                svg,
+                "    <line x1=\"{p}\" y1=\"{}\" x2=\"{p}\" y2=\"{}\"/>",
+                fmt_num(margin),
+                fmt_num(margin + height),
+                p = pos
+            );
+        }
+        for y in 0..=window.size.lines() {
+            let pos = fmt_num(margin + y as f64 * scale);
+            let _ = writeln!(
+                svg,
+                "    <line x1=\"{}\" y1=\"{p}\" x2=\"{}\" y2=\"{p}\"/>",
+                fmt_num(margin),
+                fmt_num(margin + width),
+                p = pos
+            );
+        }
+        let _ = writeln!(svg, "  </g>");
+    }
+
+    // Coordinate axes, along the top and left sides
+    if let Some(tick_spacing) = options.axes {
+        let _ = writeln!(
+            svg,
+            "  <g stroke=\"black\" font-family=\"sans-serif\" font-size=\"{}\">",
+            FONT_SIZE
+        );
+        let _ = writeln!(
+            svg,
+            "    <line x1=\"{m}\" y1=\"{m}\" x2=\"{}\" y2=\"{m}\"/>",
+            fmt_num(margin + width),
+            m = fmt_num(margin)
+        );
+        let _ = writeln!(
+            svg,
+            "    <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{}\"/>",
+            fmt_num(margin + height),
+            m = fmt_num(margin)
+        );
+        for x in first_tick(window.origin.x(), tick_spacing)
+            ..(window.origin.x() + window.size.columns() as isize)
+        {
+            if x.rem_euclid(tick_spacing as isize) != 0 {
+                continue;
+            }
+            let pos = fmt_num(px_x(x as f64 + 0.5));
+            let _ = writeln!(
+                svg,
+                "    <line x1=\"{p}\" y1=\"{}\" x2=\"{p}\" y2=\"{}\"/>",
+                fmt_num(margin - TICK_LENGTH),
+                fmt_num(margin),
+                p = pos
+            );
+            let _ = writeln!(
+                svg,
+                "    <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" stroke=\"none\">{}</text>",
+                pos,
+                fmt_num(margin - TICK_LENGTH - 2.0),
+                x
+            );
+        }
+        for y in first_tick(window.origin.y(), tick_spacing)
+            ..(window.origin.y() + window.size.lines() as isize)
+        {
+            if y.rem_euclid(tick_spacing as isize) != 0 {
+                continue;
+            }
+            let pos = fmt_num(px_y(y as f64 + 0.5));
+            let _ = writeln!(
+                svg,
+                "    <line x1=\"{}\" y1=\"{p}\" x2=\"{}\" y2=\"{p}\"/>",
+                fmt_num(margin - TICK_LENGTH),
+                fmt_num(margin),
+                p = pos
+            );
+            let _ = writeln!(
+                svg,
+                "    <text x=\"{}\" y=\"{}\" text-anchor=\"end\" dominant-baseline=\"middle\" \
+                stroke=\"none\">{}</text>",
+                fmt_num(margin - TICK_LENGTH - 2.0),
+                pos,
+                y
+            );
+        }
+        let _ = writeln!(svg, "  </g>");
+    }
+
+    // Overlays
+    for overlay in options.overlays.iter() {
+        match overlay {
+            Overlay::BoundingBox {
+                window: bbox,
+                colour,
+            } => {
+                let _ = writeln!(
+                    svg,
+                    "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" \
+                    stroke=\"{}\" stroke-width=\"{}\"/>",
+                    fmt_num(px_x(bbox.origin.x() as f64)),
+                    fmt_num(px_y(bbox.origin.y() as f64)),
+                    fmt_num(bbox.size.columns() as f64 * scale),
+                    fmt_num(bbox.size.lines() as f64 * scale),
+                    colour.to_hex(),
+                    OVERLAY_WIDTH
+                );
+            }
+            Overlay::Arrow { from, to, colour } => {
+                let (x1, y1) = (px_x(from.0), px_y(from.1));
+                let (x2, y2) = (px_x(to.0), px_y(to.1));
+                let _ = writeln!(
+                    svg,
+                    "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" \
+                    stroke-width=\"{}\"/>",
+                    fmt_num(x1),
+                    fmt_num(y1),
+                    fmt_num(x2),
+                    fmt_num(y2),
+                    colour.to_hex(),
+                    OVERLAY_WIDTH
+                );
+
+                // Arrow head
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let norm = (dx * dx + dy * dy).sqrt();
+                if norm > 0.0 {
+                    let (ux, uy) = (dx / norm, dy / norm);
+                    let size = ARROW_HEAD_SIZE * OVERLAY_WIDTH;
+                    let (bx, by) = (x2 - ux * size, y2 - uy * size);
+                    let _ = writeln!(
+                        svg,
+                        "  <polygon points=\"{},{} {},{} {},{}\" fill=\"{}\"/>",
+                        fmt_num(x2),
+                        fmt_num(y2),
+                        fmt_num(bx - uy * size / 2.0),
+                        fmt_num(by + ux * size / 2.0),
+                        fmt_num(bx + uy * size / 2.0),
+                        fmt_num(by - ux * size / 2.0),
+                        colour.to_hex()
+                    );
+                }
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn write_svg<U: Universe2D, W: Write>(
+    universe: &U,
+    window: Window2D,
+    options: &SvgOptions<U::Cell>,
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(render_svg(universe, window, options).as_bytes())?;
+    writer.flush()
+}
+
+/// Computes the outlines of the union of a set of unit cells, as closed polygons whose vertices
+/// are cell corners. Outer boundaries run clockwise and holes counterclockwise, and collinear
+/// vertices are removed.
+fn outlines(cells: &[Coordinates2D]) -> Vec<Vec<(usize, usize)>> {
+    let set: HashSet<(usize, usize)> = cells.iter().map(|c| (c.x(), c.y())).collect();
+    let is_set = |x: isize, y: isize| x >= 0 && y >= 0 && set.contains(&(x as usize, y as usize));
+
+    // Collect boundary edges, oriented clockwise around each cell
+    let mut edges: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    let mut add_edge =
+        |from: (usize, usize), to: (usize, usize)| edges.entry(from).or_default().push(to);
+    for &(x, y) in set.iter() {
+        let (sx, sy) = (x as isize, y as isize);
+        if !is_set(sx, sy - 1) {
+            add_edge((x, y), (x + 1, y));
+        }
+        if !is_set(sx + 1, sy) {
+            add_edge((x + 1, y), (x + 1, y + 1));
+        }
+        if !is_set(sx, sy + 1) {
+            add_edge((x + 1, y + 1), (x, y + 1));
+        }
+        if !is_set(sx - 1, sy) {
+            add_edge((x, y + 1), (x, y));
+        }
+    }
+
+    // Chain edges into closed loops, starting from the smallest vertex for a stable output
+    let mut starts: Vec<(usize, usize)> = edges.keys().copied().collect();
+    starts.sort_by_key(|&(x, y)| (y, x));
+    let mut loops = vec![];
+    for start in starts {
+        while let Some(next) = edges.get_mut(&start).and_then(|e| e.pop()) {
+            let mut outline = vec![start];
+            let mut current = next;
+            while current != start {
+                outline.push(current);
+                current = edges
+                    .get_mut(&current)
+                    .and_then(|e| e.pop())
+                    .expect(ERR_OPEN_OUTLINE);
+            }
+            loops.push(remove_collinear(outline));
+        }
+    }
+    loops
+}
+
+fn remove_collinear(outline: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    let n = outline.len();
+    (0..n)
+        .filter(|&i| {
+            let prev = outline[(i + n - 1) % n];
+            let cur = outline[i];
+            let next = outline[(i + 1) % n];
+            !((prev.0 == cur.0 && cur.0 == next.0) || (prev.1 == cur.1 && cur.1 == next.1))
+        })
+        .map(|i| outline[i])
+        .collect()
+}
+
+fn style_attributes(style: &FillStyle) -> String {
+    let mut attributes = format!("fill=\"{}\"", style.fill.to_hex());
+    if style.opacity < 1.0 {
+        attributes.push_str(&format!(" fill-opacity=\"{}\"", fmt_num(style.opacity)));
+    }
+    if let Some((colour, width)) = style.stroke {
+        attributes.push_str(&format!(
+            " stroke=\"{}\" stroke-width=\"{}\"",
+            colour.to_hex(),
+            fmt_num(width)
+        ));
+    }
+    attributes
+}
+
+/// Returns the smallest coordinate greater or equal to `origin` that is a multiple of `spacing`.
+fn first_tick(origin: isize, spacing: usize) -> isize {
+    let spacing = spacing as isize;
+    origin + (spacing - origin.rem_euclid(spacing)) % spacing
+}
+
+/// Formats a number with at most 3 decimals and without trailing zeros.
+fn fmt_num(val: f64) -> String {
+    let formatted = format!("{:.3}", val);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        String::from("0")
+    } else {
+        String::from(trimmed)
+    }
+}
+
+/// Returns the window enclosing all non-default cells of a universe, as a bounding box overlay.
+pub fn bounding_box<U: Universe2D>(universe: &U, colour: Rgb) -> Overlay {
+    let bounds = universe.bounds();
+    let default_cell = U::Cell::default();
+    let mut coords = vec![];
+    for y in 0..bounds.size.lines() {
+        for x in 0..bounds.size.columns() {
+            let abs = bounds.to_absolute(Coordinates2D(x, y));
+            if universe.get_signed(abs) != default_cell {
+                coords.push(SCoordinates2D(abs.x(), abs.y()));
+            }
+        }
+    }
+    Overlay::BoundingBox {
+        window: Window2D::enclosing(coords.into_iter()),
+        colour,
+    }
+}
+
+const DEFAULT_SCALE: f64 = 10.0;
+const AXES_MARGIN: f64 = 30.0;
+const TICK_LENGTH: f64 = 4.0;
+const FONT_SIZE: usize = 10;
+const GRID_WIDTH: f64 = 0.5;
+const OVERLAY_WIDTH: f64 = 2.0;
+const ARROW_HEAD_SIZE: f64 = 4.0;
+
+const ERR_NON_POSITIVE_SCALE: &str = "The rendering scale must be strictly positive.";
+const ERR_ZERO_TICK_SPACING: &str = "The spacing between axis ticks must be strictly positive.";
+const ERR_OPEN_OUTLINE: &str = "Cell outline is not closed.";
+
+#[cfg(test)]
+mod tests {
+    use super::{bounding_box, outlines, render_svg, Overlay, SvgOptions};
+    use crate::{
+        automaton::game_of_life::{catalogue, GameOfLife},
+        export::{ColourMap, Rgb},
+        universe::grid2d::{
+            infinite_grid2d::InfiniteGrid2D, Coordinates2D, SCoordinates2D, Size2D, Universe2D,
+            Window2D,
+        },
+    };
+
+    #[test]
+    fn merged_outlines() {
+        // A block is a single square
+        let block = [
+            Coordinates2D(0, 0),
+            Coordinates2D(1, 0),
+            Coordinates2D(0, 1),
+            Coordinates2D(1, 1),
+        ];
+        assert_eq!(outlines(&block), vec![vec![(0, 0), (2, 0), (2, 2), (0, 2)]]);
+
+        // A ring of 8 cells has an outer boundary and a hole
+        let ring: Vec<Coordinates2D> = (0..3)
+            .flat_map(|y| (0..3).map(move |x| Coordinates2D(x, y)))
+            .filter(|c| *c != Coordinates2D(1, 1))
+            .collect();
+        let mut ring_outlines = outlines(&ring);
+        ring_outlines.sort_by_key(|outline| outline.len());
+        assert_eq!(ring_outlines.len(), 2);
+        assert_eq!(ring_outlines[0].len(), 4);
+        assert_eq!(ring_outlines[1].len(), 4);
+    }
+
+    #[test]
+    fn svg_document() {
+        let mut grid = InfiniteGrid2D::new(3);
+        let glider = catalogue::find("Glider").unwrap();
+        glider.place(&mut grid, SCoordinates2D(-2, -2));
+        let window = Window2D::new(SCoordinates2D(-5, -5), Size2D(10, 10));
+        let colours = ColourMap::new(Rgb(255, 255, 255)).with(GameOfLife::Alive, Rgb(0, 0, 0));
+        let options = SvgOptions::new(colours)
+            .scale(5.0)
+            .grid(Rgb(200, 200, 200))
+            .axes(5)
+            .overlay(bounding_box(&grid, Rgb(255, 0, 0)))
+            .overlay(Overlay::direction(
+                grid.bounds(),
+                glider.behavior.displacement(),
+                3.0,
+                Rgb(0, 0, 255),
+            ));
+        let svg = render_svg(&grid, window, &options);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        // Background plus a single path for all live cells
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert!(svg.contains("fill=\"#000000\""));
+        assert!(svg.contains("fill=\"#ffffff\""));
+
+        // 11 vertical and 11 horizontal grid lines, 2 axes with 2 ticks each, and the arrow
+        assert_eq!(svg.matches("<line").count(), 11 + 11 + 2 + 2 + 2 + 1);
+        assert!(svg.contains(">-5</text>") && svg.contains(">0</text>"));
+
+        // Bounding box overlay and arrow head
+        assert!(svg.contains("stroke=\"#ff0000\""));
+        assert_eq!(svg.matches("<polygon").count(), 1);
+    }
+}
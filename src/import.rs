@@ -0,0 +1,274 @@
+// Standard library
+use std::io;
+use std::path::Path;
+
+// Local
+use crate::{
+    automaton::AutomatonCell,
+    export::{image::Image, Rgb},
+    universe::grid2d::{static_grid2d::StaticGrid2D, Neighbor2D, Size2D},
+};
+
+/// StateMapping
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateMapping<C: AutomatonCell> {
+    /// Every colour in the image must be one of the palette's, other colours are reported as errors.
+    Exact(Vec<(Rgb, C)>),
+    /// Every colour is mapped to the state of the closest palette colour.
+    Nearest(Vec<(Rgb, C)>),
+    /// Colours whose luminance is strictly below the threshold are mapped to `dark`, and others to
+    /// `light`.
+    Threshold { threshold: u8, dark: C, light: C },
+}
+
+impl<C: AutomatonCell> StateMapping<C> {
+    /// Maps a (possibly dithered) colour to a state, also returning the colour that the state
+    /// stands for so that the quantization error can be diffused.
+    fn map(&self, colour: [f32; 3]) -> Option<(C, [f32; 3])> {
+        match self {
+            StateMapping::Exact(palette) => {
+                let colour = Rgb(colour[0] as u8, colour[1] as u8, colour[2] as u8);
+                palette
+                    .iter()
+                    .find(|(c, _)| *c == colour)
+                    .map(|(c, state)| (*state, to_float(*c)))
+            }
+            StateMapping::Nearest(palette) => palette
+                .iter()
+                .map(|(c, state)| (*state, to_float(*c)))
+                .min_by(|(_, c1), (_, c2)| {
+                    distance(colour, *c1)
+                        .partial_cmp(&distance(colour, *c2))
+                        .unwrap()
+                }),
+            StateMapping::Threshold {
+                threshold,
+                dark,
+                light,
+            } => {
+                if luminance(colour) < *threshold as f32 {
+                    Some((*dark, [0.0; 3]))
+                } else {
+                    Some((*light, [255.0; 3]))
+                }
+            }
+        }
+    }
+}
+
+/// Dithering
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Dithering {
+    None,
+    /// Floyd-Steinberg error diffusion, which preserves the average tone of regions whose colour
+    /// isn't in the palette. It has no effect on exact mappings.
+    FloydSteinberg,
+}
+
+/// ImportOptions
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportOptions<C: AutomatonCell> {
+    pub mapping: StateMapping<C>,
+    pub dithering: Dithering,
+    /// Width and height of a single cell, in pixels. Each cell takes the colour of the center
+    /// pixel of its block.
+    pub scale: usize,
+}
+
+impl<C: AutomatonCell> ImportOptions<C> {
+    pub fn new(mapping: StateMapping<C>) -> Self {
+        Self {
+            mapping,
+            dithering: Dithering::None,
+            scale: 1,
+        }
+    }
+
+    pub fn exact(palette: Vec<(Rgb, C)>) -> Self {
+        Self::new(StateMapping::Exact(palette))
+    }
+
+    pub fn nearest(palette: Vec<(Rgb, C)>) -> Self {
+        if palette.is_empty() {
+            panic!("{}", ERR_EMPTY_PALETTE);
+        }
+        Self::new(StateMapping::Nearest(palette))
+    }
+
+    pub fn threshold(threshold: u8, dark: C, light: C) -> Self {
+        Self::new(StateMapping::Threshold {
+            threshold,
+            dark,
+            light,
+        })
+    }
+
+    pub fn dithering(mut self, dithering: Dithering) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    pub fn scale(mut self, scale: usize) -> Self {
+        if scale == 0 {
+            panic!("{}", ERR_ZERO_SCALE);
+        }
+        self.scale = scale;
+        self
+    }
+}
+
+/// Builds a grid from an image. Incomplete blocks of pixels on the right and bottom edges are
+/// ignored when the image's dimensions aren't multiples of the scale.
+pub fn grid_from_image<C: AutomatonCell<Neighbor = Neighbor2D>>(
+    image: &Image,
+    options: &ImportOptions<C>,
+) -> io::Result<StaticGrid2D<C>> {
+    let scale = options.scale;
+    let size = Size2D(image.width() / scale, image.height() / scale);
+
+    // Sample the center of each block
+    let mut colours: Vec<[f32; 3]> = Vec::with_capacity(size.total());
+    for y in 0..size.lines() {
+        for x in 0..size.columns() {
+            let colour = to_float(image.pixel(x * scale + scale / 2, y * scale + scale / 2));
+            colours.push(match options.mapping {
+                StateMapping::Threshold { .. } => [luminance(colour); 3],
+                _ => colour,
+            });
+        }
+    }
+
+    let dither = match options.mapping {
+        StateMapping::Exact(_) => false,
+        _ => options.dithering == Dithering::FloydSteinberg,
+    };
+    let mut data = Vec::with_capacity(size.total());
+    for y in 0..size.lines() {
+        for x in 0..size.columns() {
+            let idx = x + y * size.columns();
+            let colour = colours[idx];
+            let (state, quantized) = options
+                .mapping
+                .map(colour)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, ERR_UNKNOWN_COLOUR))?;
+            data.push(state);
+
+            if dither {
+                let error = [
+                    colour[0] - quantized[0],
+                    colour[1] - quantized[1],
+                    colour[2] - quantized[2],
+                ];
+                let mut diffuse = |dx: isize, dy: usize, weight: f32| {
+                    let nx = x as isize + dx;
+                    let ny = y + dy;
+                    if nx >= 0 && (nx as usize) < size.columns() && ny < size.lines() {
+                        let target = &mut colours[nx as usize + ny * size.columns()];
+                        for (channel, err) in target.iter_mut().zip(error.iter()) {
+                            *channel += err * weight;
+                        }
+                    }
+                };
+                diffuse(1, 0, 7.0 / 16.0);
+                diffuse(-1, 1, 3.0 / 16.0);
+                diffuse(0, 1, 5.0 / 16.0);
+                diffuse(1, 1, 1.0 / 16.0);
+            }
+        }
+    }
+    Ok(StaticGrid2D::new(data, size))
+}
+
+/// Builds a grid from a PNG or PPM file.
+pub fn load_grid<C: AutomatonCell<Neighbor = Neighbor2D>, P: AsRef<Path>>(
+    path: P,
+    options: &ImportOptions<C>,
+) -> io::Result<StaticGrid2D<C>> {
+    grid_from_image(&Image::open(path)?, options)
+}
+
+#[inline]
+fn to_float(colour: Rgb) -> [f32; 3] {
+    [colour.r() as f32, colour.g() as f32, colour.b() as f32]
+}
+
+#[inline]
+fn luminance(colour: [f32; 3]) -> f32 {
+    0.299 * colour[0] + 0.587 * colour[1] + 0.114 * colour[2]
+}
+
+#[inline]
+fn distance(c1: [f32; 3], c2: [f32; 3]) -> f32 {
+    c1.iter()
+        .zip(c2.iter())
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum()
+}
+
+const ERR_EMPTY_PALETTE: &str = "The palette must contain at least one colour.";
+const ERR_ZERO_SCALE: &str = "The import scale must be strictly positive.";
+const ERR_UNKNOWN_COLOUR: &str = "The image contains a colour that isn't in the palette.";
+
+#[cfg(test)]
+mod tests {
+    use super::{grid_from_image, load_grid, Dithering, ImportOptions};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        export::{
+            image::{render, Image, ImageFormat, RenderOptions},
+            ColourMap, Rgb,
+        },
+        universe::{
+            grid2d::{Coordinates2D, Universe2D},
+            Universe,
+        },
+    };
+
+    #[test]
+    fn exact_round_trip() {
+        let gun = game_of_life::gosper_glider_gun();
+        let colours = ColourMap::new(Rgb(255, 255, 255)).with(GameOfLife::Alive, Rgb(0, 0, 0));
+        let image = render(&gun, gun.bounds(), &RenderOptions::new(colours).scale(3));
+        let path = std::env::temp_dir().join("cell_import_round_trip.png");
+        image.save(&path, ImageFormat::Png).unwrap();
+
+        let palette = vec![
+            (Rgb(255, 255, 255), GameOfLife::Dead),
+            (Rgb(0, 0, 0), GameOfLife::Alive),
+        ];
+        let grid = load_grid(&path, &ImportOptions::exact(palette.clone()).scale(3)).unwrap();
+        assert_eq!(grid.size(), gun.size());
+        assert!(gun.iter().flatten().eq(grid.iter().flatten()));
+
+        // Colours outside the palette are rejected, unless they are mapped to their nearest match
+        let mut image = Image::new(2, 1, Rgb(255, 255, 255));
+        image.set_pixel(1, 0, Rgb(50, 40, 30));
+        assert!(grid_from_image(&image, &ImportOptions::exact(palette.clone())).is_err());
+        let grid = grid_from_image(&image, &ImportOptions::nearest(palette)).unwrap();
+        assert_eq!(grid.get(Coordinates2D(0, 0)), GameOfLife::Dead);
+        assert_eq!(grid.get(Coordinates2D(1, 0)), GameOfLife::Alive);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn threshold_dithering() {
+        let image = Image::new(20, 20, Rgb(128, 128, 128));
+        let options = ImportOptions::threshold(100, GameOfLife::Alive, GameOfLife::Dead);
+        let count_alive = |options: &ImportOptions<GameOfLife>| {
+            grid_from_image(&image, options)
+                .unwrap()
+                .iter()
+                .flatten()
+                .filter(|(_, c)| *c == GameOfLife::Alive)
+                .count()
+        };
+
+        // Mid-grey is above the threshold, but dithering yields about half dark cells
+        assert_eq!(count_alive(&options), 0);
+        let alive = count_alive(&options.dithering(Dithering::FloydSteinberg));
+        assert!(alive > 180 && alive < 220);
+    }
+}
@@ -2,6 +2,8 @@
 pub mod advanced_channels;
 pub mod automaton;
 pub mod commands;
+pub mod export;
+pub mod import;
 pub mod simulator;
 pub mod universe;
 
@@ -83,11 +85,23 @@ compile_automaton_shaders! {
 mod tests {
 
     use game_of_life::GameOfLife;
+    use std::task::Poll;
 
     use crate::{
         automaton::game_of_life,
-        simulator::{AsyncSimulator, Simulator, SyncSimulator},
-        universe::grid2d::static_grid2d::{GridDiff, StaticGrid2D},
+        simulator::{
+            AsyncSimulator, BranchId, BranchInfo, CycleMode, EvictionPolicy, GenerationUpdate,
+            HistoryConfig, MemoryBudget, OverflowPolicy, Simulator, SimulatorEvent,
+            SimulatorSnapshot, StopConditions, StopReason, Subscription, SyncSimulator,
+            DEFAULT_MAX_GENERATIONS,
+        },
+        universe::{
+            grid2d::{
+                static_grid2d::{GridDiff, StaticGrid2D},
+                Coordinates2D, Difference2D,
+            },
+            GenerationDifference, Storable, Universe,
+        },
     };
 
     #[test]
@@ -165,4 +179,483 @@ mod tests {
         let penta_decathlon = simulator.get_generation(210).unwrap();
         assert!(game_of_life::is_penta_decathlon(&penta_decathlon));
     }
+
+    #[test]
+    fn cycle_events() {
+        let mut sync_simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        let events = sync_simulator.events();
+        sync_simulator.detect_cycles(CycleMode::Exact);
+        sync_simulator.run(40);
+        let cycle = sync_simulator.cycle().unwrap();
+        assert_eq!((cycle.transient, cycle.period), (0, 15));
+        assert_eq!(events.try_recv(), Ok(SimulatorEvent::CycleDetected(cycle)));
+        assert!(events.try_recv().is_err());
+
+        let mut async_simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        let events = async_simulator.events();
+        async_simulator.detect_cycles(CycleMode::Exact);
+        async_simulator.run(40);
+        assert_eq!(events.recv(), Ok(SimulatorEvent::CycleDetected(cycle)));
+    }
+
+    #[test]
+    fn statistics() {
+        let mut sync_simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::r_pentomino(), 10);
+        sync_simulator.run(5);
+        sync_simulator.collect_statistics();
+        sync_simulator.run(30);
+        sync_simulator.edit(&[(Coordinates2D(0, 0), GameOfLife::Alive)]);
+        let series = sync_simulator.statistics();
+        assert_eq!(series.len(), 31);
+        assert_eq!(series[0].generation, 5);
+        for (stats, next) in series.iter().zip(series[1..].iter()) {
+            assert_eq!(
+                next.live_cells(),
+                stats.live_cells() + next.births - next.deaths + (next.generation == 35) as usize
+            );
+        }
+
+        let mut async_simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::r_pentomino(), 10);
+        async_simulator.run(5);
+        async_simulator.collect_statistics();
+        async_simulator.run(30);
+        async_simulator.edit(&[(Coordinates2D(0, 0), GameOfLife::Alive)]);
+        assert_eq!(async_simulator.statistics(), series);
+    }
+
+    #[test]
+    fn run_until() {
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::blinker(), 10);
+
+        // The blinker never becomes a still life, so the predicate fires first
+        let stop = simulator.run_until(
+            StopConditions::new()
+                .stabilisation(1)
+                .when(|gen, _| gen == 5),
+        );
+        assert_eq!(
+            (stop.reason, stop.generation),
+            (StopReason::Predicate(0), 5)
+        );
+        assert_eq!(simulator.get_highest_generation(), 5);
+
+        let stop = simulator.run_until(StopConditions::new().stabilisation(2).extinction());
+        assert_eq!(stop.generation, 7);
+        match stop.reason {
+            StopReason::Stabilisation(cycle) => assert_eq!(cycle.period, 2),
+            reason => panic!("Unexpected stop reason {:?}", reason),
+        }
+
+        // The asynchronous runner must stop exactly at the reported generation
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        simulator.run(3);
+        let stop = simulator.run_until(StopConditions::new().max_generations(10).extinction());
+        assert_eq!(
+            (stop.reason, stop.generation),
+            (StopReason::GenerationLimit, 13)
+        );
+        simulator.run(2);
+        let penta_decathlon = simulator.get_generation(15).unwrap();
+        assert!(game_of_life::is_penta_decathlon(&penta_decathlon));
+
+        // Runs without any condition still end
+        let limit = (StopReason::GenerationLimit, 15 + DEFAULT_MAX_GENERATIONS);
+        let stop = simulator.run_until(StopConditions::new());
+        assert_eq!((stop.reason, stop.generation), limit);
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        simulator.run(15);
+        let stop = simulator.run_until(StopConditions::new());
+        assert_eq!((stop.reason, stop.generation), limit);
+    }
+
+    #[test]
+    fn async_controls() {
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+
+        // Nothing is computed while paused
+        simulator.pause();
+        simulator.run(1_000_000);
+        assert_eq!(simulator.generations_completed(), 0);
+        assert_eq!(simulator.requested_generation(), 1_000_000);
+
+        // Cancel a run that was started with the wrong count
+        simulator.resume();
+        assert!(simulator.get_generation(30).is_some());
+        let gen = simulator.cancel();
+        assert!((30..1_000_000).contains(&gen));
+        assert_eq!(simulator.get_highest_generation(), gen);
+        assert_eq!(simulator.generations_completed(), gen);
+        assert!(simulator.get_generation(gen + 1).is_none());
+
+        // The simulation resumes from where it was cancelled
+        simulator.run(15 - gen % 15);
+        assert_eq!(simulator.wait_for_runs(), gen + 15 - gen % 15);
+        assert_eq!(simulator.generations_completed(), gen + 15 - gen % 15);
+        let penta_decathlon = simulator.get_generation(gen + 15 - gen % 15).unwrap();
+        assert!(game_of_life::is_penta_decathlon(&penta_decathlon));
+    }
+
+    #[test]
+    fn requests_while_paused() {
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::blinker(), 10);
+
+        // Queries and edits are answered at the generation reached, without lifting the pause
+        simulator.pause();
+        simulator.collect_statistics();
+        simulator.run(5);
+        assert_eq!(simulator.get_highest_generation(), 5);
+        simulator.edit(&[(Coordinates2D(0, 0), GameOfLife::Alive)]);
+        let statistics = simulator.statistics();
+        assert!(statistics.iter().all(|stats| stats.generation == 0));
+        assert!(simulator.controls().is_paused());
+        assert_eq!(simulator.generations_completed(), 0);
+        assert!(simulator.try_get_generation(1).is_none());
+        let edited = simulator.get_generation(0).unwrap();
+        assert_eq!(edited.get(Coordinates2D(0, 0)), GameOfLife::Alive);
+
+        // Pending runs continue from the edited universe once resumed
+        simulator.resume();
+        assert_eq!(simulator.wait_for_runs(), 5);
+        let statistics = simulator.statistics();
+        assert!(statistics.iter().map(|stats| stats.generation).eq(0..=5));
+        let universe = simulator.get_generation(1).unwrap();
+        assert_eq!(universe.get(Coordinates2D(0, 0)), GameOfLife::Dead);
+
+        // Runs requested after pausing again stay paused until they are cancelled
+        simulator.pause();
+        simulator.run(5);
+        assert_eq!(simulator.get_highest_generation(), 10);
+        assert!(simulator.controls().is_paused());
+        assert!(simulator.try_get_generation(6).is_none());
+        assert_eq!(simulator.cancel(), 5);
+        assert!(simulator.get_generation(6).is_none());
+    }
+
+    #[test]
+    fn generation_handles() {
+        // Minimal executor parking the current thread until the future is woken up
+        struct ThreadWaker(std::thread::Thread);
+        impl std::task::Wake for ThreadWaker {
+            fn wake(self: std::sync::Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        fn block_on<F: std::future::Future + Unpin>(mut future: F) -> F::Output {
+            let waker = std::sync::Arc::new(ThreadWaker(std::thread::current())).into();
+            let mut cx = std::task::Context::from_waker(&waker);
+            loop {
+                match std::pin::Pin::new(&mut future).poll(&mut cx) {
+                    std::task::Poll::Ready(output) => return output,
+                    std::task::Poll::Pending => std::thread::park(),
+                }
+            }
+        }
+
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::blinker(), 10);
+        simulator.pause();
+        simulator.run(20);
+        assert!(simulator.try_get_generation(5).is_none());
+        let mut handle = simulator.generation_handle(5);
+        assert!(handle.poll_generation().is_pending());
+        let mut never = simulator.generation_handle(21);
+        assert!(matches!(never.poll_generation(), Poll::Ready(None)));
+
+        simulator.resume();
+        let blinker = block_on(handle).unwrap();
+        assert!(game_of_life::is_blinker(&blinker, true));
+        assert!(simulator.try_get_generation(5).is_some());
+
+        // Handles resolve to nothing when the run they were waiting on is cancelled
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::blinker(), 10);
+        simulator.pause();
+        simulator.run(20);
+        let cancelled = simulator.generation_handle(15);
+        simulator.cancel();
+        assert!(block_on(cancelled).is_none());
+
+        // Cancelling from another thread's controls, handles resolve according to where the runner
+        // actually stopped
+        simulator.run(1_000_000);
+        let before = simulator.generation_handle(999_999);
+        assert!(block_on(simulator.generation_handle(30)).is_some());
+        let controls = simulator.controls();
+        std::thread::spawn(move || controls.cancel())
+            .join()
+            .unwrap();
+        let after = simulator.generation_handle(999_999);
+        assert!(block_on(before).is_none());
+        assert!(block_on(after).is_none());
+        let gen = simulator.get_highest_generation();
+        assert!((30..1_000_000).contains(&gen));
+        assert!(simulator.try_get_generation(gen).is_some());
+        assert!(block_on(simulator.generation_handle(gen)).is_some());
+
+        // Differences backwards in time are refused without taking the history down
+        assert!(simulator.try_get_difference(20, 10).is_none());
+        assert!(simulator.get_difference(20, 10).is_none());
+        assert!(simulator.try_get_difference(10, 20).is_some());
+    }
+
+    #[test]
+    fn subscriptions() {
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        let universes = simulator.subscribe(Subscription::universes().every(5));
+        let diffs = simulator.subscribe(Subscription::differences());
+        let dropping = simulator.subscribe(
+            Subscription::universes()
+                .capacity(2)
+                .overflow(OverflowPolicy::Drop),
+        );
+        simulator.run(30);
+
+        let gens: Vec<usize> = universes.try_iter().map(|u| u.generation()).collect();
+        assert_eq!(gens, vec![5, 10, 15, 20, 25, 30]);
+        let gens: Vec<usize> = dropping.try_iter().map(|u| u.generation()).collect();
+        assert_eq!(gens, vec![1, 2]);
+
+        // Differences can be chained to follow the simulation
+        let mut universe = game_of_life::penta_decathlon();
+        let mut last_gen = 0;
+        for update in diffs.try_iter() {
+            match update {
+                GenerationUpdate::Difference { ref_gen, gen, diff } => {
+                    assert_eq!(ref_gen, last_gen);
+                    universe = diff.apply_to(universe);
+                    last_gen = gen;
+                }
+                _ => panic!("Expected a difference"),
+            }
+        }
+        assert_eq!(last_gen, 30);
+        assert!(game_of_life::is_penta_decathlon(&universe));
+
+        // Edits are delivered as differences as well
+        simulator.edit(&[(Coordinates2D(0, 0), GameOfLife::Alive)]);
+        simulator.run(2);
+        for update in diffs.try_iter() {
+            if let GenerationUpdate::Difference { ref_gen, gen, diff } = update {
+                assert_eq!(ref_gen, last_gen);
+                universe = diff.apply_to(universe);
+                last_gen = gen;
+            }
+        }
+        assert_eq!(last_gen, 32);
+        let expected = simulator.get_generation(32).unwrap();
+        let diff = GridDiff::get_diff(&expected, &universe);
+        assert!(diff.modified_coords(&expected).is_empty());
+        assert!(std::panic::catch_unwind(|| Subscription::differences().capacity(0)).is_err());
+
+        // Dropped differences are merged into the next delivered one
+        let mut simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend(game_of_life::penta_decathlon(), 10);
+        let diffs = simulator.subscribe(
+            Subscription::differences()
+                .every(3)
+                .capacity(1)
+                .overflow(OverflowPolicy::Drop),
+        );
+        simulator.run(30);
+        let mut universe = simulator.get_generation(0).unwrap();
+        assert!(simulator.get_generation(30).is_some());
+        let mut last_gen = 0;
+        for update in diffs.try_iter() {
+            if let GenerationUpdate::Difference { ref_gen, gen, diff } = update {
+                assert_eq!(ref_gen, last_gen);
+                assert_eq!(gen % 3, 0);
+                universe = diff.apply_to(universe);
+                last_gen = gen;
+            }
+        }
+        assert!(0 < last_gen);
+        let expected = simulator.get_generation(last_gen).unwrap();
+        let diff = GridDiff::get_diff(&expected, &universe);
+        assert!(diff.modified_coords(&expected).is_empty());
+    }
+
+    #[test]
+    fn branches() {
+        fn check_branches<S>(mut simulator: S)
+        where
+            S: Simulator<Universe = StaticGrid2D<GameOfLife>, Diff = GridDiff<GameOfLife>>,
+        {
+            let is_empty = |grid: &StaticGrid2D<GameOfLife>| {
+                grid.iter()
+                    .flatten()
+                    .all(|(_, cell)| cell == GameOfLife::Dead)
+            };
+            simulator.run(30);
+            assert!(simulator.get_generation(30).is_some());
+
+            // Cutting the flipped blinker short kills it on the new branch only
+            let branch = simulator
+                .fork_at(13, &[(Coordinates2D(2, 1), GameOfLife::Dead)])
+                .unwrap();
+            assert_eq!(simulator.current_branch(), branch);
+            assert_eq!(simulator.get_highest_generation(), 13);
+            simulator.run(5);
+            for gen in 0..13 {
+                let blinker = simulator.get_generation(gen).unwrap();
+                assert!(game_of_life::is_blinker(&blinker, gen % 2 == 1));
+            }
+            let diff = simulator.get_difference(10, 14).unwrap();
+            assert!(is_empty(&diff.apply_to(game_of_life::blinker())));
+            assert!(is_empty(&simulator.get_generation(18).unwrap()));
+            assert!(simulator.get_generation(19).is_none());
+            assert_eq!(
+                simulator.branches(),
+                vec![
+                    BranchInfo {
+                        id: BranchId::ROOT,
+                        parent: None,
+                        fork_gen: 0,
+                        highest_generation: 30,
+                    },
+                    BranchInfo {
+                        id: branch,
+                        parent: Some(BranchId::ROOT),
+                        fork_gen: 13,
+                        highest_generation: 18,
+                    },
+                ]
+            );
+
+            // The original timeline is untouched and can be continued
+            assert!(!simulator.switch_branch(BranchId(5)));
+            assert!(simulator.switch_branch(BranchId::ROOT));
+            assert_eq!(simulator.get_highest_generation(), 30);
+            simulator.run(2);
+            let blinker = simulator.get_generation(32).unwrap();
+            assert!(game_of_life::is_blinker(&blinker, false));
+
+            // Forking at a checkpoint without edits replays the same generations
+            simulator.fork_at(10, &[]).unwrap();
+            simulator.run(3);
+            let diff = simulator.get_difference(5, 13).unwrap();
+            let blinker = diff.apply_to(simulator.get_generation(5).unwrap());
+            assert!(game_of_life::is_blinker(&blinker, true));
+            assert_eq!(simulator.branches().len(), 3);
+        }
+
+        check_branches(SyncSimulator::cpu_backend(game_of_life::blinker(), 10));
+        check_branches(AsyncSimulator::cpu_backend(game_of_life::blinker(), 10));
+    }
+
+    #[test]
+    fn interventions() {
+        fn check_interventions<S>(mut simulator: S)
+        where
+            S: Simulator<Universe = StaticGrid2D<GameOfLife>, Diff = GridDiff<GameOfLife>>,
+        {
+            let is_empty = |grid: &StaticGrid2D<GameOfLife>| {
+                grid.iter()
+                    .flatten()
+                    .all(|(_, cell)| cell == GameOfLife::Dead)
+            };
+
+            // Cutting the blinker short at a checkpoint kills it
+            simulator.run(6);
+            simulator.edit(&[(Coordinates2D(1, 2), GameOfLife::Dead)]);
+            simulator.run(2);
+            let edited = simulator.get_generation(6).unwrap();
+            assert!(!game_of_life::is_blinker(&edited, false) && !is_empty(&edited));
+            assert!(is_empty(&simulator.get_generation(7).unwrap()));
+            let diff = simulator.get_difference(5, 7).unwrap();
+            assert!(is_empty(
+                &diff.apply_to(simulator.get_generation(5).unwrap())
+            ));
+            let diff = simulator.get_difference(6, 7).unwrap();
+            assert!(is_empty(&diff.apply_to(edited)));
+
+            // Bring it back to life between checkpoints
+            simulator.edit(&[
+                (Coordinates2D(1, 2), GameOfLife::Alive),
+                (Coordinates2D(2, 2), GameOfLife::Alive),
+                (Coordinates2D(3, 2), GameOfLife::Alive),
+            ]);
+            simulator.run(1);
+            let blinker = simulator.get_generation(8).unwrap();
+            assert!(game_of_life::is_blinker(&blinker, false));
+            let diff = simulator.get_difference(7, 9).unwrap();
+            let blinker = diff.apply_to(simulator.get_generation(7).unwrap());
+            assert!(game_of_life::is_blinker(&blinker, true));
+        }
+
+        let mut simulator = SyncSimulator::cpu_backend(game_of_life::blinker(), 3);
+        let events = simulator.events();
+        check_interventions(simulator);
+        let interventions: Vec<_> = events.try_iter().collect();
+        assert_eq!(
+            interventions,
+            vec![
+                SimulatorEvent::Intervention(6),
+                SimulatorEvent::Intervention(8)
+            ]
+        );
+        check_interventions(AsyncSimulator::cpu_backend(game_of_life::blinker(), 3));
+    }
+
+    #[test]
+    fn snapshots() {
+        fn check_resumed<S>(mut simulator: S, branch: BranchId)
+        where
+            S: Simulator<Universe = StaticGrid2D<GameOfLife>, Diff = GridDiff<GameOfLife>>,
+        {
+            assert_eq!(simulator.get_highest_generation(), 20);
+            assert_eq!(simulator.current_branch(), BranchId::ROOT);
+            simulator.run(3);
+            for gen in 0..24 {
+                let blinker = simulator.get_generation(gen).unwrap();
+                assert!(game_of_life::is_blinker(&blinker, gen % 2 == 1));
+            }
+            assert!(simulator.switch_branch(branch));
+            assert_eq!(simulator.get_highest_generation(), 13);
+            let killed = simulator.get_generation(13).unwrap();
+            assert!(killed
+                .iter()
+                .flatten()
+                .all(|(_, cell)| cell == GameOfLife::Dead));
+        }
+
+        // Spilled segments end up in the snapshot too
+        let budget = MemoryBudget::new(1, EvictionPolicy::SpillToDisk);
+        let mut simulator: SyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(
+                game_of_life::blinker(),
+                HistoryConfig::new(4).memory_budget(budget),
+            );
+        simulator.run(20);
+        let branch = simulator
+            .fork_at(9, &[(Coordinates2D(2, 1), GameOfLife::Dead)])
+            .unwrap();
+        simulator.run(4);
+        simulator.switch_branch(BranchId::ROOT);
+
+        let mut bytes = vec![];
+        simulator.snapshot().store(&mut bytes).unwrap();
+        drop(simulator);
+        let snapshot = SimulatorSnapshot::load(&mut &bytes[..]).unwrap();
+        assert_eq!(snapshot.highest_generation(), 20);
+        assert_eq!(snapshot.branches().len(), 2);
+        check_resumed(SyncSimulator::cpu_backend_from(snapshot), branch);
+
+        // Snapshots of an asynchronous simulator hold the generations computed so far
+        let snapshot = SimulatorSnapshot::load(&mut &bytes[..]).unwrap();
+        let simulator: AsyncSimulator<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>> =
+            AsyncSimulator::cpu_backend_from(snapshot);
+        let snapshot = simulator.snapshot();
+        assert_eq!(snapshot.highest_generation(), 20);
+        check_resumed(AsyncSimulator::cpu_backend_from(snapshot), branch);
+    }
 }
@@ -1,21 +1,83 @@
 // Local
 mod async_simulator;
+mod batch;
+mod branch;
+mod builder;
+mod cycle_detector;
+mod history_file;
+mod journal;
+mod snapshot;
+mod statistics;
+mod stop_condition;
+mod subscription;
 mod sync_simulator;
 mod universe_history;
-use crate::universe::Universe;
-pub use async_simulator::AsyncSimulator;
+use crate::universe::{GenerationDifference, Universe};
+pub use async_simulator::{AsyncSimulator, GenerationHandle, RunControl};
+pub use batch::{Batch, BatchResults, Job, JobOutcome, JobStart, JobSummary};
+pub use branch::{BranchId, BranchInfo};
+pub use builder::{Backend, BoxedSimulator, Execution, SimulatorBuilder};
+pub use cycle_detector::{Cycle, CycleDetector, CycleMode};
+pub use history_file::HistoryFile;
+pub use journal::{
+    Divergence, GenerationHash, Journal, JournalEntry, JournalRecorder, ReplayError, TILE_SIZE,
+};
+pub use snapshot::SimulatorSnapshot;
+pub use statistics::{GenerationStats, StatisticsCollector};
+pub use stop_condition::{Stop, StopConditions, StopReason, DEFAULT_MAX_GENERATIONS};
+pub use subscription::{GenerationUpdate, OverflowPolicy, Subscription};
 pub use sync_simulator::SyncSimulator;
 use universe_history::UniverseHistory;
+pub use universe_history::{AdaptiveCheckpoints, EvictionPolicy, HistoryConfig, MemoryBudget};
+
+/// New state for the cell at some coordinates.
+pub type CellEdit<U> = (<U as Universe>::Coordinates, <U as Universe>::Cell);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SimulatorEvent {
+    /// The universe repeated a prior state for the first time.
+    CycleDetected(Cycle),
+    /// Cells of the given generation were edited.
+    Intervention(usize),
+}
 
 pub trait Simulator {
     type Universe: Universe;
+    type Diff: GenerationDifference<Universe = Self::Universe>;
 
     fn run(&mut self, n_gens: usize);
 
+    /// Runs the simulation until one of the conditions holds, and returns which one did and at
+    /// what generation. The conditions are also checked against the current generation. Runs
+    /// without a generation limit stop after `DEFAULT_MAX_GENERATIONS` generations.
+    fn run_until(&mut self, conditions: StopConditions<Self::Universe>) -> Stop;
+
+    /// Returns the highest generation requested so far, which an asynchronous simulator may not
+    /// have computed yet.
     fn get_highest_generation(&self) -> usize;
 
     fn get_generation(&self, gen: usize) -> Option<Self::Universe>;
 
+    fn get_difference(&self, ref_gen: usize, target_gen: usize) -> Option<Self::Diff>;
+
+    /// Edits cells of the highest generation, once it is computed. The edit is recorded in the
+    /// history as an intervention, separately from the evolution's differences, and the simulation
+    /// continues from the edited universe. Cycle detection starts over from there.
+    fn edit(&mut self, edits: &[CellEdit<Self::Universe>]);
+
+    /// Creates a branch that shares the current branch's history up to generation `gen`, where
+    /// the given cells are modified, and switches to it. Pending runs are cancelled once that
+    /// generation is available. Returns `None` if it never will be.
+    fn fork_at(&mut self, gen: usize, edits: &[CellEdit<Self::Universe>]) -> Option<BranchId>;
+
+    fn branches(&self) -> Vec<BranchInfo>;
+
+    fn current_branch(&self) -> BranchId;
+
+    /// Continues the simulation on another branch, from its highest generation. Pending runs are
+    /// cancelled. Returns false if there is no such branch.
+    fn switch_branch(&mut self, id: BranchId) -> bool;
+
     fn goto(&mut self, target_gen: usize) {
         let max_gen = self.get_highest_generation();
         if target_gen > max_gen {
@@ -1,26 +1,281 @@
 // Standard library
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::hash::Hash;
+use std::mem;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, RecvError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
 use std::thread;
 
 // Local
+mod pipeline;
 use super::{
-    universe_history::{HistoryRequest, HistoryResponse, UniverseHistory},
-    Simulator,
+    subscription::BoxedObserver,
+    universe_history::{HistoryConfig, HistoryRequest, HistoryResponse, UniverseHistory},
+    BranchId, BranchInfo, CellEdit, CycleDetector, CycleMode, GenerationStats, GenerationUpdate,
+    Simulator, SimulatorEvent, SimulatorSnapshot, StatisticsCollector, Stop, StopConditions,
+    StopReason, Subscription,
 };
 use crate::{
     advanced_channels::{
-        oneway_channel, twoway_channel, MasterEndpoint, SimpleSender, TransmittingEnd,
+        oneway_channel, twoway_channel, MasterEndpoint, SimpleReceiver, SimpleSender,
+        SlaveEndpoint, ThirdPartySender, TransmittingEnd,
     },
     automaton::{CPUCell, GPUCell},
-    universe::{CPUUniverse, GPUUniverse, GenerationDifference, Universe},
+    universe::{
+        grid2d::{Difference2D, Universe2D},
+        CPUDiffUniverse, GPUUniverse, GenerationDifference, ParallelCPUUniverse, Storable,
+        Universe,
+    },
 };
+use pipeline::HistoryPipeline;
+pub(super) use pipeline::QueueSlot;
 
 pub struct AsyncSimulator<U: Universe, D: GenerationDifference<Universe = U>> {
-    runner_comm: SimpleSender<usize>,
-    history_comm: MasterEndpoint<HistoryRequest<U>, HistoryResponse<U, D>>,
-    max_gen: usize,
+    runner_comm: SimpleSender<RunnerRequest<U, D>>,
+    history_comm: MasterEndpoint<HistoryRequest<U, D>, HistoryResponse<U, D>>,
+    control: RunControl,
+    /// Highest generation requested so far, which may not be computed yet.
+    requested_gen: Cell<usize>,
+    /// Cancellation epoch `requested_gen` is up-to-date with.
+    epoch: Cell<usize>,
 }
 
 impl<U: Universe, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D> {
+    fn new(
+        start_universe: U,
+        config: impl Into<HistoryConfig<U, D>>,
+        evolve_fn: EvolveFn<U, D>,
+    ) -> Self {
+        let config = config.into();
+        Self::with_history(start_universe.clone(), 0, evolve_fn, |endpoint| {
+            UniverseHistory::spawn(start_universe, config, endpoint)
+        })
+    }
+
+    /// Resumes from the highest generation of the snapshot's active branch.
+    fn from_snapshot(snapshot: SimulatorSnapshot<U, D>, evolve_fn: EvolveFn<U, D>) -> Self
+    where
+        U: Storable,
+        D: Storable,
+    {
+        let gen = snapshot.highest_generation();
+        let universe = snapshot.branches[snapshot.current.0].1.last_universe();
+        Self::with_history(universe, gen, evolve_fn, |endpoint| {
+            UniverseHistory::spawn_restored(snapshot, endpoint)
+        })
+    }
+
+    /// Starts the runner from a universe at some generation, after spawning a history thread
+    /// whose generations go up to that one.
+    fn with_history(
+        universe: U,
+        gen: usize,
+        evolve_fn: EvolveFn<U, D>,
+        spawn_history: impl FnOnce(SlaveEndpoint<HistoryResponse<U, D>, HistoryRequest<U, D>>),
+    ) -> Self {
+        // Create communication channels
+        let (runner_op_sender, runner_op_receiver) = oneway_channel();
+        let (history_master, history_slave) = twoway_channel();
+        let history_data_sender = history_master.create_third_party();
+        let control = RunControl::new();
+        control.update(|status| {
+            status.completed = gen;
+            status.reached = gen;
+        });
+
+        // Start a thread to manage the universe's history
+        spawn_history(history_slave);
+
+        // Start a thread to handle run commands
+        let pipeline = HistoryPipeline::new(universe.clone(), history_data_sender, control.clone());
+        let mut runner = Runner::new(
+            universe,
+            pipeline,
+            evolve_fn,
+            control.clone(),
+            runner_op_receiver,
+        );
+        runner.gen = gen;
+        thread::spawn(move || runner.serve());
+
+        Self {
+            runner_comm: runner_op_sender,
+            history_comm: history_master,
+            control,
+            requested_gen: Cell::new(gen),
+            epoch: Cell::new(0),
+        }
+    }
+
+    /// Captures the state of the simulator once the generations computed so far are in the
+    /// history. Pending runs aren't part of the snapshot, so a simulator resumed from it continues
+    /// from the last generation computed.
+    pub fn snapshot(&self) -> SimulatorSnapshot<U, D> {
+        match self
+            .history_comm
+            .send_and_wait_for_response(HistoryRequest::Snapshot)
+        {
+            HistoryResponse::Snapshot(snapshot) => snapshot,
+            _ => panic!("{}", ERR_INCORRECT_RESPONSE),
+        }
+    }
+
+    /// Returns a handle to control runs from any thread (e.g., a UI thread).
+    pub fn controls(&self) -> RunControl {
+        self.control.clone()
+    }
+
+    /// Pauses the runner thread after the generation it is currently computing. Edits and
+    /// statistics are still answered while paused, at the generation reached so far.
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Cancels all pending runs and returns the generation the simulation stopped at, which becomes
+    /// the highest generation, once the history caught up with it.
+    pub fn cancel(&mut self) -> usize {
+        self.control.cancel();
+        self.wait_for_runs()
+    }
+
+    /// Returns the number of generations computed so far, without blocking.
+    pub fn generations_completed(&self) -> usize {
+        self.control.generations_completed()
+    }
+
+    /// Returns the highest generation requested so far, which may not be computed yet.
+    pub fn requested_generation(&self) -> usize {
+        self.sync_with_runner();
+        self.requested_gen.get()
+    }
+
+    /// Blocks until the runs requested so far are computed or cancelled, and returns the highest
+    /// generation. Pauses aren't lifted, so this waits for the runner to be resumed.
+    pub fn wait_for_runs(&self) -> usize {
+        loop {
+            let gen = self.requested_generation();
+            if self.wait_for_generation(gen) {
+                return gen;
+            }
+        }
+    }
+
+    /// Returns the number of generations computed but not yet stored in the history, without
+    /// blocking.
+    pub fn queue_depth(&self) -> usize {
+        self.control.queue_depth()
+    }
+
+    /// Returns a generation if it has already been computed, without waiting for the runner.
+    pub fn try_get_generation(&self, gen: usize) -> Option<U> {
+        if gen <= self.control.generations_completed() {
+            self.get_generation_blocking(gen, false)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a difference if its target generation has already been computed, without waiting
+    /// for the runner. Returns `None` if the target generation comes before the reference one.
+    pub fn try_get_difference(&self, ref_gen: usize, target_gen: usize) -> Option<D> {
+        if ref_gen <= target_gen && target_gen <= self.control.generations_completed() {
+            self.get_difference_blocking(ref_gen, target_gen, false)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a handle that completes once the generation has been computed.
+    pub fn generation_handle(&self, gen: usize) -> GenerationHandle<U, D> {
+        // If runs were cancelled since the last request, the runner tells where they stopped once
+        // it is done with them
+        let status = self.control.status();
+        let requested = if status.epoch == self.epoch.get() {
+            Some(self.requested_gen.get())
+        } else {
+            status.settled
+        };
+        let state = match requested {
+            Some(requested) if requested < gen => HandleState::Ready(None),
+            _ => HandleState::Waiting,
+        };
+        GenerationHandle {
+            gen,
+            epoch: self.epoch.get(),
+            control: self.control.clone(),
+            history: self.history_comm.create_third_party(),
+            state,
+        }
+    }
+
+    /// Returns a channel on which the simulator's events will be sent.
+    pub fn events(&self) -> Receiver<SimulatorEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.runner_comm.send(RunnerRequest::SubscribeEvents(tx));
+        rx
+    }
+
+    /// Returns the statistics collected so far, ordered by generation, once previously requested
+    /// generations have been computed. If the runner is paused, they stop at the generation it
+    /// reached.
+    pub fn statistics(&self) -> Vec<GenerationStats<U::Cell>> {
+        let (tx, rx) = mpsc::channel();
+        self.ask_runner(RunnerRequest::Statistics(tx), rx)
+    }
+
+    /// Streams new generations (or differences) to a channel, starting after the last generation
+    /// requested so far.
+    pub fn subscribe(&self, subscription: Subscription) -> Receiver<GenerationUpdate<U, D>> {
+        let (observer, rx) = subscription.into_observer();
+        self.runner_comm.send(RunnerRequest::Observe(observer));
+        rx
+    }
+
+    /// Lowers the requested generation to the one the runner actually reached if runs were
+    /// cancelled since the last call. Cancelled runs end right away, even if paused, so this only
+    /// waits for the runner to be done with the generation it is computing.
+    fn sync_with_runner(&self) {
+        if self.control.status().epoch != self.epoch.get() {
+            let status = self.control.wait_until(|status| status.settled.is_some());
+            self.requested_gen.set(status.settled.unwrap());
+            self.epoch.set(status.epoch);
+        }
+    }
+
+    /// Sends a request that the runner answers even while paused, and waits for its answer.
+    fn ask_runner<T>(&self, request: RunnerRequest<U, D>, answer: Receiver<T>) -> T {
+        self.control.update(|status| status.waiting += 1);
+        self.runner_comm.send(request);
+        answer.recv().expect(ERR_DEAD_RUNNER)
+    }
+
+    /// Waits until the runner has computed a generation. Returns false if the generation will
+    /// never be computed, possibly because runs were cancelled in the meantime.
+    fn wait_for_generation(&self, gen: usize) -> bool {
+        loop {
+            self.sync_with_runner();
+            if self.requested_gen.get() < gen {
+                return false;
+            }
+            let epoch = self.epoch.get();
+            let status = self
+                .control
+                .wait_until(|status| gen <= status.completed || status.epoch != epoch);
+            if status.epoch == epoch {
+                return true;
+            }
+        }
+    }
+
     fn get_generation_blocking(&self, gen: usize, blocking: bool) -> Option<U> {
         match self
             .history_comm
@@ -30,63 +285,196 @@ impl<U: Universe, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D> {
             _ => panic!("{}", ERR_INCORRECT_RESPONSE),
         }
     }
+
+    /// Restarts the (idle) runner from a branch's highest generation.
+    fn enter_branch(&self, gen: usize, universe: U) {
+        // Update progress right away so that no one waits on the previous branch's generations
+        self.control.update(|status| status.completed = gen);
+        self.runner_comm.send(RunnerRequest::Switch(universe, gen));
+        self.requested_gen.set(gen);
+    }
+
+    fn query_branches(&self) -> (Vec<BranchInfo>, BranchId) {
+        match self
+            .history_comm
+            .send_and_wait_for_response(HistoryRequest::Branches)
+        {
+            HistoryResponse::Branches(infos, current) => (infos, current),
+            _ => panic!("{}", ERR_INCORRECT_RESPONSE),
+        }
+    }
+
+    fn get_difference_blocking(
+        &self,
+        ref_gen: usize,
+        target_gen: usize,
+        blocking: bool,
+    ) -> Option<D> {
+        match self
+            .history_comm
+            .send_and_wait_for_response(HistoryRequest::GetDiff(ref_gen, target_gen, blocking))
+        {
+            HistoryResponse::GetDiff(opt_diff) => opt_diff,
+            _ => panic!("{}", ERR_INCORRECT_RESPONSE),
+        }
+    }
+}
+
+impl<U: Universe2D, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D>
+where
+    U::Cell: Hash,
+{
+    /// Starts looking for cycles once previously requested generations have been computed. A
+    /// `SimulatorEvent::CycleDetected` is emitted the first time the universe repeats a prior
+    /// state.
+    pub fn detect_cycles(&self, mode: CycleMode) {
+        self.runner_comm
+            .send(RunnerRequest::DetectCycles(CycleDetector::new(mode)));
+    }
+}
+
+impl<U: Universe2D, D: Difference2D<Universe = U>> AsyncSimulator<U, D> {
+    /// Starts computing statistics for every generation once previously requested generations
+    /// have been computed. Statistics start over when switching branches.
+    pub fn collect_statistics(&self) {
+        self.runner_comm
+            .send(RunnerRequest::CollectStatistics(StatisticsCollector::new()));
+    }
 }
 
 impl<U: Universe, D: GenerationDifference<Universe = U>> Simulator for AsyncSimulator<U, D> {
     type Universe = U;
+    type Diff = D;
 
     fn run(&mut self, nb_gens: usize) {
-        self.runner_comm.send(nb_gens);
-        self.max_gen += nb_gens;
+        self.sync_with_runner();
+        self.runner_comm
+            .send(RunnerRequest::Run(nb_gens, self.epoch.get()));
+        self.requested_gen.set(self.requested_gen.get() + nb_gens);
     }
 
+    fn run_until(&mut self, conditions: StopConditions<U>) -> Stop {
+        self.sync_with_runner();
+        let (tx, rx) = mpsc::channel();
+        let request = RunnerRequest::RunUntil(conditions, self.epoch.get(), tx);
+        self.runner_comm.send(request);
+        let stop = rx.recv().expect(ERR_DEAD_RUNNER);
+        self.requested_gen.set(stop.generation);
+        stop
+    }
+
+    /// Returns the highest generation requested so far, which may not be computed yet. See
+    /// `wait_for_runs` to wait for it.
     fn get_highest_generation(&self) -> usize {
-        self.max_gen
+        self.requested_generation()
     }
 
     fn get_generation(&self, gen: usize) -> Option<Self::Universe> {
-        if gen <= self.max_gen {
-            self.get_generation_blocking(gen, true)
+        if self.wait_for_generation(gen) {
+            self.get_generation_blocking(gen, false)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `None` if the target generation comes before the reference one, rather than
+    /// letting the history thread panic.
+    fn get_difference(&self, ref_gen: usize, target_gen: usize) -> Option<Self::Diff> {
+        if ref_gen <= target_gen && self.wait_for_generation(target_gen) {
+            self.get_difference_blocking(ref_gen, target_gen, false)
         } else {
             None
         }
     }
+
+    /// Waits for pending runs to complete, so that reading the generation afterwards gives back
+    /// the edited universe. If the runner is paused, the generation it reached is edited instead,
+    /// and pending runs continue from there once resumed.
+    fn edit(&mut self, edits: &[CellEdit<U>]) {
+        let (tx, rx) = mpsc::channel();
+        self.ask_runner(RunnerRequest::Edit(edits.to_vec(), tx), rx);
+    }
+
+    fn fork_at(&mut self, gen: usize, edits: &[CellEdit<U>]) -> Option<BranchId> {
+        // The runner must be idle and the history up-to-date before changing branches
+        let mut universe = self.get_generation(gen)?;
+        self.cancel();
+        for (coords, cell) in edits {
+            universe.set(coords.clone(), *cell);
+        }
+        match self
+            .history_comm
+            .send_and_wait_for_response(HistoryRequest::Fork(gen, universe.clone()))
+        {
+            HistoryResponse::Forked(Some(id)) => {
+                self.enter_branch(gen, universe);
+                Some(id)
+            }
+            HistoryResponse::Forked(None) => None,
+            _ => panic!("{}", ERR_INCORRECT_RESPONSE),
+        }
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.query_branches().0
+    }
+
+    fn current_branch(&self) -> BranchId {
+        self.query_branches().1
+    }
+
+    fn switch_branch(&mut self, id: BranchId) -> bool {
+        if id == self.current_branch() {
+            return true;
+        }
+        self.cancel();
+        match self
+            .history_comm
+            .send_and_wait_for_response(HistoryRequest::Switch(id))
+        {
+            HistoryResponse::Switched(Some((gen, universe))) => {
+                self.enter_branch(gen, universe);
+                true
+            }
+            HistoryResponse::Switched(None) => false,
+            _ => panic!("{}", ERR_INCORRECT_RESPONSE),
+        }
+    }
 }
 
-impl<U: CPUUniverse, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D>
+impl<U: Universe, D: GenerationDifference<Universe = U>> Drop for AsyncSimulator<U, D> {
+    fn drop(&mut self) {
+        // Don't let the runner compute generations no one will ever look at
+        self.control.cancel();
+    }
+}
+
+impl<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D>
 where
     U::Cell: CPUCell,
 {
-    pub fn cpu_backend(start_universe: U, f_check: usize) -> Self {
-        // Create communication channels
-        let (runner_op_sender, runner_op_receiver) = oneway_channel();
-        let (history_master, history_slave) = twoway_channel();
-        let history_data_sender = history_master.create_third_party();
-
-        // Start a thread to manage the universe's history
-        UniverseHistory::new(start_universe.clone(), f_check).detach(history_slave);
+    pub fn cpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, cpu_evolve_callback)
+    }
+}
 
-        // Start a thread to handle run commands
-        thread::spawn(move || {
-            let mut current_universe = start_universe;
-            let callback =
-                |universe: &U| history_data_sender.send(HistoryRequest::Push(universe.clone()));
-            loop {
-                match runner_op_receiver.wait_for_mail() {
-                    Ok(nb_gens) => {
-                        current_universe =
-                            U::cpu_evolve_callback(current_universe, nb_gens, callback)
-                    }
-                    Err(_) => break, // Simulator died, time to die
-                }
-            }
-        });
+impl<U: CPUDiffUniverse<D> + Storable, D: GenerationDifference<Universe = U> + Storable>
+    AsyncSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    pub fn cpu_backend_from(snapshot: SimulatorSnapshot<U, D>) -> Self {
+        Self::from_snapshot(snapshot, cpu_evolve_callback)
+    }
+}
 
-        Self {
-            runner_comm: runner_op_sender,
-            history_comm: history_master,
-            max_gen: 0,
-        }
+impl<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    /// Evolves universes on as many threads as the machine can run in parallel.
+    pub fn parallel_cpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, par_cpu_evolve_callback)
     }
 }
 
@@ -94,37 +482,658 @@ impl<U: GPUUniverse, D: GenerationDifference<Universe = U>> AsyncSimulator<U, D>
 where
     U::Cell: GPUCell,
 {
-    pub fn gpu_backend(start_universe: U, f_check: usize) -> Self {
-        // Create communication channels
-        let (runner_op_sender, runner_op_receiver) = oneway_channel();
-        let (history_master, history_slave) = twoway_channel();
-        let history_data_sender = history_master.create_third_party();
+    pub fn gpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, gpu_evolve_callback)
+    }
+}
 
-        // Start a thread to manage the universe's history
-        UniverseHistory::new(start_universe.clone(), f_check).detach(history_slave);
+impl<U: GPUUniverse + Storable, D: GenerationDifference<Universe = U> + Storable>
+    AsyncSimulator<U, D>
+where
+    U::Cell: GPUCell,
+{
+    pub fn gpu_backend_from(snapshot: SimulatorSnapshot<U, D>) -> Self {
+        Self::from_snapshot(snapshot, gpu_evolve_callback)
+    }
+}
 
-        // Start a thread to handle run commands
-        thread::spawn(move || {
-            let mut current_universe = start_universe;
-            let callback =
-                |universe: &U| history_data_sender.send(HistoryRequest::Push(universe.clone()));
-            loop {
-                match runner_op_receiver.wait_for_mail() {
-                    Ok(nb_gens) => {
-                        current_universe =
-                            U::gpu_evolve_callback(current_universe, nb_gens, callback)
-                    }
-                    Err(_) => break, // Simulator died, time to die
+/// RunControl
+
+/// Handle to pause, resume or cancel the runs of an `AsyncSimulator` and to query their progress
+/// without blocking. Handles may be cloned and sent to other threads.
+#[derive(Clone)]
+pub struct RunControl {
+    state: Arc<(Mutex<ControlState>, Condvar)>,
+}
+
+impl RunControl {
+    fn new() -> Self {
+        let state = ControlState {
+            status: RunStatus {
+                completed: 0,
+                paused: false,
+                epoch: 0,
+                queued: 0,
+                waiting: 0,
+                reached: 0,
+                running: false,
+                settled: None,
+            },
+            wakers: vec![],
+        };
+        Self {
+            state: Arc::new((Mutex::new(state), Condvar::new())),
+        }
+    }
+
+    /// Pauses the runner thread after the generation it is currently computing. Edits and
+    /// statistics are still answered while paused.
+    pub fn pause(&self) {
+        self.update(|status| status.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.update(|status| status.paused = false);
+    }
+
+    /// Cancels the run in progress as well as all pending ones, and resumes the runner if it was
+    /// paused. Generations computed before the cancellation are kept.
+    pub fn cancel(&self) {
+        self.update(|status| {
+            status.paused = false;
+            status.epoch += 1;
+            status.settled = if status.running {
+                None
+            } else {
+                Some(status.reached)
+            };
+        });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status().paused
+    }
+
+    /// Returns the number of generations computed so far. Generations count as computed once
+    /// they were handed over to the history.
+    pub fn generations_completed(&self) -> usize {
+        self.status().completed
+    }
+
+    /// Returns the number of generations on their way to the history. The runner waits for the
+    /// history to catch up when there are too many of them.
+    pub fn queue_depth(&self) -> usize {
+        self.status().queued
+    }
+
+    fn status(&self) -> RunStatus {
+        self.state.0.lock().unwrap().status
+    }
+
+    fn update(&self, update_fn: impl FnOnce(&mut RunStatus)) {
+        let (state, changed) = &*self.state;
+        let mut to_wake = vec![];
+        {
+            let mut state = state.lock().unwrap();
+            update_fn(&mut state.status);
+            let status = state.status;
+            state.wakers.retain(|(gen, epoch, waker)| {
+                if status.decides(*gen, *epoch) {
+                    to_wake.push(waker.clone());
+                    false
+                } else {
+                    true
                 }
+            });
+        }
+        changed.notify_all();
+        for waker in to_wake {
+            waker.wake();
+        }
+    }
+
+    /// Marks the runner as working on runs, so that cancelling them waits for it to stop.
+    fn start_runs(&self) {
+        self.update(|status| status.running = true);
+    }
+
+    /// Marks the runner as done with runs. If they were cancelled, they stopped at the generation
+    /// it reached.
+    fn end_runs(&self) {
+        self.update(|status| {
+            status.running = false;
+            if status.settled.is_none() {
+                status.settled = Some(status.reached);
             }
         });
+    }
+
+    /// Blocks until the status satisfies the condition, and returns it.
+    fn wait_until(&self, condition: impl Fn(&RunStatus) -> bool) -> RunStatus {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        while !condition(&state.status) {
+            state = changed.wait(state).unwrap();
+        }
+        state.status
+    }
+
+    /// Returns the status if it is known whether the generation requested in `epoch` is computed.
+    /// Otherwise, the waker (if any) is registered to be woken up when that happens.
+    fn poll_generation(
+        &self,
+        gen: usize,
+        epoch: usize,
+        waker: Option<&Waker>,
+    ) -> Option<RunStatus> {
+        let mut state = self.state.0.lock().unwrap();
+        let status = state.status;
+        if status.decides(gen, epoch) {
+            Some(status)
+        } else {
+            if let Some(waker) = waker {
+                state.wakers.push((gen, epoch, waker.clone()));
+            }
+            None
+        }
+    }
+}
+
+struct ControlState {
+    status: RunStatus,
+    /// Wakers of generation handles, along with the generation and epoch they are waiting on.
+    wakers: Vec<(usize, usize, Waker)>,
+}
+
+#[derive(Copy, Clone)]
+struct RunStatus {
+    completed: usize,
+    paused: bool,
+    /// Incremented on every cancellation, requests made in an older epoch are ignored.
+    epoch: usize,
+    /// Number of generations on their way to the history.
+    queued: usize,
+    /// Number of requests answered even while paused that the runner hasn't received yet.
+    waiting: usize,
+    /// Last generation computed by the runner, which may not be in the history yet.
+    reached: usize,
+    /// Whether the runner is working on runs.
+    running: bool,
+    /// Generation at which the runs cancelled last stopped, once the runner is done with them.
+    settled: Option<usize>,
+}
+
+impl RunStatus {
+    /// Whether it is known if a generation requested in some epoch will be computed: either it
+    /// was, or runs were cancelled since and stopped before it.
+    fn decides(&self, gen: usize, epoch: usize) -> bool {
+        gen <= self.completed
+            || (epoch != self.epoch && self.settled.is_some_and(|settled| settled < gen))
+    }
+}
+
+/// GenerationHandle
+
+/// A generation that may not be computed yet. It can either be polled repeatedly (e.g., once per
+/// frame) with `poll_generation`, or awaited as a `Future`. In both cases, the result is `None` if
+/// the generation will never be computed because it was never requested or because runs were
+/// cancelled before it was.
+pub struct GenerationHandle<U: Universe, D: GenerationDifference<Universe = U>> {
+    gen: usize,
+    epoch: usize,
+    control: RunControl,
+    history: ThirdPartySender<HistoryRequest<U, D>>,
+    state: HandleState<U>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> GenerationHandle<U, D> {
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.gen
+    }
+
+    /// Checks whether the generation is available, without blocking.
+    pub fn poll_generation(&mut self) -> Poll<Option<U>> {
+        self.poll_with(None)
+    }
+
+    fn poll_with(&mut self, waker: Option<&Waker>) -> Poll<Option<U>> {
+        if let HandleState::Waiting = self.state {
+            match self.control.poll_generation(self.gen, self.epoch, waker) {
+                Some(status) if status.completed < self.gen => {
+                    self.state = HandleState::Ready(None);
+                }
+                Some(_) => {
+                    // Ask the history for the generation without waiting for the answer
+                    let slot = Arc::new(Mutex::new(FetchSlot {
+                        universe: None,
+                        waker: None,
+                    }));
+                    let callback_slot = Arc::clone(&slot);
+                    let respond = move |universe: Option<U>| {
+                        let mut slot = callback_slot.lock().unwrap();
+                        slot.universe = Some(universe);
+                        if let Some(waker) = slot.waker.take() {
+                            waker.wake();
+                        }
+                    };
+                    self.history
+                        .send(HistoryRequest::Fetch(self.gen, Box::new(respond)));
+                    self.state = HandleState::Fetching(slot);
+                }
+                None => return Poll::Pending,
+            }
+        }
+
+        if let HandleState::Fetching(slot) = &self.state {
+            let mut slot = slot.lock().unwrap();
+            match slot.universe.take() {
+                Some(universe) => {
+                    drop(slot);
+                    self.state = HandleState::Ready(universe);
+                }
+                None => {
+                    slot.waker = waker.cloned();
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        match mem::replace(&mut self.state, HandleState::Done) {
+            HandleState::Ready(universe) => Poll::Ready(universe),
+            _ => panic!("{}", ERR_POLLED_AFTER_COMPLETION),
+        }
+    }
+}
+
+// The handle is never structurally pinned, so it may be moved even if universes may not
+impl<U: Universe, D: GenerationDifference<Universe = U>> Unpin for GenerationHandle<U, D> {}
 
+impl<U: Universe, D: GenerationDifference<Universe = U>> Future for GenerationHandle<U, D> {
+    type Output = Option<U>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().poll_with(Some(cx.waker()))
+    }
+}
+
+enum HandleState<U> {
+    /// Waiting for the runner to compute the generation.
+    Waiting,
+    /// Waiting for the history to send the generation back.
+    Fetching(Arc<Mutex<FetchSlot<U>>>),
+    Ready(Option<U>),
+    Done,
+}
+
+struct FetchSlot<U> {
+    universe: Option<Option<U>>,
+    waker: Option<Waker>,
+}
+
+/// Runner
+
+/// State of the thread that computes new generations.
+struct Runner<U: Universe, D: GenerationDifference<Universe = U>> {
+    universe: Option<U>,
+    gen: usize,
+    pipeline: HistoryPipeline<U, D>,
+    evolve_fn: EvolveFn<U, D>,
+    control: RunControl,
+    requests: SimpleReceiver<RunnerRequest<U, D>>,
+    /// Requests received while paused, which must wait for the runs before them.
+    deferred: VecDeque<RunnerRequest<U, D>>,
+    /// Universe to continue from once the ongoing evolution is interrupted, because it was
+    /// cancelled or edited while paused. Generations computed in the meantime are discarded.
+    interrupted_at: Option<U>,
+    cycle_detector: Option<CycleDetector<U>>,
+    statistics: Option<StatisticsCollector<U, D>>,
+    event_senders: Vec<Sender<SimulatorEvent>>,
+    observers: Vec<BoxedObserver<U, D>>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Runner<U, D> {
+    fn new(
+        universe: U,
+        pipeline: HistoryPipeline<U, D>,
+        evolve_fn: EvolveFn<U, D>,
+        control: RunControl,
+        requests: SimpleReceiver<RunnerRequest<U, D>>,
+    ) -> Self {
         Self {
-            runner_comm: runner_op_sender,
-            history_comm: history_master,
-            max_gen: 0,
+            universe: Some(universe),
+            gen: 0,
+            pipeline,
+            evolve_fn,
+            control,
+            requests,
+            deferred: VecDeque::new(),
+            interrupted_at: None,
+            cycle_detector: None,
+            statistics: None,
+            event_senders: vec![],
+            observers: vec![],
+        }
+    }
+
+    fn serve(self) {
+        let control = self.control.clone();
+        let runner = RefCell::new(self);
+        loop {
+            let request = runner.borrow_mut().next_request();
+            match request {
+                Ok(RunnerRequest::Run(nb_gens, epoch)) => {
+                    // Evolve in chunks to bound the work wasted when the run is cancelled
+                    control.start_runs();
+                    let mut remaining = nb_gens;
+                    while 0 < remaining && Self::wait_between(&runner, epoch) {
+                        let nb_chunk = cmp::min(remaining, RUN_CHUNK_SIZE);
+                        remaining -= Self::evolve(&runner, nb_chunk, epoch);
+                    }
+                    control.end_runs();
+                }
+                Ok(RunnerRequest::RunUntil(mut conditions, epoch, tx)) => {
+                    // Evolve one generation at a time so that we never overshoot
+                    control.start_runs();
+                    let stop = loop {
+                        let gen = runner.borrow().gen;
+                        let reason = {
+                            let runner = runner.borrow();
+                            conditions.check(gen, runner.universe.as_ref().unwrap())
+                        };
+                        if let Some(reason) = reason {
+                            break Stop {
+                                reason,
+                                generation: gen,
+                            };
+                        }
+                        if !Self::wait_between(&runner, epoch) {
+                            break Stop {
+                                reason: StopReason::Cancelled,
+                                generation: gen,
+                            };
+                        }
+                        Self::evolve(&runner, 1, epoch);
+                    };
+
+                    control.end_runs();
+
+                    // The simulator may have been dropped in the meantime
+                    let _ = tx.send(stop);
+                }
+                Ok(RunnerRequest::DetectCycles(mut detector)) => {
+                    let mut runner = runner.borrow_mut();
+                    detector.observe(runner.gen, runner.universe.as_ref().unwrap());
+                    runner.cycle_detector = Some(detector);
+                }
+                Ok(RunnerRequest::CollectStatistics(mut collector)) => {
+                    let mut runner = runner.borrow_mut();
+                    collector.start(runner.gen, runner.universe.as_ref().unwrap());
+                    runner.statistics = Some(collector);
+                }
+                Ok(RunnerRequest::Statistics(tx)) => runner.borrow().send_statistics(tx),
+                Ok(RunnerRequest::SubscribeEvents(tx)) => {
+                    runner.borrow_mut().event_senders.push(tx)
+                }
+                Ok(RunnerRequest::Observe(mut observer)) => {
+                    let mut runner = runner.borrow_mut();
+                    observer.start(runner.gen, runner.universe.as_ref().unwrap());
+                    runner.observers.push(observer);
+                }
+                Ok(RunnerRequest::Edit(edits, tx)) => {
+                    let mut runner = runner.borrow_mut();
+                    let mut universe = runner.universe.take().unwrap();
+                    runner.edit(&mut universe, &edits);
+                    runner.universe = Some(universe);
+                    let _ = tx.send(());
+                }
+                Ok(RunnerRequest::Switch(universe, gen)) => {
+                    runner.borrow_mut().enter_branch(universe, gen)
+                }
+                Err(_) => break, // Simulator died, time to die
+            }
+        }
+    }
+
+    /// Takes requests deferred while paused first, in the order they were received.
+    fn next_request(&mut self) -> Result<RunnerRequest<U, D>, RecvError> {
+        match self.deferred.pop_front() {
+            Some(request) => Ok(request),
+            None => self.receive(),
         }
     }
+
+    fn receive(&self) -> Result<RunnerRequest<U, D>, RecvError> {
+        let request = self.requests.wait_for_mail()?;
+        if request.answered_while_paused() {
+            self.control.update(|status| status.waiting -= 1);
+        }
+        Ok(request)
+    }
+
+    /// Evolves for some generations, and returns how many were kept.
+    fn evolve(runner: &RefCell<Self>, nb_gens: usize, epoch: usize) -> usize {
+        let (universe, evolve_fn, from_gen) = {
+            let mut runner = runner.borrow_mut();
+            (
+                runner.universe.take().unwrap(),
+                runner.evolve_fn,
+                runner.gen,
+            )
+        };
+        let callback = |universe: &U, diff: Option<D>| {
+            runner.borrow_mut().on_generation(universe, diff, epoch)
+        };
+        let universe = evolve_fn(universe, nb_gens, &callback);
+
+        // Generations computed after an interruption are discarded
+        let mut runner = runner.borrow_mut();
+        let universe = runner.interrupted_at.take().unwrap_or(universe);
+        runner.universe = Some(universe);
+        runner.gen - from_gen
+    }
+
+    /// Waits before evolving while paused. Returns false if runs of `epoch` were cancelled.
+    fn wait_between(runner: &RefCell<Self>, epoch: usize) -> bool {
+        let mut runner = runner.borrow_mut();
+        let universe = runner.universe.take().unwrap();
+        let go_on = runner.wait_while_paused(&universe, epoch);
+        let universe = runner.interrupted_at.take().unwrap_or(universe);
+        runner.universe = Some(universe);
+        go_on
+    }
+
+    /// Blocks while runs of `epoch` are paused at `universe`, answering edits and statistics in
+    /// the meantime. Other requests are deferred until after the runs. Returns false if the runs
+    /// were cancelled.
+    fn wait_while_paused(&mut self, universe: &U, epoch: usize) -> bool {
+        loop {
+            let status = self
+                .control
+                .wait_until(|status| !status.paused || status.epoch != epoch || 0 < status.waiting);
+            if !status.paused || status.epoch != epoch {
+                return status.epoch == epoch;
+            }
+            let request = match self.receive() {
+                Ok(request) => request,
+                Err(_) => return false,
+            };
+            match request {
+                RunnerRequest::Statistics(tx) => self.send_statistics(tx),
+                RunnerRequest::Edit(edits, tx) => {
+                    // Edits made while paused stick to the generation reached
+                    let mut edited = self
+                        .interrupted_at
+                        .take()
+                        .unwrap_or_else(|| universe.clone());
+                    self.edit(&mut edited, &edits);
+                    self.interrupted_at = Some(edited);
+                    let _ = tx.send(());
+                }
+                request => self.deferred.push_back(request),
+            }
+        }
+    }
+
+    fn on_generation(&mut self, universe: &U, diff: Option<D>, epoch: usize) {
+        if self.interrupted_at.is_some() {
+            return;
+        }
+        if let Some(collector) = &mut self.statistics {
+            collector.observe(self.gen + 1, universe, diff.as_ref());
+        }
+
+        self.gen += 1;
+        let gen = self.gen;
+        self.control.update(|status| status.reached = gen);
+
+        // Forget about subscribers that went away
+        self.observers
+            .retain_mut(|observer| observer.observe(gen, universe, diff.as_ref()));
+        self.pipeline.push(gen, universe, diff);
+
+        let cycle = match &mut self.cycle_detector {
+            Some(detector) => detector.observe(self.gen, universe),
+            None => None,
+        };
+        if let Some(cycle) = cycle {
+            self.emit(SimulatorEvent::CycleDetected(cycle));
+        }
+
+        if !self.wait_while_paused(universe, epoch) && self.interrupted_at.is_none() {
+            self.interrupted_at = Some(universe.clone());
+        }
+    }
+
+    /// Edits cells of the current generation, and returns once the history was told.
+    fn edit(&mut self, universe: &mut U, edits: &[CellEdit<U>]) {
+        for (coords, cell) in edits {
+            universe.set(coords.clone(), *cell);
+        }
+        self.pipeline.intervene(universe);
+
+        let gen = self.gen;
+        if let Some(detector) = &mut self.cycle_detector {
+            detector.reset();
+            detector.observe(gen, universe);
+        }
+        if let Some(collector) = &mut self.statistics {
+            collector.edit(gen, universe);
+        }
+        self.emit(SimulatorEvent::Intervention(gen));
+        self.observers
+            .retain_mut(|observer| observer.observe(gen, universe, None));
+        self.pipeline.flush();
+    }
+
+    fn send_statistics(&self, tx: Sender<Vec<GenerationStats<U::Cell>>>) {
+        let series = match &self.statistics {
+            Some(collector) => collector.series().to_vec(),
+            None => vec![],
+        };
+        let _ = tx.send(series);
+    }
+
+    /// Continues from another branch. Cycle detection stops, and subscribers continue from the
+    /// branch's highest generation.
+    fn enter_branch(&mut self, universe: U, gen: usize) {
+        self.gen = gen;
+        self.control.update(|status| status.reached = gen);
+        self.pipeline.reset(&universe);
+        self.cycle_detector = None;
+        if let Some(collector) = &mut self.statistics {
+            collector.start(gen, &universe);
+        }
+        for observer in self.observers.iter_mut() {
+            observer.start(gen, &universe);
+        }
+        self.universe = Some(universe);
+    }
+
+    fn emit(&mut self, event: SimulatorEvent) {
+        // Forget about receivers that were dropped
+        self.event_senders.retain(|tx| tx.send(event).is_ok());
+    }
 }
 
+/// Evolves a universe for some generations, calling the callback on each new one along with its
+/// difference to the previous one if it was produced directly.
+type EvolveFn<U, D> = fn(U, usize, &dyn Fn(&U, Option<D>)) -> U;
+
+/// Run requests are tagged with the cancellation epoch they were sent in.
+enum RunnerRequest<U: Universe, D: GenerationDifference<Universe = U>> {
+    Run(usize, usize),
+    RunUntil(StopConditions<U>, usize, Sender<Stop>),
+    DetectCycles(CycleDetector<U>),
+    CollectStatistics(StatisticsCollector<U, D>),
+    /// Asks for the statistics collected once previous requests were handled, or right away if
+    /// paused.
+    Statistics(Sender<Vec<GenerationStats<U::Cell>>>),
+    SubscribeEvents(Sender<SimulatorEvent>),
+    Observe(BoxedObserver<U, D>),
+    /// Edits cells of the current generation once previous requests were handled, or right away
+    /// if paused, and acknowledges once the history was told.
+    Edit(Vec<CellEdit<U>>, Sender<()>),
+    /// Continues from a branch's universe and generation.
+    Switch(U, usize),
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> RunnerRequest<U, D> {
+    /// Whether the runner answers the request without waiting for pending runs when paused.
+    fn answered_while_paused(&self) -> bool {
+        matches!(self, RunnerRequest::Statistics(_) | RunnerRequest::Edit(..))
+    }
+}
+
+fn cpu_evolve_callback<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>>(
+    universe: U,
+    nb_gens: usize,
+    callback: &dyn Fn(&U, Option<D>),
+) -> U
+where
+    U::Cell: CPUCell,
+{
+    let mut universe = universe;
+    for _ in 0..nb_gens {
+        let (next, diff) = universe.cpu_evolve_once_diff();
+        callback(&next, Some(diff));
+        universe = next;
+    }
+    universe
+}
+
+fn par_cpu_evolve_callback<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>>(
+    universe: U,
+    nb_gens: usize,
+    callback: &dyn Fn(&U, Option<D>),
+) -> U
+where
+    U::Cell: CPUCell,
+{
+    let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut universe = universe;
+    for _ in 0..nb_gens {
+        let (next, diff) = universe.par_cpu_evolve_once_diff(n_threads);
+        callback(&next, Some(diff));
+        universe = next;
+    }
+    universe
+}
+
+fn gpu_evolve_callback<U: GPUUniverse, D>(
+    universe: U,
+    nb_gens: usize,
+    callback: &dyn Fn(&U, Option<D>),
+) -> U
+where
+    U::Cell: GPUCell,
+{
+    universe.gpu_evolve_callback(nb_gens, |universe| callback(universe, None))
+}
+
+/// Number of generations the runner computes between checks for pauses and cancellations.
+const RUN_CHUNK_SIZE: usize = 16;
+
+const ERR_POLLED_AFTER_COMPLETION: &str = "A generation handle was polled after completing.";
+const ERR_DEAD_RUNNER: &str = "The runner thread stopped unexpectedly.";
 const ERR_INCORRECT_RESPONSE: &str = "The received response is incompatible with the sent request.";
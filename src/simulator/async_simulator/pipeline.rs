@@ -0,0 +1,284 @@
+// Standard library
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Local
+use super::RunControl;
+use crate::{
+    advanced_channels::{ThirdPartySender, TransmittingEnd},
+    simulator::universe_history::HistoryRequest,
+    universe::{GenerationDifference, Universe},
+};
+
+/// HistoryPipeline
+
+/// Carries what the runner produces to the history thread. Differences that evolving didn't
+/// produce are computed by a pool of workers, and a sequencer thread hands everything over to the
+/// history in the order in which the runner sent it. At most `QUEUE_CAPACITY` generations can be
+/// on their way to the history (including those waiting in its queue), after which pushing blocks
+/// the runner until the history catches up.
+pub(super) struct HistoryPipeline<U: Universe, D: GenerationDifference<Universe = U>> {
+    control: RunControl,
+    /// Highest generation sent down the pipeline, which the next one is diffed against if evolving
+    /// didn't produce its difference.
+    previous: Option<U>,
+    next_seq: usize,
+    jobs: Sender<DiffJob<U>>,
+    sequencer: Sender<(usize, Stage<U, D>)>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> HistoryPipeline<U, D> {
+    /// Starts the workers and the sequencer for a history whose highest generation is `universe`.
+    pub(super) fn new(
+        universe: U,
+        history: ThirdPartySender<HistoryRequest<U, D>>,
+        control: RunControl,
+    ) -> Self {
+        let (sequencer, stages) = mpsc::channel();
+        let (jobs, jobs_rx) = mpsc::channel();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let n_workers =
+            thread::available_parallelism().map_or(1, |n| n.get().clamp(1, MAX_WORKERS));
+        for _ in 0..n_workers {
+            let jobs_rx = Arc::clone(&jobs_rx);
+            let sequencer = sequencer.clone();
+            thread::spawn(move || compute_diffs(jobs_rx, sequencer));
+        }
+        let sequencer_control = control.clone();
+        thread::spawn(move || sequence(stages, history, sequencer_control));
+
+        Self {
+            control,
+            previous: Some(universe),
+            next_seq: 0,
+            jobs,
+            sequencer,
+        }
+    }
+
+    /// Sends a new generation to the history, along with its difference to the previous one if
+    /// evolving produced it. Blocks while the queue is full.
+    pub(super) fn push(&mut self, gen: usize, universe: &U, diff: Option<D>) {
+        self.control
+            .wait_until(|status| status.queued < QUEUE_CAPACITY);
+        self.control.update(|status| status.queued += 1);
+        let slot = QueueSlot(self.control.clone());
+        let seq = self.next_seq();
+        match (diff, self.previous.take()) {
+            (Some(diff), _) => {
+                let request = HistoryRequest::PushDiff(diff);
+                self.send(seq, Stage::Push(gen, queued(request, slot)));
+            }
+            (None, Some(previous)) => {
+                self.previous = Some(universe.clone());
+                let job = DiffJob {
+                    seq,
+                    gen,
+                    previous,
+                    next: universe.clone(),
+                    slot,
+                };
+                self.jobs.send(job).expect(ERR_DEAD_PIPELINE);
+            }
+            // Evolving stopped producing differences, so let the history compute this one
+            (None, None) => {
+                self.previous = Some(universe.clone());
+                let request = HistoryRequest::Push(universe.clone());
+                self.send(seq, Stage::Push(gen, queued(request, slot)));
+            }
+        }
+    }
+
+    /// Replaces the highest generation in the history with an edited version of it.
+    pub(super) fn intervene(&mut self, universe: &U) {
+        self.previous = Some(universe.clone());
+        let seq = self.next_seq();
+        self.send(
+            seq,
+            Stage::Forward(HistoryRequest::Intervene(universe.clone())),
+        );
+    }
+
+    /// Continues from another universe, which the history already knows as its highest generation
+    /// (e.g., after switching branches).
+    pub(super) fn reset(&mut self, universe: &U) {
+        self.previous = Some(universe.clone());
+    }
+
+    /// Blocks until everything sent so far was handed over to the history, so that requests sent
+    /// to it afterwards are served after them.
+    pub(super) fn flush(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let seq = self.next_seq();
+        self.send(seq, Stage::Flush(tx));
+        rx.recv().expect(ERR_DEAD_PIPELINE);
+    }
+
+    fn next_seq(&mut self) -> usize {
+        self.next_seq += 1;
+        self.next_seq - 1
+    }
+
+    fn send(&self, seq: usize, stage: Stage<U, D>) {
+        self.sequencer.send((seq, stage)).expect(ERR_DEAD_PIPELINE);
+    }
+}
+
+/// QueueSlot
+
+/// A place in the queue to the history, freed once the history is done with the generation
+/// (or if it drops it).
+pub(in crate::simulator) struct QueueSlot(RunControl);
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        self.0.update(|status| status.queued -= 1);
+    }
+}
+
+/// A generation whose difference to the previous one must be computed.
+struct DiffJob<U> {
+    seq: usize,
+    gen: usize,
+    previous: U,
+    next: U,
+    slot: QueueSlot,
+}
+
+enum Stage<U: Universe, D: GenerationDifference<Universe = U>> {
+    /// Pushes a generation, which is then considered completed.
+    Push(usize, HistoryRequest<U, D>),
+    Forward(HistoryRequest<U, D>),
+    /// Acknowledges that previous stages were handed over.
+    Flush(Sender<()>),
+}
+
+fn queued<U: Universe, D: GenerationDifference<Universe = U>>(
+    request: HistoryRequest<U, D>,
+    slot: QueueSlot,
+) -> HistoryRequest<U, D> {
+    HistoryRequest::Queued(Box::new(request), slot)
+}
+
+fn compute_diffs<U: Universe, D: GenerationDifference<Universe = U>>(
+    jobs: Arc<Mutex<Receiver<DiffJob<U>>>>,
+    sequencer: Sender<(usize, Stage<U, D>)>,
+) {
+    loop {
+        // Release the lock before working, so that other workers can pick up the next job
+        let job = jobs.lock().unwrap().recv();
+        let job = match job {
+            Ok(job) => job,
+            Err(_) => break, // The runner is gone
+        };
+        let diff = D::get_diff(&job.previous, &job.next);
+        let request = queued(HistoryRequest::PushWithDiff(job.next, diff), job.slot);
+        if sequencer
+            .send((job.seq, Stage::Push(job.gen, request)))
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Hands stages over to the history in sequence order, whatever the order in which workers
+/// finish them.
+fn sequence<U: Universe, D: GenerationDifference<Universe = U>>(
+    stages: Receiver<(usize, Stage<U, D>)>,
+    history: ThirdPartySender<HistoryRequest<U, D>>,
+    control: RunControl,
+) {
+    let mut pending = BTreeMap::new();
+    let mut next_seq = 0;
+    for (seq, stage) in stages {
+        pending.insert(seq, stage);
+        while let Some(stage) = pending.remove(&next_seq) {
+            next_seq += 1;
+            match stage {
+                Stage::Push(gen, request) => {
+                    history.send(request);
+                    // Generations are only completed once the history can answer for them
+                    control.update(|status| status.completed = gen);
+                }
+                Stage::Forward(request) => history.send(request),
+                Stage::Flush(tx) => {
+                    let _ = tx.send(());
+                }
+            }
+        }
+    }
+}
+
+/// Maximum number of generations on their way to the history.
+pub(super) const QUEUE_CAPACITY: usize = 64;
+
+/// Maximum number of threads computing differences.
+const MAX_WORKERS: usize = 4;
+
+const ERR_DEAD_PIPELINE: &str = "A thread of the history pipeline stopped unexpectedly.";
+
+#[cfg(test)]
+mod tests {
+    use super::{HistoryPipeline, RunControl, QUEUE_CAPACITY};
+    use crate::{
+        advanced_channels::{twoway_channel, MailType},
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::universe_history::{HistoryRequest, HistoryResponse},
+        universe::{
+            grid2d::static_grid2d::{GridDiff, StaticGrid2D},
+            CPUUniverse, GenerationDifference,
+        },
+    };
+    use std::thread;
+
+    type Grid = StaticGrid2D<GameOfLife>;
+    type Diff = GridDiff<GameOfLife>;
+
+    #[test]
+    fn ordered_backpressure() {
+        let (master, slave) =
+            twoway_channel::<HistoryRequest<Grid, Diff>, HistoryResponse<Grid, Diff>>();
+        let control = RunControl::new();
+        let start = game_of_life::r_pentomino();
+        let mut pipeline =
+            HistoryPipeline::new(start.clone(), master.create_third_party(), control.clone());
+
+        // The runner gets stuck once the queue is full, since no one serves the history
+        let n_gens = QUEUE_CAPACITY + 10;
+        let runner = thread::spawn(move || {
+            let mut universe = start;
+            for gen in 1..=n_gens {
+                universe = universe.cpu_evolve_once();
+                pipeline.push(gen, &universe, None);
+            }
+            pipeline.flush();
+        });
+        control.wait_until(|status| status.completed == QUEUE_CAPACITY);
+        assert_eq!(control.queue_depth(), QUEUE_CAPACITY);
+
+        // Generations arrive in order, with correct differences
+        let mut universe = game_of_life::r_pentomino();
+        for gen in 1..=n_gens {
+            match slave.wait_for_mail() {
+                MailType::Message(HistoryRequest::Queued(request, _), None) => match *request {
+                    HistoryRequest::PushWithDiff(next, diff) => {
+                        let expected = universe.clone().cpu_evolve_once();
+                        let cells = |grid: &Grid| grid.iter().flatten().collect::<Vec<_>>();
+                        assert_eq!(cells(&next), cells(&expected));
+                        assert_eq!(cells(&diff.apply_to(universe)), cells(&expected));
+                        universe = next;
+                    }
+                    _ => panic!("Generation {} wasn't pushed with its difference.", gen),
+                },
+                _ => panic!("Generation {} wasn't queued.", gen),
+            }
+        }
+        runner.join().unwrap();
+        assert_eq!(control.queue_depth(), 0);
+        assert_eq!(control.generations_completed(), n_gens);
+        drop(master);
+    }
+}
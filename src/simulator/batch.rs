@@ -0,0 +1,388 @@
+// Standard library
+use std::any::Any;
+use std::fmt::Write as FmtWrite;
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Local
+use super::{stop_condition::population_2d, Stop, StopConditions, StopReason};
+use crate::{
+    automaton::{AutomatonCell, CPUCell},
+    universe::{
+        grid2d::{static_grid2d::StaticGrid2D, Neighbor2D, Size2D, Universe2D},
+        CPUUniverse, Universe,
+    },
+};
+
+/// JobStart
+
+/// What a job's initial universe was created from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStart {
+    /// A named pattern.
+    Pattern(String),
+    /// A random soup.
+    Soup { seed: u64, density: f64 },
+}
+
+/// Job
+
+/// A simulation to run as part of a batch: an initial universe, whose cells' automaton gives the
+/// job's rule (see `AutomatonCell::rule`), a generation budget, and conditions under which to stop
+/// earlier.
+pub struct Job<U: Universe> {
+    start: JobStart,
+    universe: U,
+    max_gens: usize,
+    conditions: StopConditions<U>,
+}
+
+impl<U: Universe2D> Job<U>
+where
+    U::Cell: Hash,
+{
+    /// Runs a named pattern for at most `max_gens` generations.
+    pub fn pattern(name: &str, universe: U, max_gens: usize) -> Self {
+        Self {
+            start: JobStart::Pattern(name.to_string()),
+            universe,
+            max_gens,
+            conditions: StopConditions::new(),
+        }
+    }
+}
+
+impl<C: AutomatonCell<Neighbor = Neighbor2D> + Hash> Job<StaticGrid2D<C>> {
+    /// Runs a random soup (see `StaticGrid2D::soup`) for at most `max_gens` generations.
+    pub fn soup(size: Size2D, density: f64, seed: u64, state: C, max_gens: usize) -> Self {
+        Self {
+            start: JobStart::Soup { seed, density },
+            universe: StaticGrid2D::soup(size, density, seed, state),
+            max_gens,
+            conditions: StopConditions::new(),
+        }
+    }
+}
+
+impl<U: Universe> Job<U> {
+    /// Stops the job as soon as one of the conditions holds. The job's generation budget replaces
+    /// the conditions' own generation limit, if any.
+    pub fn stop_when(mut self, conditions: StopConditions<U>) -> Self {
+        self.conditions = conditions;
+        self
+    }
+}
+
+/// JobOutcome
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JobOutcome {
+    pub stop: Stop,
+    /// Number of non-default cells in the last generation.
+    pub final_population: usize,
+    /// Period of the cycle the universe settled in, if it stabilised.
+    pub period: Option<usize>,
+}
+
+/// JobSummary
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSummary {
+    pub rule: String,
+    pub start: JobStart,
+    /// How the job ended, or the message it panicked with.
+    pub outcome: Result<JobOutcome, String>,
+    pub runtime: Duration,
+}
+
+/// Batch
+
+/// Runs many independent jobs (e.g., to explore rule space) across a pool of threads, evolving
+/// their universes on the CPU. A job that panics is reported as failed without affecting the
+/// others.
+pub struct Batch {
+    threads: usize,
+    jobs: Vec<PendingJob>,
+}
+
+impl Batch {
+    /// Creates an empty batch using as many threads as the machine can run in parallel.
+    pub fn new() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs: vec![],
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        if threads == 0 {
+            panic!("{}", ERR_ZERO_THREADS);
+        }
+        self.threads = threads;
+        self
+    }
+
+    /// Adds a job to the batch. Jobs may use different universe and cell types.
+    pub fn job<U: CPUUniverse + Universe2D>(mut self, job: Job<U>) -> Self
+    where
+        U::Cell: CPUCell,
+    {
+        let Job {
+            start,
+            universe,
+            max_gens,
+            conditions,
+        } = job;
+        let conditions = conditions.max_generations(max_gens);
+        self.jobs.push(PendingJob {
+            rule: U::Cell::rule().to_string(),
+            start,
+            run: Box::new(move || run_job(universe, conditions)),
+        });
+        self
+    }
+
+    /// Runs every job and returns their summaries, in the order in which jobs were added.
+    pub fn run(self) -> BatchResults {
+        let Self { threads, jobs } = self;
+        let n_jobs = jobs.len();
+        let queue = Arc::new(Mutex::new(jobs.into_iter().enumerate()));
+        let (tx, rx) = mpsc::channel();
+        let workers: Vec<_> = (0..threads.min(n_jobs))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let tx = tx.clone();
+                thread::spawn(move || loop {
+                    let next = queue.lock().unwrap().next();
+                    match next {
+                        Some((idx, job)) => tx.send((idx, job.execute())).unwrap(),
+                        None => break,
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut summaries = vec![None; n_jobs];
+        for (idx, summary) in rx {
+            summaries[idx] = Some(summary);
+        }
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        BatchResults {
+            jobs: summaries.into_iter().map(Option::unwrap).collect(),
+        }
+    }
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// BatchResults
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResults {
+    /// Summaries in the order in which jobs were added to the batch.
+    pub jobs: Vec<JobSummary>,
+}
+
+impl BatchResults {
+    pub fn failures(&self) -> impl Iterator<Item = &JobSummary> {
+        self.jobs.iter().filter(|summary| summary.outcome.is_err())
+    }
+
+    /// Renders the results as a CSV table with one line per job. Fields that don't apply to a
+    /// job (e.g., the seed of a pattern, or the period of a failed job) are left empty.
+    pub fn render_csv(&self) -> String {
+        let mut csv = String::from(
+            "rule,pattern,seed,density,stop_reason,generation,final_population,period,\
+             runtime_ms,error\n",
+        );
+        for summary in self.jobs.iter() {
+            csv.push_str(&csv_field(&summary.rule));
+            match &summary.start {
+                JobStart::Pattern(name) => write!(csv, ",{},,", csv_field(name)).unwrap(),
+                JobStart::Soup { seed, density } => write!(csv, ",,{},{}", seed, density).unwrap(),
+            }
+            match &summary.outcome {
+                Ok(outcome) => write!(
+                    csv,
+                    ",{},{},{},{}",
+                    reason_name(outcome.stop.reason),
+                    outcome.stop.generation,
+                    outcome.final_population,
+                    outcome.period.map_or(String::new(), |p| p.to_string())
+                )
+                .unwrap(),
+                Err(_) => csv.push_str(",,,,"),
+            }
+            write!(csv, ",{:.3}", summary.runtime.as_secs_f64() * 1e3).unwrap();
+            match &summary.outcome {
+                Ok(_) => csv.push_str(",\n"),
+                Err(msg) => writeln!(csv, ",{}", csv_field(msg)).unwrap(),
+            }
+        }
+        csv
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.render_csv().as_bytes())?;
+        writer.flush()
+    }
+}
+
+/// A job whose universe type was erased.
+struct PendingJob {
+    rule: String,
+    start: JobStart,
+    run: Box<dyn FnOnce() -> JobOutcome + Send>,
+}
+
+impl PendingJob {
+    fn execute(self) -> JobSummary {
+        let start = Instant::now();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(self.run)).map_err(panic_message);
+        JobSummary {
+            rule: self.rule,
+            start: self.start,
+            outcome,
+            runtime: start.elapsed(),
+        }
+    }
+}
+
+fn run_job<U: CPUUniverse + Universe2D>(
+    mut universe: U,
+    mut conditions: StopConditions<U>,
+) -> JobOutcome
+where
+    U::Cell: CPUCell,
+{
+    let mut gen = 0;
+    let stop = loop {
+        if let Some(reason) = conditions.check(gen, &universe) {
+            break Stop {
+                reason,
+                generation: gen,
+            };
+        }
+        universe = universe.cpu_evolve_once();
+        gen += 1;
+    };
+    JobOutcome {
+        stop,
+        final_population: population_2d(&universe),
+        period: match stop.reason {
+            StopReason::Stabilisation(cycle) => Some(cycle.period),
+            _ => None,
+        },
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from(UNKNOWN_PANIC)
+    }
+}
+
+fn reason_name(reason: StopReason) -> &'static str {
+    match reason {
+        StopReason::Extinction => "extinction",
+        StopReason::Stabilisation(_) => "stabilisation",
+        StopReason::PopulationAbove(_) => "population_above",
+        StopReason::PopulationBelow(_) => "population_below",
+        StopReason::CellReached(_) => "cell_reached",
+        StopReason::Timeout => "timeout",
+        StopReason::GenerationLimit => "generation_limit",
+        StopReason::Predicate(_) => "predicate",
+        StopReason::Cancelled => "cancelled",
+    }
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+const UNKNOWN_PANIC: &str = "The job panicked.";
+
+const ERR_ZERO_THREADS: &str = "A batch needs at least one thread.";
+
+#[cfg(test)]
+mod tests {
+    use super::{Batch, Job, JobStart};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::{StopConditions, StopReason},
+        universe::grid2d::Size2D,
+    };
+
+    #[test]
+    fn sweep() {
+        let soup = |seed| {
+            Job::soup(Size2D(32, 32), 0.3, seed, GameOfLife::Alive, 2000)
+                .stop_when(StopConditions::new().extinction().stabilisation(2))
+        };
+        let results = Batch::new()
+            .threads(3)
+            .job(
+                Job::pattern("Blinker", game_of_life::blinker(), 100)
+                    .stop_when(StopConditions::new().stabilisation(2)),
+            )
+            .job(soup(1))
+            .job(Job::pattern("R-pentomino", game_of_life::r_pentomino(), 10))
+            .job(
+                Job::pattern("Blinker, \"failing\"", game_of_life::blinker(), 10)
+                    .stop_when(StopConditions::new().when(|gen, _| gen == 3 && panic!("boom"))),
+            )
+            .job(soup(1))
+            .run();
+
+        // Summaries come back in order, and the failing job doesn't prevent others from running
+        let jobs = &results.jobs;
+        assert_eq!(jobs.len(), 5);
+        let blinker = jobs[0].outcome.clone().unwrap();
+        assert_eq!((blinker.period, blinker.final_population), (Some(2), 3));
+        let limited = jobs[2].outcome.clone().unwrap();
+        assert_eq!(limited.stop.reason, StopReason::GenerationLimit);
+        assert_eq!(limited.stop.generation, 10);
+        assert_eq!(jobs[3].outcome, Err(String::from("boom")));
+        assert_eq!(results.failures().count(), 1);
+
+        // Soups are reproducible
+        assert_eq!(
+            jobs[1].start,
+            JobStart::Soup {
+                seed: 1,
+                density: 0.3
+            }
+        );
+        assert_eq!(jobs[1].outcome, jobs[4].outcome);
+
+        let csv = results.render_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("B3/S23,Blinker,,,stabilisation,2,3,2,"));
+        assert!(lines[1].ends_with(','));
+        assert!(lines[2].starts_with("B3/S23,,1,0.3,"));
+        assert!(lines[4].starts_with("B3/S23,\"Blinker, \"\"failing\"\"\",,,,,,,"));
+        assert!(lines[4].ends_with(",boom"));
+    }
+}
@@ -0,0 +1,156 @@
+// Standard library
+use std::mem;
+
+// Local
+use super::{SimulatorSnapshot, UniverseHistory};
+use crate::universe::{GenerationDifference, Storable, Universe};
+
+/// BranchId
+
+/// Identifies a branch of a simulator's timeline. The original timeline is `BranchId::ROOT`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BranchId(pub usize);
+
+impl BranchId {
+    pub const ROOT: BranchId = BranchId(0);
+}
+
+/// BranchInfo
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BranchInfo {
+    pub id: BranchId,
+    /// Branch this one was forked from, `None` for the root.
+    pub parent: Option<BranchId>,
+    /// Generation at which the branch diverged from its parent (0 for the root).
+    pub fork_gen: usize,
+    pub highest_generation: usize,
+}
+
+/// Branches
+
+/// Keeps the histories of the branches that are not being simulated. The active branch's history
+/// is owned by the simulator and swapped in and out of here.
+pub(super) struct Branches<U: Universe, D: GenerationDifference<Universe = U>> {
+    current: BranchId,
+    infos: Vec<BranchInfo>,
+    parked: Vec<Option<UniverseHistory<U, D>>>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Branches<U, D> {
+    pub fn new() -> Self {
+        Self {
+            current: BranchId::ROOT,
+            infos: vec![BranchInfo {
+                id: BranchId::ROOT,
+                parent: None,
+                fork_gen: 0,
+                highest_generation: 0,
+            }],
+            parked: vec![None],
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> BranchId {
+        self.current
+    }
+
+    /// Forks the active history at `gen`, where the new branch starts from `universe`, and makes
+    /// the new branch active. Returns `None` if the generation isn't available.
+    pub fn fork(
+        &mut self,
+        active: &mut UniverseHistory<U, D>,
+        gen: usize,
+        universe: U,
+    ) -> Option<BranchId> {
+        let forked = active.fork(gen, universe)?;
+        let id = BranchId(self.infos.len());
+        self.infos.push(BranchInfo {
+            id,
+            parent: Some(self.current),
+            fork_gen: gen,
+            highest_generation: gen,
+        });
+        self.parked.push(None);
+        self.park(mem::replace(active, forked), id);
+        Some(id)
+    }
+
+    /// Makes another branch active. Returns false if there is no such branch.
+    pub fn switch(&mut self, active: &mut UniverseHistory<U, D>, id: BranchId) -> bool {
+        if id == self.current {
+            return true;
+        }
+        let history = match self.parked.get_mut(id.0).and_then(Option::take) {
+            Some(history) => history,
+            None => return false,
+        };
+        self.park(mem::replace(active, history), id);
+        true
+    }
+
+    pub fn list(&self, active: &UniverseHistory<U, D>) -> Vec<BranchInfo> {
+        let mut infos = self.infos.clone();
+        infos[self.current.0].highest_generation = active.highest_generation();
+        infos
+    }
+
+    /// Copies the histories of every branch, the active one included. The generations a branch
+    /// shares with its parent are only copied once.
+    pub fn snapshot(&self, active: &UniverseHistory<U, D>) -> SimulatorSnapshot<U, D> {
+        let branches = self
+            .list(active)
+            .into_iter()
+            .map(|info| {
+                let parent = info.parent.map(|id| self.history(id, active));
+                (info, self.history(info.id, active).snapshot(parent))
+            })
+            .collect();
+        SimulatorSnapshot {
+            current: self.current,
+            branches,
+        }
+    }
+
+    fn history<'a>(
+        &'a self,
+        id: BranchId,
+        active: &'a UniverseHistory<U, D>,
+    ) -> &'a UniverseHistory<U, D> {
+        self.parked[id.0].as_ref().unwrap_or(active)
+    }
+
+    fn park(&mut self, history: UniverseHistory<U, D>, new_current: BranchId) {
+        let info = &mut self.infos[self.current.0];
+        info.highest_generation = history.highest_generation();
+        self.parked[self.current.0] = Some(history);
+        self.current = new_current;
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> Branches<U, D> {
+    /// Restores the branches of a snapshot, and returns them along with the active history.
+    /// Branches still share the generations before their fork.
+    pub fn restore(snapshot: SimulatorSnapshot<U, D>) -> (Self, UniverseHistory<U, D>) {
+        let current = snapshot.current;
+        let mut infos = Vec::with_capacity(snapshot.branches.len());
+        let mut parked = Vec::with_capacity(snapshot.branches.len());
+        let mut active = None;
+        for (info, history) in UniverseHistory::restore_branches(snapshot.branches) {
+            if info.id == current {
+                active = Some(history);
+                parked.push(None);
+            } else {
+                parked.push(Some(history));
+            }
+            infos.push(info);
+        }
+        let branches = Self {
+            current,
+            infos,
+            parked,
+        };
+        (branches, active.unwrap())
+    }
+}
@@ -0,0 +1,237 @@
+// Standard library
+use std::thread;
+
+// Local
+use super::{AsyncSimulator, HistoryConfig, Simulator, SyncSimulator};
+use crate::{
+    automaton::{CPUCell, GPUCell},
+    universe::{
+        self, CPUDiffUniverse, GPUUniverse, GenerationDifference, ParallelCPUUniverse, Universe,
+    },
+};
+
+/// A simulator whose execution and backend were chosen at runtime.
+pub type BoxedSimulator<U, D> = Box<dyn Simulator<Universe = U, Diff = D>>;
+
+/// Whether generations are computed on the calling thread (`SyncSimulator`) or by a background
+/// runner (`AsyncSimulator`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Execution {
+    Sync,
+    Async,
+}
+
+/// Where generations are computed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// On a single CPU thread.
+    Cpu,
+    /// On as many CPU threads as the machine can run in parallel.
+    ParallelCpu,
+    /// On a Vulkan device.
+    Gpu,
+}
+
+/// SimulatorBuilder
+
+/// Builds simulators whose execution and backend come from runtime configuration, so that callers
+/// need a single code path whatever the backend. Every universe can evolve on a single CPU thread,
+/// and other backends are opted into with `support_*` methods, which are only available to
+/// universes that can use them. A backend that wasn't opted into or that the machine can't
+/// provide falls back to the next simplest one (GPU, then parallel CPU, then CPU).
+pub struct SimulatorBuilder<U: Universe, D: GenerationDifference<Universe = U>> {
+    execution: Execution,
+    backend: Backend,
+    cpu: Constructor<U, D>,
+    parallel_cpu: Option<Constructor<U, D>>,
+    gpu: Option<Constructor<U, D>>,
+}
+
+impl<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>> SimulatorBuilder<U, D>
+where
+    U::Cell: CPUCell,
+{
+    /// Creates a builder for synchronous simulators on a single CPU thread.
+    pub fn new() -> Self {
+        Self {
+            execution: Execution::Sync,
+            backend: Backend::Cpu,
+            cpu: cpu_simulator,
+            parallel_cpu: None,
+            gpu: None,
+        }
+    }
+}
+
+impl<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>> Default
+    for SimulatorBuilder<U, D>
+where
+    U::Cell: CPUCell,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>> SimulatorBuilder<U, D>
+where
+    U::Cell: CPUCell,
+{
+    pub fn support_parallel_cpu(mut self) -> Self {
+        self.parallel_cpu = Some(parallel_cpu_simulator);
+        self
+    }
+}
+
+impl<U: GPUUniverse, D: GenerationDifference<Universe = U>> SimulatorBuilder<U, D>
+where
+    U::Cell: GPUCell,
+{
+    pub fn support_gpu(mut self) -> Self {
+        self.gpu = Some(gpu_simulator);
+        self
+    }
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> SimulatorBuilder<U, D> {
+    pub fn execution(mut self, execution: Execution) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    /// Requests a backend, which is only used if it is available when building.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Returns the backend that building would use: the requested one if it is available,
+    /// otherwise the closest available fallback.
+    pub fn resolved_backend(&self) -> Backend {
+        let candidates: &[Backend] = match self.backend {
+            Backend::Gpu => &[Backend::Gpu, Backend::ParallelCpu, Backend::Cpu],
+            Backend::ParallelCpu => &[Backend::ParallelCpu, Backend::Cpu],
+            Backend::Cpu => &[Backend::Cpu],
+        };
+        *candidates
+            .iter()
+            .find(|backend| self.constructor(**backend).is_some())
+            .unwrap()
+    }
+
+    pub fn build(
+        &self,
+        start_universe: U,
+        config: impl Into<HistoryConfig<U, D>>,
+    ) -> BoxedSimulator<U, D> {
+        let constructor = self.constructor(self.resolved_backend()).unwrap();
+        constructor(self.execution, start_universe, config.into())
+    }
+
+    /// Returns how to build a simulator on a backend, if it is available.
+    fn constructor(&self, backend: Backend) -> Option<Constructor<U, D>> {
+        match backend {
+            Backend::Cpu => Some(self.cpu),
+            Backend::ParallelCpu => self
+                .parallel_cpu
+                .filter(|_| 1 < thread::available_parallelism().map_or(1, |n| n.get())),
+            Backend::Gpu => self.gpu.filter(|_| universe::gpu_available()),
+        }
+    }
+}
+
+type Constructor<U, D> = fn(Execution, U, HistoryConfig<U, D>) -> BoxedSimulator<U, D>;
+
+fn cpu_simulator<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>>(
+    execution: Execution,
+    start_universe: U,
+    config: HistoryConfig<U, D>,
+) -> BoxedSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    match execution {
+        Execution::Sync => Box::new(SyncSimulator::cpu_backend(start_universe, config)),
+        Execution::Async => Box::new(AsyncSimulator::cpu_backend(start_universe, config)),
+    }
+}
+
+fn parallel_cpu_simulator<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>>(
+    execution: Execution,
+    start_universe: U,
+    config: HistoryConfig<U, D>,
+) -> BoxedSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    match execution {
+        Execution::Sync => Box::new(SyncSimulator::parallel_cpu_backend(start_universe, config)),
+        Execution::Async => Box::new(AsyncSimulator::parallel_cpu_backend(start_universe, config)),
+    }
+}
+
+fn gpu_simulator<U: GPUUniverse, D: GenerationDifference<Universe = U>>(
+    execution: Execution,
+    start_universe: U,
+    config: HistoryConfig<U, D>,
+) -> BoxedSimulator<U, D>
+where
+    U::Cell: GPUCell,
+{
+    match execution {
+        Execution::Sync => Box::new(SyncSimulator::gpu_backend(start_universe, config)),
+        Execution::Async => Box::new(AsyncSimulator::gpu_backend(start_universe, config)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Execution, SimulatorBuilder};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::{Simulator, SyncSimulator},
+        universe::grid2d::static_grid2d::{GridDiff, StaticGrid2D},
+    };
+
+    type Builder = SimulatorBuilder<StaticGrid2D<GameOfLife>, GridDiff<GameOfLife>>;
+
+    #[test]
+    fn fallbacks() {
+        // The GPU backend wasn't opted into, and parallel CPU evolution needs several threads
+        let multi_threaded = std::thread::available_parallelism().unwrap().get() > 1;
+        let builder = Builder::new().backend(Backend::Gpu);
+        assert_eq!(builder.resolved_backend(), Backend::Cpu);
+        let builder = builder.support_parallel_cpu();
+        let expected = if multi_threaded {
+            Backend::ParallelCpu
+        } else {
+            Backend::Cpu
+        };
+        assert_eq!(builder.resolved_backend(), expected);
+        assert_eq!(
+            builder.backend(Backend::Cpu).resolved_backend(),
+            Backend::Cpu
+        );
+
+        let mut reference: SyncSimulator<_, GridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::r_pentomino(), 10);
+        reference.run(30);
+        for execution in [Execution::Sync, Execution::Async].iter() {
+            let mut simulator = Builder::new()
+                .execution(*execution)
+                .backend(Backend::ParallelCpu)
+                .support_parallel_cpu()
+                .build(game_of_life::r_pentomino(), 10);
+            simulator.run(30);
+            for gen in [0, 17, 30].iter() {
+                let cells = |universe: StaticGrid2D<GameOfLife>| {
+                    universe.iter().flatten().collect::<Vec<_>>()
+                };
+                assert_eq!(
+                    cells(simulator.get_generation(*gen).unwrap()),
+                    cells(reference.get_generation(*gen).unwrap())
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Standard library
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+// Local
+use crate::universe::{
+    grid2d::{Coordinates2D, SCoordinates2D, Universe2D},
+    Universe,
+};
+
+/// CycleMode
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CycleMode {
+    /// The universe must repeat exactly.
+    Exact,
+    /// The universe may repeat at a different position, as spaceships do.
+    ModuloTranslation,
+}
+
+/// Cycle
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Cycle {
+    /// Number of generations before the universe first enters the cycle (pre-period).
+    pub transient: usize,
+    pub period: usize,
+    /// Displacement of the universe's content over one period (always zero in exact mode).
+    pub displacement: (isize, isize),
+}
+
+impl Cycle {
+    /// Returns the generation at which the repetition was detected.
+    pub fn detected_at(&self) -> usize {
+        self.transient + self.period
+    }
+}
+
+/// CycleDetector
+
+/// Detects when a universe repeats one of its prior states by remembering a hash of every
+/// generation it observes. Only the content of the universe is hashed (i.e., the non-default
+/// cells), so that a pattern's position can optionally be ignored.
+pub struct CycleDetector<U: Universe> {
+    mode: CycleMode,
+    fingerprint: fn(&U, CycleMode) -> Fingerprint,
+    seen: HashMap<u64, (usize, SCoordinates2D)>,
+    /// Observation order of the remembered hashes, only kept when the period is bounded.
+    order: VecDeque<(usize, u64)>,
+    max_period: Option<usize>,
+    cycle: Option<Cycle>,
+}
+
+impl<U: Universe2D> CycleDetector<U>
+where
+    U::Cell: Hash,
+{
+    pub fn new(mode: CycleMode) -> Self {
+        Self {
+            mode,
+            fingerprint: fingerprint_2d,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            max_period: None,
+            cycle: None,
+        }
+    }
+}
+
+impl<U: Universe> CycleDetector<U> {
+    /// Only looks for cycles whose period is at most `max_period`, which bounds the number of
+    /// hashes the detector has to remember.
+    pub fn max_period(mut self, max_period: usize) -> Self {
+        self.max_period = Some(max_period);
+        self
+    }
+
+    #[inline]
+    pub fn mode(&self) -> CycleMode {
+        self.mode
+    }
+
+    /// Returns the first cycle found so far, if any.
+    #[inline]
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Forgets all observed generations and the cycle found, if any. This is needed when the
+    /// universe is modified by something else than its evolution.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+        self.cycle = None;
+    }
+
+    /// Records a generation. Returns the cycle the first time a generation repeats an earlier one.
+    /// Generations should be observed in increasing order, and the transient is only accurate if
+    /// observation started at generation 0.
+    pub fn observe(&mut self, gen: usize, universe: &U) -> Option<Cycle> {
+        if self.cycle.is_some() {
+            return None;
+        }
+        let fingerprint = (self.fingerprint)(universe, self.mode);
+        match self.seen.get(&fingerprint.hash) {
+            Some((first_gen, first_origin)) => {
+                let cycle = Cycle {
+                    transient: *first_gen,
+                    period: gen - first_gen,
+                    displacement: (
+                        fingerprint.origin.x() - first_origin.x(),
+                        fingerprint.origin.y() - first_origin.y(),
+                    ),
+                };
+                self.cycle = Some(cycle);
+
+                // We won't need the hashes anymore
+                self.seen = HashMap::new();
+                self.order = VecDeque::new();
+                Some(cycle)
+            }
+            None => {
+                self.seen
+                    .insert(fingerprint.hash, (gen, fingerprint.origin));
+                if let Some(max_period) = self.max_period {
+                    // Forget generations that are too old to be part of a short enough cycle
+                    self.order.push_back((gen, fingerprint.hash));
+                    while let Some(&(old_gen, old_hash)) = self.order.front() {
+                        if gen - old_gen < max_period {
+                            break;
+                        }
+                        self.seen.remove(&old_hash);
+                        self.order.pop_front();
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Fingerprint
+
+struct Fingerprint {
+    hash: u64,
+    /// Top-left corner of the box enclosing all non-default cells.
+    origin: SCoordinates2D,
+}
+
+fn fingerprint_2d<U: Universe2D>(universe: &U, mode: CycleMode) -> Fingerprint
+where
+    U::Cell: Hash,
+{
+    let bounds = universe.bounds();
+    let default_cell = U::Cell::default();
+    let mut cells = vec![];
+    for y in 0..bounds.size.lines() {
+        for x in 0..bounds.size.columns() {
+            let coords = bounds.to_absolute(Coordinates2D(x, y));
+            let cell = universe.get_signed(coords);
+            if cell != default_cell {
+                cells.push((coords, cell));
+            }
+        }
+    }
+
+    let origin = SCoordinates2D(
+        cells.iter().map(|(c, _)| c.x()).min().unwrap_or(0),
+        cells.iter().map(|(c, _)| c.y()).min().unwrap_or(0),
+    );
+    let (dx, dy) = match mode {
+        CycleMode::Exact => (0, 0),
+        CycleMode::ModuloTranslation => (origin.x(), origin.y()),
+    };
+    let mut hasher = DefaultHasher::new();
+    for (coords, cell) in cells {
+        (coords.x() - dx).hash(&mut hasher);
+        (coords.y() - dy).hash(&mut hasher);
+        cell.hash(&mut hasher);
+    }
+    Fingerprint {
+        hash: hasher.finish(),
+        origin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CycleDetector, CycleMode};
+    use crate::{
+        automaton::game_of_life::catalogue,
+        universe::{
+            grid2d::{infinite_grid2d::InfiniteGrid2D, SCoordinates2D},
+            CPUUniverse,
+        },
+    };
+
+    fn find_cycle(name: &str, mode: CycleMode, max_gens: usize) -> Option<super::Cycle> {
+        let mut universe = InfiniteGrid2D::new(4);
+        catalogue::find(name)
+            .unwrap()
+            .place(&mut universe, SCoordinates2D(0, 0));
+        let mut detector = CycleDetector::new(mode);
+        for gen in 0..max_gens {
+            if let Some(cycle) = detector.observe(gen, &universe) {
+                return Some(cycle);
+            }
+            universe = universe.cpu_evolve_once();
+        }
+        None
+    }
+
+    #[test]
+    fn oscillators_and_spaceships() {
+        let cycle = find_cycle("Pulsar", CycleMode::Exact, 10).unwrap();
+        assert_eq!((cycle.transient, cycle.period), (0, 3));
+        assert_eq!(cycle.detected_at(), 3);
+
+        // Spaceships only repeat modulo translation
+        assert!(find_cycle("Glider", CycleMode::Exact, 20).is_none());
+        let cycle = find_cycle("Glider", CycleMode::ModuloTranslation, 20).unwrap();
+        assert_eq!((cycle.transient, cycle.period), (0, 4));
+        assert_eq!(cycle.displacement, (1, 1));
+        let cycle = find_cycle("Lightweight spaceship", CycleMode::ModuloTranslation, 20).unwrap();
+        assert_eq!(cycle.displacement, (-2, 0));
+
+        // The diehard disappears after 130 generations and then stays empty
+        let cycle = find_cycle("Diehard", CycleMode::Exact, 200).unwrap();
+        assert_eq!((cycle.transient, cycle.period), (130, 1));
+    }
+}
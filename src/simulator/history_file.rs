@@ -0,0 +1,697 @@
+// Standard library
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Local
+use crate::universe::{
+    read_u32, read_u64, write_u32, write_u64, GenerationDifference, Storable, Universe,
+};
+
+/// Codec
+
+/// Storage functions for a universe and its differences. They are captured where the `Storable`
+/// bounds are known so that the history doesn't need to carry them around.
+pub(super) struct Codec<U: Universe, D: GenerationDifference<Universe = U>> {
+    pub universe_footprint: fn(&U) -> usize,
+    pub diff_footprint: fn(&D) -> usize,
+    pub store_universe: fn(&U, &mut dyn Write) -> io::Result<()>,
+    pub load_universe: fn(&mut dyn Read) -> io::Result<U>,
+    pub store_diff: fn(&D, &mut dyn Write) -> io::Result<()>,
+    pub load_diff: fn(&mut dyn Read) -> io::Result<D>,
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> Codec<U, D> {
+    pub fn new() -> Self {
+        Self {
+            universe_footprint: U::footprint,
+            diff_footprint: D::footprint,
+            store_universe: U::store,
+            load_universe: U::load,
+            store_diff: D::store,
+            load_diff: D::load,
+        }
+    }
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Clone for Codec<U, D> {
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+/// HistoryWriter
+
+/// Appends the checkpoints and differences of a running simulation to a history file.
+///
+/// A history file starts with a header (magic bytes, format version and reserved flags), followed
+/// by a sequence of records. Each record is made of a kind, a pair of generations, the length of
+/// its payload, the payload itself and a CRC-32 of everything that precedes it in the record.
+/// When the writer is finished, an index of all records is appended to the file along with a
+/// trailer pointing to it, so that the file can be opened without scanning it. Files that weren't
+/// finished (e.g., because the process was killed) are recovered up to the last complete record.
+pub(super) struct HistoryWriter<U: Universe, D: GenerationDifference<Universe = U>> {
+    file: BufWriter<File>,
+    pos: u64,
+    index: Vec<IndexEntry>,
+    codec: Codec<U, D>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> HistoryWriter<U, D> {
+    pub fn create(path: &Path, start_universe: &U, codec: Codec<U, D>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        write_u32(&mut file, VERSION)?;
+        write_u32(&mut file, 0)?;
+        let mut writer = Self {
+            file,
+            pos: HEADER_LEN,
+            index: vec![],
+            codec,
+        };
+        writer.write_checkpoint(0, start_universe)?;
+        Ok(writer)
+    }
+
+    pub fn write_checkpoint(&mut self, gen: usize, universe: &U) -> io::Result<()> {
+        let mut payload = vec![];
+        (self.codec.store_universe)(universe, &mut payload)?;
+        self.write_record(RecordKind::Checkpoint, gen, gen, &payload)?;
+
+        // Make sure that everything up to the checkpoint survives a crash
+        self.file.flush()
+    }
+
+    pub fn write_diff(&mut self, from: usize, to: usize, diff: &D) -> io::Result<()> {
+        let mut payload = vec![];
+        (self.codec.store_diff)(diff, &mut payload)?;
+        self.write_record(RecordKind::Diff, from, to, &payload)
+    }
+
+    /// Writes the difference made by editing cells of a generation, which applies after the
+    /// generation's checkpoint or evolution difference.
+    pub fn write_intervention(&mut self, gen: usize, diff: &D) -> io::Result<()> {
+        let mut payload = vec![];
+        (self.codec.store_diff)(diff, &mut payload)?;
+        self.write_record(RecordKind::Intervention, gen, gen, &payload)
+    }
+
+    /// Writes the index and trailer. Nothing should be written to the file afterwards.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut payload = vec![];
+        write_u64(&mut payload, self.index.len() as u64)?;
+        for entry in self.index.iter() {
+            entry.write(&mut payload)?;
+        }
+        let index_pos = self.pos;
+        self.write_record(RecordKind::Index, 0, 0, &payload)?;
+        write_u64(&mut self.file, index_pos)?;
+        self.file.write_all(TRAILER_MAGIC)?;
+        self.file.flush()
+    }
+
+    fn write_record(
+        &mut self,
+        kind: RecordKind,
+        from: usize,
+        to: usize,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let mut header = Vec::with_capacity(RECORD_HEADER_LEN as usize);
+        header.push(kind as u8);
+        write_u64(&mut header, from as u64)?;
+        write_u64(&mut header, to as u64)?;
+        write_u64(&mut header, payload.len() as u64)?;
+        self.file.write_all(&header)?;
+        self.file.write_all(payload)?;
+        write_u32(&mut self.file, crc32(crc32(0, &header), payload))?;
+
+        if kind != RecordKind::Index {
+            self.index.push(IndexEntry {
+                kind,
+                from,
+                to,
+                pos: self.pos,
+                len: payload.len() as u64,
+            });
+        }
+        self.pos += RECORD_HEADER_LEN + payload.len() as u64 + CHECKSUM_LEN;
+        Ok(())
+    }
+}
+
+/// HistoryFile
+
+/// A history file opened for reading. Only the index is loaded when opening the file, checkpoints
+/// and differences are read (and their checksums verified) on demand.
+pub struct HistoryFile<U: Universe, D: GenerationDifference<Universe = U>> {
+    file: RefCell<File>,
+    checkpoints: Vec<IndexEntry>,
+    diffs: Vec<IndexEntry>,
+    interventions: Vec<IndexEntry>,
+    recovered: bool,
+    codec: Codec<U, D>,
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> HistoryFile<U, D> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+
+        // Check the header
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data(ERR_NOT_A_HISTORY_FILE));
+        }
+        if read_u32(&mut file)? != VERSION {
+            return Err(invalid_data(ERR_UNSUPPORTED_VERSION));
+        }
+        read_u32(&mut file)?;
+
+        // Use the index if the file was finished, otherwise scan the records
+        let (entries, recovered) = match Self::read_index(&mut file, file_len)? {
+            Some(entries) => (entries, false),
+            None => (Self::scan(&mut file, file_len)?, true),
+        };
+
+        let (mut checkpoints, mut diffs, mut interventions) = (vec![], vec![], vec![]);
+        for entry in entries {
+            match entry.kind {
+                RecordKind::Checkpoint => checkpoints.push(entry),
+                RecordKind::Diff => diffs.push(entry),
+                RecordKind::Intervention => interventions.push(entry),
+                RecordKind::Index => (),
+            }
+        }
+        if checkpoints.first().map(|entry| entry.from) != Some(0) {
+            return Err(invalid_data(ERR_MISSING_START));
+        }
+        if diffs
+            .iter()
+            .enumerate()
+            .any(|(i, entry)| entry.from != i || entry.to != i + 1)
+        {
+            return Err(invalid_data(ERR_NON_CONTIGUOUS));
+        }
+        if interventions
+            .iter()
+            .any(|entry| entry.from != entry.to || diffs.len() < entry.to)
+            || interventions
+                .windows(2)
+                .any(|pair| pair[1].from < pair[0].from)
+        {
+            return Err(invalid_data(ERR_INVALID_INTERVENTION));
+        }
+
+        Ok(Self {
+            file: RefCell::new(file),
+            checkpoints,
+            diffs,
+            interventions,
+            recovered,
+            codec: Codec::new(),
+        })
+    }
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> HistoryFile<U, D> {
+    pub fn get_highest_generation(&self) -> usize {
+        self.diffs.len()
+    }
+
+    /// Returns whether the file wasn't properly finished, in which case only the complete records
+    /// at the beginning of the file are available.
+    pub fn is_recovered(&self) -> bool {
+        self.recovered
+    }
+
+    /// Returns the generations at which cells were edited, in the order the edits were made.
+    pub fn get_interventions(&self) -> Vec<usize> {
+        self.interventions.iter().map(|entry| entry.from).collect()
+    }
+
+    pub fn get_generation(&self, gen: usize) -> io::Result<Option<U>> {
+        if self.diffs.len() < gen {
+            return Ok(None);
+        }
+
+        // Start from the closest checkpoint before the target generation
+        let idx = match self
+            .checkpoints
+            .binary_search_by_key(&gen, |entry| entry.from)
+        {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        let checkpoint = &self.checkpoints[idx];
+        let mut file = self.file.borrow_mut();
+        let universe = (self.codec.load_universe)(&mut &read_payload(&mut file, checkpoint)?[..])?;
+
+        // Checkpoints are written before the generation's cells are edited
+        let mut acc_diff = D::empty_diff();
+        let mut edited = self.stack_interventions(&mut file, &mut acc_diff, checkpoint.from)?;
+        if checkpoint.from != gen {
+            self.stack_diffs(&mut file, &mut acc_diff, checkpoint.from, gen)?;
+            edited = true;
+        }
+        if edited {
+            if !acc_diff.fits(&universe) {
+                return Err(invalid_data(ERR_MISFIT_DIFF));
+            }
+            Ok(Some(acc_diff.apply_to(universe)))
+        } else {
+            Ok(Some(universe))
+        }
+    }
+
+    pub fn get_difference(&self, ref_gen: usize, target_gen: usize) -> io::Result<Option<D>> {
+        if target_gen < ref_gen {
+            panic!("{}", ERR_INCORRECT_DIFF);
+        }
+        if self.diffs.len() < target_gen {
+            return Ok(None);
+        }
+        let mut file = self.file.borrow_mut();
+        let mut acc_diff = D::empty_diff();
+        self.stack_diffs(&mut file, &mut acc_diff, ref_gen, target_gen)?;
+        Ok(Some(acc_diff))
+    }
+
+    /// Reads every record in the file to check its integrity.
+    pub fn verify(&self) -> io::Result<()> {
+        let mut file = self.file.borrow_mut();
+        for entry in self
+            .checkpoints
+            .iter()
+            .chain(self.diffs.iter())
+            .chain(self.interventions.iter())
+        {
+            read_payload(&mut file, entry)?;
+        }
+        Ok(())
+    }
+
+    /// Stacks the differences between two generations, along with the interventions made at
+    /// every generation after the first one.
+    fn stack_diffs(
+        &self,
+        file: &mut File,
+        acc_diff: &mut D,
+        from: usize,
+        to: usize,
+    ) -> io::Result<()> {
+        for entry in self.diffs[from..to].iter() {
+            let diff = (self.codec.load_diff)(&mut &read_payload(file, entry)?[..])?;
+            acc_diff.stack(&diff);
+            self.stack_interventions(file, acc_diff, entry.to)?;
+        }
+        Ok(())
+    }
+
+    /// Stacks the interventions made at a generation, and returns whether there were any.
+    fn stack_interventions(
+        &self,
+        file: &mut File,
+        acc_diff: &mut D,
+        gen: usize,
+    ) -> io::Result<bool> {
+        let start = self.interventions.partition_point(|entry| entry.from < gen);
+        let end = self
+            .interventions
+            .partition_point(|entry| entry.from <= gen);
+        for entry in self.interventions[start..end].iter() {
+            let diff = (self.codec.load_diff)(&mut &read_payload(file, entry)?[..])?;
+            acc_diff.stack(&diff);
+        }
+        Ok(start < end)
+    }
+
+    fn read_index(file: &mut File, file_len: u64) -> io::Result<Option<Vec<IndexEntry>>> {
+        if file_len < HEADER_LEN + TRAILER_LEN {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(file_len - TRAILER_LEN))?;
+        let index_pos = read_u64(file)?;
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if magic != *TRAILER_MAGIC {
+            return Ok(None);
+        }
+
+        // The index is itself a record, which ends where the trailer starts
+        let index_end = file_len - TRAILER_LEN;
+        if record_end(index_pos, 0).is_none_or(|end| index_end < end) {
+            return Err(invalid_data(ERR_RECORD_LENGTH));
+        }
+        file.seek(SeekFrom::Start(index_pos + RECORD_HEADER_LEN - 8))?;
+        let len = read_u64(file)?;
+        let index_record = IndexEntry {
+            kind: RecordKind::Index,
+            from: 0,
+            to: 0,
+            pos: index_pos,
+            len,
+        };
+        if record_end(index_pos, len) != Some(index_end) {
+            return Err(invalid_data(ERR_RECORD_LENGTH));
+        }
+        let payload = read_payload(file, &index_record)?;
+        let mut reader = &payload[..];
+        let n_entries = read_u64(&mut reader)?;
+        if n_entries != (reader.len() as u64) / INDEX_ENTRY_LEN {
+            return Err(invalid_data(ERR_RECORD_LENGTH));
+        }
+        let mut entries = Vec::with_capacity(n_entries as usize);
+        for _ in 0..n_entries {
+            let entry = IndexEntry::read(&mut reader)?;
+            // Records all precede the index
+            if record_end(entry.pos, entry.len).is_none_or(|end| index_pos < end) {
+                return Err(invalid_data(ERR_RECORD_LENGTH));
+            }
+            entries.push(entry);
+        }
+        Ok(Some(entries))
+    }
+
+    fn scan(file: &mut File, file_len: u64) -> io::Result<Vec<IndexEntry>> {
+        let mut entries = vec![];
+        let mut pos = HEADER_LEN;
+        while pos + RECORD_HEADER_LEN + CHECKSUM_LEN <= file_len {
+            file.seek(SeekFrom::Start(pos))?;
+            let mut kind = [0];
+            file.read_exact(&mut kind)?;
+            let kind = RecordKind::from_u8(kind[0])?;
+            let from = read_u64(file)? as usize;
+            let to = read_u64(file)? as usize;
+            let len = read_u64(file)?;
+            let end = record_end(pos, len).ok_or_else(|| invalid_data(ERR_RECORD_LENGTH))?;
+            if file_len < end || kind == RecordKind::Index {
+                // Incomplete record, or index of a file that was finished but got truncated
+                break;
+            }
+            entries.push(IndexEntry {
+                kind,
+                from,
+                to,
+                pos,
+                len,
+            });
+            pos = end;
+        }
+        Ok(entries)
+    }
+}
+
+/// RecordKind
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RecordKind {
+    Checkpoint = 1,
+    Diff = 2,
+    Index = 3,
+    Intervention = 4,
+}
+
+impl RecordKind {
+    fn from_u8(val: u8) -> io::Result<Self> {
+        match val {
+            1 => Ok(RecordKind::Checkpoint),
+            2 => Ok(RecordKind::Diff),
+            3 => Ok(RecordKind::Index),
+            4 => Ok(RecordKind::Intervention),
+            _ => Err(invalid_data(ERR_RECORD_KIND)),
+        }
+    }
+}
+
+/// IndexEntry
+
+#[derive(Debug, Copy, Clone)]
+struct IndexEntry {
+    kind: RecordKind,
+    from: usize,
+    to: usize,
+    /// Offset of the record in the file.
+    pos: u64,
+    /// Length of the record's payload.
+    len: u64,
+}
+
+impl IndexEntry {
+    fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[self.kind as u8])?;
+        write_u64(writer, self.from as u64)?;
+        write_u64(writer, self.to as u64)?;
+        write_u64(writer, self.pos)?;
+        write_u64(writer, self.len)
+    }
+
+    fn read(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut kind = [0];
+        reader.read_exact(&mut kind)?;
+        Ok(Self {
+            kind: RecordKind::from_u8(kind[0])?,
+            from: read_u64(reader)? as usize,
+            to: read_u64(reader)? as usize,
+            pos: read_u64(reader)?,
+            len: read_u64(reader)?,
+        })
+    }
+}
+
+/// Returns the offset right after a record, if it can be represented.
+fn record_end(pos: u64, len: u64) -> Option<u64> {
+    pos.checked_add(RECORD_HEADER_LEN)?
+        .checked_add(len)?
+        .checked_add(CHECKSUM_LEN)
+}
+
+/// Reads a record's payload, after checking its integrity.
+fn read_payload(file: &mut File, entry: &IndexEntry) -> io::Result<Vec<u8>> {
+    // Lengths come from the file itself, so check them before allocating
+    let file_len = file.metadata()?.len();
+    if record_end(entry.pos, entry.len).is_none_or(|end| file_len < end) {
+        return Err(invalid_data(ERR_RECORD_LENGTH));
+    }
+    file.seek(SeekFrom::Start(entry.pos))?;
+    let mut record = vec![0; (RECORD_HEADER_LEN + entry.len) as usize];
+    file.read_exact(&mut record)?;
+    if crc32(0, &record) != read_u32(file)? {
+        return Err(invalid_data(ERR_CHECKSUM));
+    }
+    Ok(record.split_off(RECORD_HEADER_LEN as usize))
+}
+
+/// Computes the CRC-32 (IEEE) of some data, starting from the CRC of the data preceding it.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+const MAGIC: &[u8; 8] = b"CELLHIST";
+const TRAILER_MAGIC: &[u8; 8] = b"CELLIDX\0";
+const VERSION: u32 = 2;
+const HEADER_LEN: u64 = 16;
+const RECORD_HEADER_LEN: u64 = 25;
+const CHECKSUM_LEN: u64 = 4;
+const TRAILER_LEN: u64 = 16;
+/// Length of an entry in the index (kind, generations, offset and length).
+const INDEX_ENTRY_LEN: u64 = 33;
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const ERR_NOT_A_HISTORY_FILE: &str = "The file isn't a history file.";
+const ERR_UNSUPPORTED_VERSION: &str = "The history file's format version isn't supported.";
+const ERR_MISSING_START: &str = "The history file doesn't contain the initial universe.";
+const ERR_NON_CONTIGUOUS: &str = "The history file's differences aren't contiguous.";
+const ERR_CHECKSUM: &str = "The history file is corrupted (checksum mismatch).";
+const ERR_RECORD_KIND: &str = "The history file contains an unknown record kind.";
+const ERR_RECORD_LENGTH: &str = "The history file contains a record with an invalid length.";
+const ERR_MISFIT_DIFF: &str = "The history file's differences don't fit its universes.";
+const ERR_INVALID_INTERVENTION: &str =
+    "The history file contains an intervention at a generation it doesn't have.";
+const ERR_INCORRECT_DIFF: &str = "Base generation should be smaller than target generation.";
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::{ErrorKind, Seek, SeekFrom, Write};
+
+    use super::HistoryFile;
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::{HistoryConfig, Simulator, SyncSimulator},
+        universe::{
+            grid2d::{
+                static_grid2d::{GridDiff, StaticGrid2D},
+                Coordinates2D,
+            },
+            GenerationDifference,
+        },
+    };
+
+    type Grid = StaticGrid2D<GameOfLife>;
+    type Diff = GridDiff<GameOfLife>;
+
+    fn same(g1: &Grid, g2: &Grid) -> bool {
+        g1.iter().flatten().eq(g2.iter().flatten())
+    }
+
+    #[test]
+    fn reopen() {
+        let path = std::env::temp_dir().join("cell_history_file_reopen.hist");
+        let reference = {
+            let mut simulator: SyncSimulator<Grid, Diff> = SyncSimulator::cpu_backend(
+                game_of_life::r_pentomino(),
+                HistoryConfig::new(8).persist(&path),
+            );
+            simulator.run(30);
+            (0..=30)
+                .map(|gen| simulator.get_generation(gen).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let file: HistoryFile<Grid, Diff> = HistoryFile::open(&path).unwrap();
+        assert!(!file.is_recovered());
+        assert_eq!(file.get_highest_generation(), 30);
+        file.verify().unwrap();
+        for (gen, universe) in reference.iter().enumerate() {
+            assert!(same(&file.get_generation(gen).unwrap().unwrap(), universe));
+        }
+        assert!(file.get_generation(31).unwrap().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn interventions() {
+        let path = std::env::temp_dir().join("cell_history_file_interventions.hist");
+        let reference = {
+            let mut simulator: SyncSimulator<Grid, Diff> = SyncSimulator::cpu_backend(
+                game_of_life::r_pentomino(),
+                HistoryConfig::new(8).persist(&path),
+            );
+            for &gen in [8, 11].iter() {
+                simulator.goto(gen);
+                simulator.edit(&[(Coordinates2D(0, 0), GameOfLife::Alive)]);
+            }
+            simulator.run(10);
+            (0..=21)
+                .map(|gen| simulator.get_generation(gen).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Edits are replayed at the checkpoint they were made on as well as between checkpoints
+        let file: HistoryFile<Grid, Diff> = HistoryFile::open(&path).unwrap();
+        assert_eq!(file.get_interventions(), vec![8, 11]);
+        file.verify().unwrap();
+        for (gen, universe) in reference.iter().enumerate() {
+            assert!(same(&file.get_generation(gen).unwrap().unwrap(), universe));
+        }
+        let diff = file.get_difference(3, 12).unwrap().unwrap();
+        assert!(same(&diff.apply_to(reference[3].clone()), &reference[12]));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_and_corrupted() {
+        let path = std::env::temp_dir().join("cell_history_file_corrupted.hist");
+        {
+            let mut simulator: SyncSimulator<Grid, Diff> = SyncSimulator::cpu_backend(
+                game_of_life::r_pentomino(),
+                HistoryConfig::new(8).persist(&path),
+            );
+            simulator.run(20);
+        }
+
+        // Cutting the file in the middle of a record loses the index and that record
+        let len = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len / 2).unwrap();
+        let history: HistoryFile<Grid, Diff> = HistoryFile::open(&path).unwrap();
+        assert!(history.is_recovered());
+        let max_gen = history.get_highest_generation();
+        assert!(0 < max_gen && max_gen < 20);
+        history.verify().unwrap();
+        assert!(history.get_generation(max_gen).unwrap().is_some());
+
+        // Flipping a byte is detected when reading the affected record
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(100)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        drop(file);
+        let history: HistoryFile<Grid, Diff> = HistoryFile::open(&path).unwrap();
+        assert!(history.verify().is_err());
+        assert!(history.get_generation(0).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        let path = std::env::temp_dir().join("cell_history_file_lengths.hist");
+        let write_history = || {
+            let mut simulator: SyncSimulator<Grid, Diff> = SyncSimulator::cpu_backend(
+                game_of_life::r_pentomino(),
+                HistoryConfig::new(8).persist(&path),
+            );
+            simulator.run(10);
+        };
+        let overwrite = |pos: u64, val: u64| {
+            let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+            file.seek(SeekFrom::Start(pos)).unwrap();
+            file.write_all(&val.to_le_bytes()).unwrap();
+        };
+        let invalid = || match HistoryFile::<Grid, Diff>::open(&path) {
+            Err(err) => err.kind() == ErrorKind::InvalidData,
+            Ok(_) => false,
+        };
+
+        // Offsets of the index, which the trailer points to
+        for &index_pos in [u64::MAX - 8, 1 << 40].iter() {
+            write_history();
+            let len = fs::metadata(&path).unwrap().len();
+            overwrite(len - 16, index_pos);
+            assert!(invalid());
+        }
+
+        // Payload length of the first record, when records are scanned without the index
+        write_history();
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        overwrite(33, u64::MAX - 8);
+        assert!(invalid());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,683 @@
+// Standard library
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::mem;
+
+// Local
+use super::{BranchId, BranchInfo, CellEdit, Simulator, Stop, StopConditions};
+use crate::{
+    automaton::AutomatonCell,
+    universe::{
+        grid2d::{Coordinates2D, SCoordinates2D, Size2D, Universe2D, Window2D},
+        load_capacity, read_cell, read_u32, read_u64, write_u32, write_u64, Storable, Universe,
+    },
+};
+
+/// Journal
+
+/// A compact record of everything needed to reproduce a simulation: the initial universe, the
+/// automaton, the seed the initial universe was generated from (if any), and every operation
+/// applied to the simulator, interleaved with hashes of the generations computed along the way.
+/// Automata are identified by their rule (see `AutomatonCell::rule`).
+pub struct Journal<U: Universe> {
+    automaton: String,
+    seed: Option<u64>,
+    hash_interval: usize,
+    start: U,
+    entries: Vec<JournalEntry<U>>,
+}
+
+impl<U: Universe> Journal<U> {
+    #[inline]
+    pub fn automaton(&self) -> &str {
+        &self.automaton
+    }
+
+    #[inline]
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns the number of generations between two hashes.
+    #[inline]
+    pub fn hash_interval(&self) -> usize {
+        self.hash_interval
+    }
+
+    /// Returns the universe the simulator replaying the journal should start from.
+    #[inline]
+    pub fn start_universe(&self) -> &U {
+        &self.start
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[JournalEntry<U>] {
+        &self.entries
+    }
+}
+
+impl<U: Universe2D> Journal<U>
+where
+    U::Cell: AutomatonCell<Encoded = u32>,
+{
+    /// Applies the journal's operations to a simulator that hasn't run yet and started from the
+    /// journal's universe, on any backend. Every recorded hash is verified along the way, and the
+    /// replay stops at the first generation that differs from the recording or that the simulator
+    /// can't reproduce.
+    pub fn replay<S: Simulator<Universe = U>>(&self, simulator: &mut S) -> Result<(), ReplayError> {
+        if simulator.get_highest_generation() != 0 {
+            panic!("{}", ERR_NOT_FRESH);
+        }
+        for entry in self.entries.iter() {
+            match entry {
+                JournalEntry::Run(n_gens) => simulator.run(*n_gens),
+                JournalEntry::Edit(edits) => simulator.edit(edits),
+                JournalEntry::Fork(gen, edits) => {
+                    if simulator.fork_at(*gen, edits).is_none() {
+                        return Err(ReplayError::Branches);
+                    }
+                }
+                JournalEntry::Switch(id) => {
+                    if !simulator.switch_branch(*id) {
+                        return Err(ReplayError::Branches);
+                    }
+                }
+                JournalEntry::Check(expected) => {
+                    let universe = simulator
+                        .get_generation(expected.generation)
+                        .ok_or(ReplayError::Unavailable(expected.generation))?;
+                    let actual = GenerationHash::compute(expected.generation, &universe);
+                    if actual.hash != expected.hash {
+                        return Err(ReplayError::Diverged(Divergence {
+                            generation: expected.generation,
+                            region: expected.differing_region(&actual),
+                        }));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<U: Universe2D + Storable> Storable for Journal<U>
+where
+    U::Coordinates: Storable,
+    U::Cell: AutomatonCell<Encoded = u32>,
+{
+    fn footprint(&self) -> usize {
+        let entries: usize = self
+            .entries
+            .iter()
+            .map(|entry| match entry {
+                JournalEntry::Edit(edits) | JournalEntry::Fork(_, edits) => {
+                    edits.capacity() * mem::size_of::<CellEdit<U>>()
+                }
+                JournalEntry::Check(check) => check.tiles.len() * mem::size_of::<TileEntry>(),
+                _ => 0,
+            })
+            .sum();
+        mem::size_of::<Self>()
+            + self.automaton.capacity()
+            + self.start.footprint()
+            + self.entries.capacity() * mem::size_of::<JournalEntry<U>>()
+            + entries
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, VERSION)?;
+        write_u64(writer, self.automaton.len() as u64)?;
+        writer.write_all(self.automaton.as_bytes())?;
+        match self.seed {
+            Some(seed) => {
+                writer.write_all(&[1])?;
+                write_u64(writer, seed)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        write_u64(writer, self.hash_interval as u64)?;
+        self.start.store(writer)?;
+
+        write_u64(writer, self.entries.len() as u64)?;
+        for entry in self.entries.iter() {
+            match entry {
+                JournalEntry::Run(n_gens) => {
+                    writer.write_all(&[TAG_RUN])?;
+                    write_u64(writer, *n_gens as u64)?;
+                }
+                JournalEntry::Edit(edits) => {
+                    writer.write_all(&[TAG_EDIT])?;
+                    store_edits::<U>(edits, writer)?;
+                }
+                JournalEntry::Fork(gen, edits) => {
+                    writer.write_all(&[TAG_FORK])?;
+                    write_u64(writer, *gen as u64)?;
+                    store_edits::<U>(edits, writer)?;
+                }
+                JournalEntry::Switch(id) => {
+                    writer.write_all(&[TAG_SWITCH])?;
+                    write_u64(writer, id.0 as u64)?;
+                }
+                JournalEntry::Check(check) => {
+                    writer.write_all(&[TAG_CHECK])?;
+                    write_u64(writer, check.generation as u64)?;
+                    write_u64(writer, check.hash)?;
+                    write_u64(writer, check.tiles.len() as u64)?;
+                    for (&(x, y), &hash) in check.tiles.iter() {
+                        write_u64(writer, x as u64)?;
+                        write_u64(writer, y as u64)?;
+                        write_u64(writer, hash)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data(ERR_NOT_A_JOURNAL));
+        }
+        if read_u32(reader)? != VERSION {
+            return Err(invalid_data(ERR_UNSUPPORTED_VERSION));
+        }
+        let automaton_len = read_u64(reader)? as usize;
+        if automaton_len != U::Cell::rule().len() {
+            return Err(invalid_data(ERR_AUTOMATON_MISMATCH));
+        }
+        let mut automaton = vec![0; automaton_len];
+        reader.read_exact(&mut automaton)?;
+        if automaton != U::Cell::rule().as_bytes() {
+            return Err(invalid_data(ERR_AUTOMATON_MISMATCH));
+        }
+        let mut has_seed = [0];
+        reader.read_exact(&mut has_seed)?;
+        let seed = match has_seed[0] {
+            0 => None,
+            _ => Some(read_u64(reader)?),
+        };
+        let hash_interval = read_u64(reader)? as usize;
+        let start = U::load(reader)?;
+
+        let n_entries = read_u64(reader)? as usize;
+        let mut entries = Vec::with_capacity(load_capacity(n_entries));
+        for _ in 0..n_entries {
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+            let entry = match tag[0] {
+                TAG_RUN => JournalEntry::Run(read_u64(reader)? as usize),
+                TAG_EDIT => JournalEntry::Edit(load_edits::<U>(reader)?),
+                TAG_FORK => {
+                    let gen = read_u64(reader)? as usize;
+                    JournalEntry::Fork(gen, load_edits::<U>(reader)?)
+                }
+                TAG_SWITCH => JournalEntry::Switch(BranchId(read_u64(reader)? as usize)),
+                TAG_CHECK => {
+                    let generation = read_u64(reader)? as usize;
+                    let hash = read_u64(reader)?;
+                    let mut tiles = BTreeMap::new();
+                    for _ in 0..read_u64(reader)? {
+                        let x = read_u64(reader)? as isize;
+                        let y = read_u64(reader)? as isize;
+                        tiles.insert((x, y), read_u64(reader)?);
+                    }
+                    JournalEntry::Check(GenerationHash {
+                        generation,
+                        hash,
+                        tiles,
+                    })
+                }
+                _ => return Err(invalid_data(ERR_ENTRY_KIND)),
+            };
+            entries.push(entry);
+        }
+
+        Ok(Self {
+            automaton: String::from_utf8_lossy(&automaton).into_owned(),
+            seed,
+            hash_interval,
+            start,
+            entries,
+        })
+    }
+}
+
+/// JournalEntry
+
+#[derive(Clone)]
+pub enum JournalEntry<U: Universe> {
+    /// Run for some generations (runs stopped by conditions are recorded as plain runs).
+    Run(usize),
+    /// Edit cells of the highest generation.
+    Edit(Vec<CellEdit<U>>),
+    /// Fork at a generation with some edits.
+    Fork(usize, Vec<CellEdit<U>>),
+    Switch(BranchId),
+    /// Hash of a generation computed while recording.
+    Check(GenerationHash),
+}
+
+/// GenerationHash
+
+/// A hash of a generation's non-default cells, along with hashes of the square tiles of
+/// `TILE_SIZE` cells the universe is divided into, which locate differences. Hashes only depend
+/// on the cells' positions and encoded states, so they are the same on every backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationHash {
+    pub generation: usize,
+    pub hash: u64,
+    /// Hashes of non-empty tiles, indexed by tile coordinates.
+    tiles: BTreeMap<(isize, isize), u64>,
+}
+
+impl GenerationHash {
+    fn compute<U: Universe2D>(generation: usize, universe: &U) -> Self
+    where
+        U::Cell: AutomatonCell<Encoded = u32>,
+    {
+        // Cells are visited line by line, and so are the cells of each tile
+        let bounds = universe.bounds();
+        let default_cell = U::Cell::default();
+        let mut tiles = BTreeMap::new();
+        for y in 0..bounds.size.lines() {
+            for x in 0..bounds.size.columns() {
+                let coords = bounds.to_absolute(Coordinates2D(x, y));
+                let cell = universe.get_signed(coords);
+                if cell != default_cell {
+                    let tile = (
+                        coords.x().div_euclid(TILE_SIZE as isize),
+                        coords.y().div_euclid(TILE_SIZE as isize),
+                    );
+                    let hash = tiles.entry(tile).or_insert(FNV_OFFSET);
+                    *hash = fnv1a(*hash, &(coords.x() as u64).to_le_bytes());
+                    *hash = fnv1a(*hash, &(coords.y() as u64).to_le_bytes());
+                    *hash = fnv1a(*hash, &cell.encode().to_le_bytes());
+                }
+            }
+        }
+
+        let mut hash = FNV_OFFSET;
+        for (&(x, y), &tile_hash) in tiles.iter() {
+            hash = fnv1a(hash, &(x as u64).to_le_bytes());
+            hash = fnv1a(hash, &(y as u64).to_le_bytes());
+            hash = fnv1a(hash, &tile_hash.to_le_bytes());
+        }
+        Self {
+            generation,
+            hash,
+            tiles,
+        }
+    }
+
+    /// Returns the smallest window containing every tile whose hash differs.
+    fn differing_region(&self, other: &Self) -> Window2D {
+        let differing = self
+            .tiles
+            .iter()
+            .filter(|(tile, hash)| other.tiles.get(tile) != Some(hash))
+            .chain(
+                other
+                    .tiles
+                    .iter()
+                    .filter(|(tile, _)| !self.tiles.contains_key(tile)),
+            )
+            .map(|(&(x, y), _)| SCoordinates2D(x, y));
+        let tiles = Window2D::enclosing(differing);
+        Window2D::new(
+            SCoordinates2D(
+                tiles.origin.x() * TILE_SIZE as isize,
+                tiles.origin.y() * TILE_SIZE as isize,
+            ),
+            Size2D(
+                tiles.size.columns() * TILE_SIZE,
+                tiles.size.lines() * TILE_SIZE,
+            ),
+        )
+    }
+}
+
+/// ReplayError
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReplayError {
+    /// A hashed generation differs from the recording.
+    Diverged(Divergence),
+    /// The simulator can't give back a generation that was hashed while recording (e.g., because
+    /// its history merged differences).
+    Unavailable(usize),
+    /// The simulator couldn't fork or switch branches as recorded.
+    Branches,
+}
+
+/// Divergence
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence {
+    /// First hashed generation that differs from the recording. The replay diverged after the
+    /// previous hash, which is at most `hash_interval` generations before.
+    pub generation: usize,
+    /// Smallest window containing every differing tile.
+    pub region: Window2D,
+}
+
+/// JournalRecorder
+
+/// Wraps a simulator to record a journal of the operations applied to it.
+pub struct JournalRecorder<S: Simulator> {
+    simulator: S,
+    journal: Journal<S::Universe>,
+}
+
+impl<S: Simulator> JournalRecorder<S>
+where
+    S::Universe: Universe2D,
+    <S::Universe as Universe>::Cell: AutomatonCell<Encoded = u32>,
+{
+    /// Starts recording the operations applied to a simulator that hasn't run yet. Generations are
+    /// hashed every `hash_interval` generations, as well as whenever cells are edited.
+    pub fn new(simulator: S, hash_interval: usize) -> Self {
+        if hash_interval == 0 {
+            panic!("{}", ERR_ZERO_INTERVAL);
+        }
+        if simulator.get_highest_generation() != 0 {
+            panic!("{}", ERR_NOT_FRESH);
+        }
+        let start = simulator.get_generation(0).unwrap();
+        let mut recorder = Self {
+            simulator,
+            journal: Journal {
+                automaton: <S::Universe as Universe>::Cell::rule().to_string(),
+                seed: None,
+                hash_interval,
+                start,
+                entries: vec![],
+            },
+        };
+        recorder.check(0);
+        recorder
+    }
+
+    /// Records the seed the initial universe was generated from.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.journal.seed = Some(seed);
+        self
+    }
+
+    #[inline]
+    pub fn journal(&self) -> &Journal<S::Universe> {
+        &self.journal
+    }
+
+    pub fn into_parts(self) -> (S, Journal<S::Universe>) {
+        (self.simulator, self.journal)
+    }
+
+    /// Hashes the generations due for a check after running from `from_gen` to `to_gen`.
+    fn check_run(&mut self, from_gen: usize, to_gen: usize) {
+        let interval = self.journal.hash_interval;
+        for gen in ((from_gen / interval + 1) * interval..=to_gen).step_by(interval) {
+            self.check(gen);
+        }
+    }
+
+    fn check(&mut self, gen: usize) {
+        // Generations lost when merging differences can't be checked
+        if let Some(universe) = self.simulator.get_generation(gen) {
+            let hash = GenerationHash::compute(gen, &universe);
+            self.journal.entries.push(JournalEntry::Check(hash));
+        }
+    }
+}
+
+impl<S: Simulator> Simulator for JournalRecorder<S>
+where
+    S::Universe: Universe2D,
+    <S::Universe as Universe>::Cell: AutomatonCell<Encoded = u32>,
+{
+    type Universe = S::Universe;
+    type Diff = S::Diff;
+
+    fn run(&mut self, n_gens: usize) {
+        let from_gen = self.simulator.get_highest_generation();
+        self.simulator.run(n_gens);
+        self.journal.entries.push(JournalEntry::Run(n_gens));
+        self.check_run(from_gen, from_gen + n_gens);
+    }
+
+    fn run_until(&mut self, conditions: StopConditions<Self::Universe>) -> Stop {
+        let from_gen = self.simulator.get_highest_generation();
+        let stop = self.simulator.run_until(conditions);
+        self.journal
+            .entries
+            .push(JournalEntry::Run(stop.generation - from_gen));
+        self.check_run(from_gen, stop.generation);
+        stop
+    }
+
+    fn get_highest_generation(&self) -> usize {
+        self.simulator.get_highest_generation()
+    }
+
+    fn get_generation(&self, gen: usize) -> Option<Self::Universe> {
+        self.simulator.get_generation(gen)
+    }
+
+    fn get_difference(&self, ref_gen: usize, target_gen: usize) -> Option<Self::Diff> {
+        self.simulator.get_difference(ref_gen, target_gen)
+    }
+
+    fn edit(&mut self, edits: &[CellEdit<Self::Universe>]) {
+        self.simulator.edit(edits);
+        self.journal
+            .entries
+            .push(JournalEntry::Edit(edits.to_vec()));
+        self.check(self.simulator.get_highest_generation());
+    }
+
+    fn fork_at(&mut self, gen: usize, edits: &[CellEdit<Self::Universe>]) -> Option<BranchId> {
+        let id = self.simulator.fork_at(gen, edits)?;
+        self.journal
+            .entries
+            .push(JournalEntry::Fork(gen, edits.to_vec()));
+        self.check(gen);
+        Some(id)
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.simulator.branches()
+    }
+
+    fn current_branch(&self) -> BranchId {
+        self.simulator.current_branch()
+    }
+
+    fn switch_branch(&mut self, id: BranchId) -> bool {
+        let switched = self.simulator.switch_branch(id);
+        if switched {
+            self.journal.entries.push(JournalEntry::Switch(id));
+        }
+        switched
+    }
+}
+
+fn store_edits<U: Universe>(edits: &[CellEdit<U>], writer: &mut dyn Write) -> io::Result<()>
+where
+    U::Coordinates: Storable,
+    U::Cell: AutomatonCell<Encoded = u32>,
+{
+    write_u64(writer, edits.len() as u64)?;
+    for (coords, cell) in edits.iter() {
+        coords.store(writer)?;
+        write_u32(writer, cell.encode())?;
+    }
+    Ok(())
+}
+
+fn load_edits<U: Universe>(reader: &mut dyn Read) -> io::Result<Vec<CellEdit<U>>>
+where
+    U::Coordinates: Storable,
+    U::Cell: AutomatonCell<Encoded = u32>,
+{
+    let n_edits = read_u64(reader)? as usize;
+    let mut edits = Vec::with_capacity(load_capacity(n_edits));
+    for _ in 0..n_edits {
+        let coords = U::Coordinates::load(reader)?;
+        edits.push((coords, read_cell(reader)?));
+    }
+    Ok(edits)
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Tile coordinates and hash.
+type TileEntry = ((isize, isize), u64);
+
+/// Width and height of the tiles hashed separately to locate divergences.
+pub const TILE_SIZE: usize = 16;
+
+const MAGIC: &[u8; 8] = b"CELLJRNL";
+const VERSION: u32 = 1;
+const TAG_RUN: u8 = 1;
+const TAG_EDIT: u8 = 2;
+const TAG_FORK: u8 = 3;
+const TAG_SWITCH: u8 = 4;
+const TAG_CHECK: u8 = 5;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+const ERR_ZERO_INTERVAL: &str = "The hash interval must be at least one generation.";
+const ERR_NOT_FRESH: &str = "Journals must be recorded and replayed from generation 0.";
+const ERR_NOT_A_JOURNAL: &str = "The data isn't a journal.";
+const ERR_UNSUPPORTED_VERSION: &str = "The journal's format version isn't supported.";
+const ERR_AUTOMATON_MISMATCH: &str = "The journal was recorded with another automaton.";
+const ERR_ENTRY_KIND: &str = "The journal contains an unknown entry kind.";
+
+#[cfg(test)]
+mod tests {
+    use super::{Journal, JournalEntry, JournalRecorder, ReplayError, TILE_SIZE};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::{AsyncSimulator, BranchId, Simulator, SyncSimulator},
+        universe::{
+            grid2d::{
+                static_grid2d::{GridDiff, StaticGrid2D},
+                Coordinates2D, SCoordinates2D,
+            },
+            Storable,
+        },
+    };
+
+    use std::io::ErrorKind;
+
+    type Grid = StaticGrid2D<GameOfLife>;
+    type Diff = GridDiff<GameOfLife>;
+
+    #[test]
+    fn record_and_replay() {
+        let simulator: SyncSimulator<Grid, Diff> =
+            SyncSimulator::cpu_backend(game_of_life::r_pentomino(), 10);
+        let mut recorder = JournalRecorder::new(simulator, 4).seed(42);
+        recorder.run(10);
+        recorder.edit(&[(Coordinates2D(1, 1), GameOfLife::Alive)]);
+        recorder.run(10);
+        recorder.fork_at(15, &[(Coordinates2D(2, 2), GameOfLife::Alive)]);
+        recorder.run(7);
+        recorder.switch_branch(BranchId::ROOT);
+        recorder.run(3);
+        let (_, journal) = recorder.into_parts();
+
+        // Journals survive storage and replay exactly on another backend
+        let mut bytes = vec![];
+        journal.store(&mut bytes).unwrap();
+        let journal: Journal<Grid> = Journal::load(&mut &bytes[..]).unwrap();
+        assert_eq!(journal.seed(), Some(42));
+        let mut replayed: AsyncSimulator<Grid, Diff> =
+            AsyncSimulator::cpu_backend(journal.start_universe().clone(), 10);
+        assert_eq!(journal.replay(&mut replayed), Ok(()));
+        assert_eq!(replayed.get_highest_generation(), 23);
+
+        // Dropping the edit is caught by the hash taken right after it
+        let mut tampered: Journal<Grid> = Journal::load(&mut &bytes[..]).unwrap();
+        tampered
+            .entries
+            .retain(|entry| !matches!(entry, JournalEntry::Edit(_)));
+        let mut replayed: SyncSimulator<Grid, Diff> =
+            SyncSimulator::cpu_backend(journal.start_universe().clone(), 10);
+        let divergence = match tampered.replay(&mut replayed) {
+            Err(ReplayError::Diverged(divergence)) => divergence,
+            result => panic!("Unexpected replay result {:?}", result),
+        };
+        assert_eq!(divergence.generation, 10);
+        assert!(divergence.region.contains(SCoordinates2D(1, 1)));
+        assert_eq!(divergence.region.size.columns() % TILE_SIZE, 0);
+
+        // Generations the simulator can't give back are reported as well
+        let mut unavailable: Journal<Grid> = Journal::load(&mut &bytes[..]).unwrap();
+        let mut check = match &unavailable.entries[0] {
+            JournalEntry::Check(check) => check.clone(),
+            _ => panic!("The initial generation wasn't hashed."),
+        };
+        check.generation = 1000;
+        unavailable.entries.push(JournalEntry::Check(check));
+        let mut replayed: SyncSimulator<Grid, Diff> =
+            SyncSimulator::cpu_backend(journal.start_universe().clone(), 10);
+        assert_eq!(
+            unavailable.replay(&mut replayed),
+            Err(ReplayError::Unavailable(1000))
+        );
+    }
+
+    #[test]
+    fn corrupted_journals() {
+        let simulator: SyncSimulator<Grid, Diff> =
+            SyncSimulator::cpu_backend(game_of_life::blinker(), 10);
+        let mut recorder = JournalRecorder::new(simulator, 4);
+        recorder.edit(&[(Coordinates2D(1, 1), GameOfLife::Alive)]);
+        let (_, journal) = recorder.into_parts();
+        let mut bytes = vec![];
+        journal.store(&mut bytes).unwrap();
+        assert_eq!(journal.automaton(), "B3/S23");
+        let invalid = |bytes: &[u8]| match Journal::<Grid>::load(&mut &bytes[..]) {
+            Err(err) => err.kind() == ErrorKind::InvalidData,
+            Ok(_) => false,
+        };
+
+        // Automata are checked, and so are the lengths of their identifiers
+        let mut tampered = bytes.clone();
+        tampered[20] = b'X';
+        assert!(invalid(&tampered));
+        let mut tampered = bytes.clone();
+        tampered[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(invalid(&tampered));
+
+        // Huge entry counts fail when reading runs out of data
+        let mut start = vec![];
+        journal.start_universe().store(&mut start).unwrap();
+        let n_entries = 8 + 4 + 8 + 6 + 1 + 8 + start.len();
+        let mut tampered = bytes.clone();
+        tampered[n_entries..n_entries + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Journal::<Grid>::load(&mut &tampered[..]).is_err());
+
+        // Edits to unknown states
+        let mut edit = vec![2, 1, 0, 0, 0, 0, 0, 0, 0];
+        Coordinates2D(1, 1).store(&mut edit).unwrap();
+        let edit_pos = bytes
+            .windows(edit.len())
+            .position(|window| window == &edit[..])
+            .unwrap();
+        let mut tampered = bytes.clone();
+        tampered[edit_pos + edit.len()] = 7;
+        assert!(invalid(&tampered));
+    }
+}
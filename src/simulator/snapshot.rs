@@ -0,0 +1,122 @@
+// Standard library
+use std::io::{self, Read, Write};
+use std::mem;
+
+// Local
+use super::{universe_history::HistorySnapshot, BranchId, BranchInfo};
+use crate::universe::{
+    load_capacity, read_u32, read_u64, write_u32, write_u64, GenerationDifference, Storable,
+    Universe,
+};
+
+/// SimulatorSnapshot
+
+/// The state of a simulator at some point: the history of every branch along with its
+/// configuration, and the branch being simulated. A simulator resumed from a snapshot (on either
+/// backend) continues from the highest generation of that branch.
+///
+/// What is tied to the running process is left out: event receivers, subscriptions, cycle
+/// detection and the file the history was persisted to. Like cloned universes, universes loaded
+/// from a snapshot recreate their GPU state when they are first evolved on the GPU.
+pub struct SimulatorSnapshot<U: Universe, D: GenerationDifference<Universe = U>> {
+    pub(super) current: BranchId,
+    /// Branches ordered by identifier. A branch's history leaves out the generations it shares
+    /// with its parent.
+    pub(super) branches: Vec<(BranchInfo, HistorySnapshot<U, D>)>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> SimulatorSnapshot<U, D> {
+    /// Returns the generation the simulation will continue from.
+    pub fn highest_generation(&self) -> usize {
+        self.branches[self.current.0].1.highest_generation()
+    }
+
+    pub fn branches(&self) -> Vec<BranchInfo> {
+        self.branches.iter().map(|(info, _)| *info).collect()
+    }
+
+    #[inline]
+    pub fn current_branch(&self) -> BranchId {
+        self.current
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> Storable
+    for SimulatorSnapshot<U, D>
+{
+    fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+            + self
+                .branches
+                .iter()
+                .map(|(_, history)| mem::size_of::<BranchInfo>() + history.footprint())
+                .sum::<usize>()
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(writer, VERSION)?;
+        write_u64(writer, self.current.0 as u64)?;
+        write_u64(writer, self.branches.len() as u64)?;
+        for (info, history) in self.branches.iter() {
+            // The parent of the root is stored as the root itself
+            write_u64(writer, info.parent.unwrap_or(BranchId::ROOT).0 as u64)?;
+            write_u64(writer, info.fork_gen as u64)?;
+            history.store(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data(ERR_NOT_A_SNAPSHOT));
+        }
+        if read_u32(reader)? != VERSION {
+            return Err(invalid_data(ERR_UNSUPPORTED_VERSION));
+        }
+        let current = BranchId(read_u64(reader)? as usize);
+        let n_branches = read_u64(reader)? as usize;
+        if n_branches <= current.0 {
+            return Err(invalid_data(ERR_INVALID_BRANCHES));
+        }
+
+        let mut branches: Vec<(BranchInfo, HistorySnapshot<U, D>)> =
+            Vec::with_capacity(load_capacity(n_branches));
+        for id in 0..n_branches {
+            let parent = BranchId(read_u64(reader)? as usize);
+            let fork_gen = read_u64(reader)? as usize;
+            if 0 < id && id <= parent.0 {
+                return Err(invalid_data(ERR_INVALID_BRANCHES));
+            }
+            let mut ancestors = vec![];
+            let mut ancestor = if id == 0 { None } else { Some(parent) };
+            while let Some(BranchId(ancestor_id)) = ancestor {
+                let (info, history) = &branches[ancestor_id];
+                ancestors.push(history);
+                ancestor = info.parent;
+            }
+            let history = HistorySnapshot::load(reader, &ancestors)?;
+            let info = BranchInfo {
+                id: BranchId(id),
+                parent: if id == 0 { None } else { Some(parent) },
+                fork_gen,
+                highest_generation: history.highest_generation(),
+            };
+            branches.push((info, history));
+        }
+        Ok(Self { current, branches })
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+const MAGIC: &[u8; 8] = b"CELLSNAP";
+const VERSION: u32 = 3;
+
+const ERR_NOT_A_SNAPSHOT: &str = "The data isn't a simulator snapshot.";
+const ERR_UNSUPPORTED_VERSION: &str = "The snapshot's format version isn't supported.";
+const ERR_INVALID_BRANCHES: &str = "The snapshot's branches are inconsistent.";
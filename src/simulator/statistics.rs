@@ -0,0 +1,266 @@
+// Local
+use crate::{
+    automaton::AutomatonCell,
+    universe::{
+        grid2d::{Difference2D, SCoordinates2D, Size2D, Universe2D, Window2D},
+        GenerationDifference, Universe,
+    },
+};
+
+/// GenerationStats
+
+/// Statistics about a single generation. Births and deaths are cells that went from the default
+/// state to another one and back, respectively. The bounding box and centre of mass only take
+/// non-default cells into account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationStats<C: AutomatonCell> {
+    pub generation: usize,
+    /// Number of cells in each state within the universe's bounds, in the order in which states
+    /// were first encountered.
+    pub population: Vec<(C, usize)>,
+    pub births: usize,
+    pub deaths: usize,
+    pub bounding_box: Option<Window2D>,
+    pub centre_of_mass: Option<(f64, f64)>,
+}
+
+impl<C: AutomatonCell> GenerationStats<C> {
+    /// Returns the number of cells in some state.
+    pub fn count(&self, state: C) -> usize {
+        self.population
+            .iter()
+            .find(|(cell, _)| *cell == state)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Returns the number of non-default cells.
+    pub fn live_cells(&self) -> usize {
+        self.population
+            .iter()
+            .filter(|(cell, _)| *cell != C::default())
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// StatisticsCollector
+
+/// Computes statistics for every generation it observes and keeps them as a time series. Births
+/// and deaths come from the generations' differences when they are available, which saves
+/// comparing every cell to the previous generation.
+pub struct StatisticsCollector<U: Universe, D: GenerationDifference<Universe = U>> {
+    scan: fn(usize, &U) -> GenerationStats<U::Cell>,
+    births_deaths: fn(&D, &U) -> (usize, usize),
+    /// Last generation observed, only kept when its difference to the next one may have to be
+    /// computed.
+    previous: Option<U>,
+    series: Vec<GenerationStats<U::Cell>>,
+}
+
+impl<U: Universe2D, D: Difference2D<Universe = U>> StatisticsCollector<U, D> {
+    pub fn new() -> Self {
+        Self {
+            scan: scan_2d,
+            births_deaths: births_deaths_2d,
+            previous: None,
+            series: vec![],
+        }
+    }
+}
+
+impl<U: Universe2D, D: Difference2D<Universe = U>> Default for StatisticsCollector<U, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> StatisticsCollector<U, D> {
+    /// Returns the statistics of every generation observed so far, ordered by generation.
+    #[inline]
+    pub fn series(&self) -> &[GenerationStats<U::Cell>] {
+        &self.series
+    }
+
+    pub fn get(&self, gen: usize) -> Option<&GenerationStats<U::Cell>> {
+        self.series
+            .binary_search_by_key(&gen, |stats| stats.generation)
+            .ok()
+            .map(|idx| &self.series[idx])
+    }
+
+    /// Starts a new time series from a generation (e.g., when continuing from another branch).
+    pub fn start(&mut self, gen: usize, universe: &U) {
+        self.series = vec![(self.scan)(gen, universe)];
+        self.previous = Some(universe.clone());
+    }
+
+    /// Records the generation following the last one observed, along with its difference to it
+    /// if evolving produced one.
+    pub fn observe(&mut self, gen: usize, universe: &U, diff: Option<&D>) {
+        let (births, deaths) = match (diff, &self.previous) {
+            (Some(diff), _) => (self.births_deaths)(diff, universe),
+            (None, Some(previous)) => {
+                (self.births_deaths)(&D::get_diff(previous, universe), universe)
+            }
+            (None, None) => (0, 0),
+        };
+        self.previous = match diff {
+            Some(_) => None,
+            None => Some(universe.clone()),
+        };
+
+        let mut stats = (self.scan)(gen, universe);
+        stats.births = births;
+        stats.deaths = deaths;
+        self.series.push(stats);
+    }
+
+    /// Updates the statistics of the last generation observed after its cells were edited.
+    /// Edited cells don't count as births or deaths.
+    pub fn edit(&mut self, gen: usize, universe: &U) {
+        let mut stats = (self.scan)(gen, universe);
+        if let Some(last) = self.series.last() {
+            if last.generation == gen {
+                stats.births = last.births;
+                stats.deaths = last.deaths;
+                self.series.pop();
+            }
+        }
+        self.series.push(stats);
+        if self.previous.is_some() {
+            self.previous = Some(universe.clone());
+        }
+    }
+}
+
+/// Computes the statistics of a generation that can be computed from the universe alone.
+fn scan_2d<U: Universe2D>(gen: usize, universe: &U) -> GenerationStats<U::Cell> {
+    let bounds = universe.bounds();
+    let mut population: Vec<(U::Cell, usize)> = vec![];
+    let mut corners: Option<(SCoordinates2D, SCoordinates2D)> = None;
+    let (mut sum_x, mut sum_y, mut n_live) = (0.0, 0.0, 0usize);
+    for y in 0..bounds.size.lines() {
+        for x in 0..bounds.size.columns() {
+            let coords = SCoordinates2D(
+                bounds.origin.x() + x as isize,
+                bounds.origin.y() + y as isize,
+            );
+            let cell = universe.get_signed(coords);
+            match population.iter_mut().find(|(state, _)| *state == cell) {
+                Some((_, count)) => *count += 1,
+                None => population.push((cell, 1)),
+            }
+            if cell == U::Cell::default() {
+                continue;
+            }
+            corners = match corners {
+                Some((min, max)) => Some((
+                    SCoordinates2D(min.x().min(coords.x()), min.y().min(coords.y())),
+                    SCoordinates2D(max.x().max(coords.x()), max.y().max(coords.y())),
+                )),
+                None => Some((coords, coords)),
+            };
+            sum_x += coords.x() as f64;
+            sum_y += coords.y() as f64;
+            n_live += 1;
+        }
+    }
+
+    GenerationStats {
+        generation: gen,
+        population,
+        births: 0,
+        deaths: 0,
+        bounding_box: corners.map(|(min, max)| {
+            let size = Size2D(
+                (max.x() - min.x() + 1) as usize,
+                (max.y() - min.y() + 1) as usize,
+            );
+            Window2D::new(min, size)
+        }),
+        centre_of_mass: if n_live == 0 {
+            None
+        } else {
+            Some((sum_x / n_live as f64, sum_y / n_live as f64))
+        },
+    }
+}
+
+fn births_deaths_2d<U: Universe2D, D: Difference2D<Universe = U>>(
+    diff: &D,
+    universe: &U,
+) -> (usize, usize) {
+    let dead = U::Cell::default();
+    diff.changed_cells(universe).into_iter().fold(
+        (0, 0),
+        |(births, deaths), (_, old_cell, new_cell)| {
+            if old_cell == dead && new_cell != dead {
+                (births + 1, deaths)
+            } else if old_cell != dead && new_cell == dead {
+                (births, deaths + 1)
+            } else {
+                (births, deaths)
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatisticsCollector;
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        universe::{
+            grid2d::{
+                static_grid2d::{CompactGridDiff, GridDiff, StaticGrid2D},
+                Coordinates2D, SCoordinates2D, Size2D, Window2D,
+            },
+            CPUDiffUniverse, CPUUniverse, Universe,
+        },
+    };
+
+    type Grid = StaticGrid2D<GameOfLife>;
+
+    #[test]
+    fn blinker_statistics() {
+        let mut universe = game_of_life::blinker();
+        let mut with_diffs: StatisticsCollector<Grid, GridDiff<GameOfLife>> =
+            StatisticsCollector::new();
+        let mut without_diffs: StatisticsCollector<Grid, CompactGridDiff<GameOfLife>> =
+            StatisticsCollector::new();
+        with_diffs.start(0, &universe);
+        without_diffs.start(0, &universe);
+        for gen in 1..=4 {
+            let (next, diff) = universe.clone().cpu_evolve_once_diff();
+            with_diffs.observe(gen, &next, Some(&diff));
+            without_diffs.observe(gen, &universe.cpu_evolve_once(), None);
+            universe = next;
+        }
+        assert_eq!(with_diffs.series(), without_diffs.series());
+
+        let first = with_diffs.get(0).unwrap();
+        assert_eq!(first.count(GameOfLife::Alive), 3);
+        assert_eq!(first.count(GameOfLife::Dead), 22);
+        assert_eq!((first.births, first.deaths), (0, 0));
+        assert_eq!(
+            first.bounding_box,
+            Some(Window2D::new(SCoordinates2D(1, 2), Size2D(3, 1)))
+        );
+        let stats = with_diffs.get(3).unwrap();
+        assert_eq!(stats.live_cells(), 3);
+        assert_eq!((stats.births, stats.deaths), (2, 2));
+        assert_eq!(
+            stats.bounding_box,
+            Some(Window2D::new(SCoordinates2D(2, 1), Size2D(1, 3)))
+        );
+        assert_eq!(stats.centre_of_mass, Some((2.0, 2.0)));
+
+        // Edits aren't births
+        universe.set(Coordinates2D(0, 0), GameOfLife::Alive);
+        with_diffs.edit(4, &universe);
+        let stats = with_diffs.get(4).unwrap();
+        assert_eq!((stats.live_cells(), stats.births), (4, 2));
+        with_diffs.start(4, &universe);
+        assert_eq!(with_diffs.series().len(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+// Standard library
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+// Local
+use super::{Cycle, CycleDetector, CycleMode};
+use crate::universe::{
+    grid2d::{Coordinates2D, SCoordinates2D, Universe2D},
+    Universe,
+};
+
+/// StopReason
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    /// All cells are in their default state.
+    Extinction,
+    /// The universe entered a cycle whose period is short enough.
+    Stabilisation(Cycle),
+    /// The population (number of non-default cells) went above the threshold.
+    PopulationAbove(usize),
+    /// The population (number of non-default cells) went below the threshold.
+    PopulationBelow(usize),
+    /// The cell at the given coordinates reached the watched state.
+    CellReached(SCoordinates2D),
+    /// The wall-clock time limit was exceeded.
+    Timeout,
+    /// The maximum number of generations to run was reached.
+    GenerationLimit,
+    /// The user-provided predicate with this index (in the order they were added) returned true.
+    Predicate(usize),
+    /// The run was cancelled through the simulator's controls.
+    Cancelled,
+}
+
+/// Stop
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Stop {
+    pub reason: StopReason,
+    /// Generation at which the condition fired, which is the simulator's new highest generation.
+    pub generation: usize,
+}
+
+/// StopConditions
+
+/// A set of conditions under which a simulation should stop, checked after each generation (and
+/// once before running). The first condition that holds, in the order they are listed in
+/// `StopReason`, is reported. So that runs always end, they are limited to
+/// `DEFAULT_MAX_GENERATIONS` generations unless another limit is set with `max_generations`.
+pub struct StopConditions<U: Universe> {
+    population: fn(&U) -> usize,
+    get_cell: fn(&U, SCoordinates2D) -> U::Cell,
+    extinction: bool,
+    stabilisation: Option<CycleDetector<U>>,
+    population_above: Option<usize>,
+    population_below: Option<usize>,
+    cells: Vec<(SCoordinates2D, U::Cell)>,
+    timeout: Option<Duration>,
+    max_gens: usize,
+    predicates: Vec<Predicate<U>>,
+    start: Option<(usize, Instant)>,
+}
+
+impl<U: Universe2D> StopConditions<U>
+where
+    U::Cell: Hash,
+{
+    pub fn new() -> Self {
+        Self {
+            population: population_2d,
+            get_cell: U::get_signed,
+            extinction: false,
+            stabilisation: None,
+            population_above: None,
+            population_below: None,
+            cells: vec![],
+            timeout: None,
+            max_gens: DEFAULT_MAX_GENERATIONS,
+            predicates: vec![],
+            start: None,
+        }
+    }
+
+    /// Stops once the universe repeats itself with a period of at most `max_period` (1 for still
+    /// lifes).
+    pub fn stabilisation(mut self, max_period: usize) -> Self {
+        self.stabilisation = Some(CycleDetector::new(CycleMode::Exact).max_period(max_period));
+        self
+    }
+}
+
+impl<U: Universe2D> Default for StopConditions<U>
+where
+    U::Cell: Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U: Universe> StopConditions<U> {
+    /// Stops once all cells are in their default state.
+    pub fn extinction(mut self) -> Self {
+        self.extinction = true;
+        self
+    }
+
+    /// Stops once more than `threshold` cells are in a non-default state.
+    pub fn population_above(mut self, threshold: usize) -> Self {
+        self.population_above = Some(threshold);
+        self
+    }
+
+    /// Stops once fewer than `threshold` cells are in a non-default state.
+    pub fn population_below(mut self, threshold: usize) -> Self {
+        self.population_below = Some(threshold);
+        self
+    }
+
+    /// Stops once the cell at `coords` is in the given state. May be called several times to
+    /// watch several cells.
+    pub fn cell_reaches(mut self, coords: SCoordinates2D, state: U::Cell) -> Self {
+        self.cells.push((coords, state));
+        self
+    }
+
+    /// Stops once the simulation has been running for longer than `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stops after `max_gens` generations if no other condition fired before, instead of after
+    /// `DEFAULT_MAX_GENERATIONS`.
+    pub fn max_generations(mut self, max_gens: usize) -> Self {
+        self.max_gens = max_gens;
+        self
+    }
+
+    /// Stops once the closure, called with each generation number and universe, returns true.
+    pub fn when(mut self, predicate: impl FnMut(usize, &U) -> bool + Send + 'static) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Checks all conditions against a new generation, returning the first one that holds. The
+    /// first call marks the beginning of the run.
+    pub(super) fn check(&mut self, gen: usize, universe: &U) -> Option<StopReason> {
+        let (start_gen, start_time) = *self.start.get_or_insert_with(|| (gen, Instant::now()));
+
+        // Counting cells requires a pass over the whole universe, so only do it when needed
+        let population = if self.extinction
+            || self.population_above.is_some()
+            || self.population_below.is_some()
+        {
+            (self.population)(universe)
+        } else {
+            0
+        };
+        if self.extinction && population == 0 {
+            return Some(StopReason::Extinction);
+        }
+        if let Some(detector) = &mut self.stabilisation {
+            if let Some(cycle) = detector.observe(gen, universe) {
+                return Some(StopReason::Stabilisation(cycle));
+            }
+        }
+        if let Some(threshold) = self.population_above {
+            if population > threshold {
+                return Some(StopReason::PopulationAbove(threshold));
+            }
+        }
+        if let Some(threshold) = self.population_below {
+            if population < threshold {
+                return Some(StopReason::PopulationBelow(threshold));
+            }
+        }
+        for (coords, state) in &self.cells {
+            if (self.get_cell)(universe, *coords) == *state {
+                return Some(StopReason::CellReached(*coords));
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            if start_time.elapsed() > timeout {
+                return Some(StopReason::Timeout);
+            }
+        }
+        if gen - start_gen >= self.max_gens {
+            return Some(StopReason::GenerationLimit);
+        }
+        for (idx, predicate) in self.predicates.iter_mut().enumerate() {
+            if predicate(gen, universe) {
+                return Some(StopReason::Predicate(idx));
+            }
+        }
+        None
+    }
+}
+
+type Predicate<U> = Box<dyn FnMut(usize, &U) -> bool + Send>;
+
+/// Maximum number of generations run by `run_until`, unless conditions set another limit.
+pub const DEFAULT_MAX_GENERATIONS: usize = 10_000;
+
+pub(super) fn population_2d<U: Universe2D>(universe: &U) -> usize {
+    let bounds = universe.bounds();
+    let default_cell = U::Cell::default();
+    let mut population = 0;
+    for y in 0..bounds.size.lines() {
+        for x in 0..bounds.size.columns() {
+            if universe.get_signed(bounds.to_absolute(Coordinates2D(x, y))) != default_cell {
+                population += 1;
+            }
+        }
+    }
+    population
+}
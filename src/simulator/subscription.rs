@@ -0,0 +1,202 @@
+// Standard library
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+
+// Local
+use crate::universe::{GenerationDifference, Universe};
+
+/// Subscription
+
+/// Describes what a subscriber receives from a simulator, and how often.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    differences: bool,
+    every: usize,
+    capacity: usize,
+    overflow: OverflowPolicy,
+}
+
+impl Subscription {
+    /// Subscribes to full universes.
+    pub fn universes() -> Self {
+        Self {
+            differences: false,
+            every: 1,
+            capacity: DEFAULT_CAPACITY,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+
+    /// Subscribes to differences, each one going from the previously delivered generation (or the
+    /// generation at which the subscription was made) to the new one.
+    pub fn differences() -> Self {
+        Self {
+            differences: true,
+            ..Self::universes()
+        }
+    }
+
+    /// Only delivers generations that are a multiple of `n`.
+    pub fn every(mut self, n: usize) -> Self {
+        if n == 0 {
+            panic!("{}", ERR_ZERO_PERIOD);
+        }
+        self.every = n;
+        self
+    }
+
+    /// Sets how many updates may be waiting in the channel before the overflow policy kicks in.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        if capacity == 0 {
+            panic!("{}", ERR_ZERO_CAPACITY);
+        }
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub(super) fn into_observer<U: Universe, D: GenerationDifference<Universe = U>>(
+        self,
+    ) -> (BoxedObserver<U, D>, Receiver<GenerationUpdate<U, D>>) {
+        let (tx, rx) = mpsc::sync_channel(self.capacity);
+        let observer = ChannelObserver {
+            subscription: self,
+            tx,
+            pending: None,
+        };
+        (Box::new(observer), rx)
+    }
+}
+
+/// OverflowPolicy
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// The simulation waits for the subscriber to catch up (backpressure). A synchronous simulator
+    /// will therefore block if the subscriber lives on the same thread and falls behind.
+    Block,
+    /// Updates that don't fit in the channel are dropped. Differences then span from the last
+    /// delivered generation, so that consumers can still follow the simulation.
+    Drop,
+}
+
+/// GenerationUpdate
+
+/// A generation is delivered again when its cells are edited.
+pub enum GenerationUpdate<U, D> {
+    Universe { gen: usize, universe: U },
+    Difference { ref_gen: usize, gen: usize, diff: D },
+}
+
+impl<U, D> GenerationUpdate<U, D> {
+    /// Returns the generation the update brings the subscriber to.
+    pub fn generation(&self) -> usize {
+        match self {
+            Self::Universe { gen, .. } | Self::Difference { gen, .. } => *gen,
+        }
+    }
+}
+
+/// Observer
+
+/// Something notified of every generation a simulator computes.
+pub(super) trait Observer<U, D>: Send {
+    /// Called once, with the simulator's current generation, when the observer is attached.
+    fn start(&mut self, gen: usize, universe: &U);
+
+    /// Called with the difference to the previous generation when evolving produced one, and
+    /// without any for edits. Returns false once the observer is no longer interested.
+    fn observe(&mut self, gen: usize, universe: &U, diff: Option<&D>) -> bool;
+}
+
+pub(super) type BoxedObserver<U, D> = Box<dyn Observer<U, D>>;
+
+struct ChannelObserver<U, D> {
+    subscription: Subscription,
+    tx: SyncSender<GenerationUpdate<U, D>>,
+    /// Only kept for differences.
+    pending: Option<PendingDifference<U, D>>,
+}
+
+/// The difference from the last delivered generation to the latest one, along with the latest
+/// universe, which is kept up-to-date by applying differences rather than by copying every
+/// generation.
+struct PendingDifference<U, D> {
+    ref_gen: usize,
+    diff: D,
+    universe: U,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Observer<U, D> for ChannelObserver<U, D> {
+    fn start(&mut self, gen: usize, universe: &U) {
+        if self.subscription.differences {
+            self.pending = Some(PendingDifference {
+                ref_gen: gen,
+                diff: D::empty_diff(),
+                universe: universe.clone(),
+            });
+        }
+    }
+
+    fn observe(&mut self, gen: usize, universe: &U, diff: Option<&D>) -> bool {
+        if let Some(mut pending) = self.pending.take() {
+            match diff {
+                Some(diff) => {
+                    pending.universe = diff.apply_to(pending.universe);
+                    pending.diff.stack(diff);
+                }
+                // Edits are compared with the generation they replace
+                None => {
+                    pending
+                        .diff
+                        .stack(&D::get_diff(&pending.universe, universe));
+                    pending.universe = universe.clone();
+                }
+            }
+            self.pending = Some(pending);
+        }
+        if !gen.is_multiple_of(self.subscription.every) {
+            return true;
+        }
+
+        let update = match &mut self.pending {
+            Some(pending) => GenerationUpdate::Difference {
+                ref_gen: pending.ref_gen,
+                gen,
+                diff: mem::replace(&mut pending.diff, D::empty_diff()),
+            },
+            None => GenerationUpdate::Universe {
+                gen,
+                universe: universe.clone(),
+            },
+        };
+        let undelivered = match self.subscription.overflow {
+            OverflowPolicy::Block => match self.tx.send(update) {
+                Ok(()) => None,
+                Err(_) => return false,
+            },
+            OverflowPolicy::Drop => match self.tx.try_send(update) {
+                Ok(()) => None,
+                Err(TrySendError::Full(update)) => Some(update),
+                Err(TrySendError::Disconnected(_)) => return false,
+            },
+        };
+        if let Some(pending) = &mut self.pending {
+            match undelivered {
+                // The next difference also covers the dropped one
+                Some(GenerationUpdate::Difference { diff, .. }) => pending.diff = diff,
+                _ => pending.ref_gen = gen,
+            }
+        }
+        true
+    }
+}
+
+const DEFAULT_CAPACITY: usize = 64;
+
+const ERR_ZERO_PERIOD: &str = "A subscription's period must be at least one generation.";
+const ERR_ZERO_CAPACITY: &str = "A subscription's channel must hold at least one update.";
@@ -1,40 +1,208 @@
+// Standard library
+use std::hash::Hash;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
 // Local
-use super::{Simulator, UniverseHistory};
+use super::{
+    branch::Branches, subscription::BoxedObserver, BranchId, BranchInfo, CellEdit, Cycle,
+    CycleDetector, CycleMode, GenerationStats, GenerationUpdate, HistoryConfig, Simulator,
+    SimulatorEvent, SimulatorSnapshot, StatisticsCollector, Stop, StopConditions, Subscription,
+    UniverseHistory,
+};
 use crate::{
     automaton::{CPUCell, GPUCell},
-    universe::{CPUUniverse, GPUUniverse, GenerationDifference, Universe},
+    universe::{
+        grid2d::{Difference2D, Universe2D},
+        CPUDiffUniverse, GPUUniverse, GenerationDifference, ParallelCPUUniverse, Storable,
+        Universe,
+    },
 };
 
 pub struct SyncSimulator<U: Universe, D: GenerationDifference<Universe = U>> {
     current_gen: U,
     history: UniverseHistory<U, D>,
-    evolve_fn: fn(U) -> U,
+    evolve_fn: EvolveFn<U, D>,
     max_gen: usize,
+    cycle_detector: Option<CycleDetector<U>>,
+    statistics: Option<StatisticsCollector<U, D>>,
+    event_senders: Vec<Sender<SimulatorEvent>>,
+    observers: Vec<BoxedObserver<U, D>>,
+    branches: Branches<U, D>,
 }
 
 impl<U: Universe, D: GenerationDifference<Universe = U>> SyncSimulator<U, D> {
-    fn new(start_universe: U, f_check: usize, evolve_fn: fn(U) -> U) -> Self {
+    fn new(
+        start_universe: U,
+        config: impl Into<HistoryConfig<U, D>>,
+        evolve_fn: EvolveFn<U, D>,
+    ) -> Self {
         Self {
             current_gen: start_universe.clone(),
-            history: UniverseHistory::new(start_universe, f_check),
+            history: UniverseHistory::new(start_universe, config),
             evolve_fn,
             max_gen: 0,
+            cycle_detector: None,
+            statistics: None,
+            event_senders: vec![],
+            observers: vec![],
+            branches: Branches::new(),
+        }
+    }
+
+    /// Builds a simulator from the history and branches of a snapshot, continuing from the
+    /// highest generation of its active branch.
+    fn from_snapshot(snapshot: SimulatorSnapshot<U, D>, evolve_fn: EvolveFn<U, D>) -> Self
+    where
+        U: Storable,
+        D: Storable,
+    {
+        let (branches, history) = Branches::restore(snapshot);
+        let max_gen = history.highest_generation();
+        Self {
+            current_gen: history.get_gen(max_gen).unwrap(),
+            history,
+            evolve_fn,
+            max_gen,
+            cycle_detector: None,
+            statistics: None,
+            event_senders: vec![],
+            observers: vec![],
+            branches,
         }
     }
+
+    /// Captures the simulator's state, so that the simulation can be resumed later (e.g., in
+    /// another process after storing the snapshot).
+    pub fn snapshot(&self) -> SimulatorSnapshot<U, D> {
+        self.branches.snapshot(&self.history)
+    }
+
+    /// Returns the estimated number of bytes used by the history, if it has a memory budget.
+    pub fn history_memory_usage(&self) -> usize {
+        self.history.memory_usage()
+    }
+
+    /// Returns a channel on which the simulator's events will be sent.
+    pub fn events(&mut self) -> Receiver<SimulatorEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.event_senders.push(tx);
+        rx
+    }
+
+    /// Streams new generations (or differences) to a channel, starting after the current one.
+    pub fn subscribe(&mut self, subscription: Subscription) -> Receiver<GenerationUpdate<U, D>> {
+        let (mut observer, rx) = subscription.into_observer();
+        observer.start(self.max_gen, &self.current_gen);
+        self.observers.push(observer);
+        rx
+    }
+
+    /// Returns the cycle found by the cycle detector, if it is enabled and the universe repeated.
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle_detector.as_ref().and_then(|d| d.cycle())
+    }
+
+    /// Returns the statistics collected so far, ordered by generation.
+    pub fn statistics(&self) -> &[GenerationStats<U::Cell>] {
+        match &self.statistics {
+            Some(collector) => collector.series(),
+            None => &[],
+        }
+    }
+
+    fn evolve_once(&mut self, universe: U) -> U {
+        let (universe, diff) = (self.evolve_fn)(universe);
+        if let Some(collector) = &mut self.statistics {
+            collector.observe(self.max_gen + 1, &universe, diff.as_ref());
+        }
+        // Forget about subscribers that went away
+        let gen = self.max_gen + 1;
+        self.observers
+            .retain_mut(|observer| observer.observe(gen, &universe, diff.as_ref()));
+        match diff {
+            Some(diff) => self.history.push_with_diff(universe.clone(), diff),
+            None => self.history.push(universe.clone()),
+        }
+        self.max_gen += 1;
+
+        let cycle = match &mut self.cycle_detector {
+            Some(detector) => detector.observe(self.max_gen, &universe),
+            None => None,
+        };
+        if let Some(cycle) = cycle {
+            self.emit(SimulatorEvent::CycleDetected(cycle));
+        }
+        universe
+    }
+
+    /// Picks up where the newly active branch's history left off. Cycle detection stops, and
+    /// subscribers continue from the branch's highest generation.
+    fn enter_branch(&mut self) {
+        self.max_gen = self.history.highest_generation();
+        self.current_gen = self.history.get_gen(self.max_gen).unwrap();
+        self.cycle_detector = None;
+        if let Some(collector) = &mut self.statistics {
+            collector.start(self.max_gen, &self.current_gen);
+        }
+        for observer in self.observers.iter_mut() {
+            observer.start(self.max_gen, &self.current_gen);
+        }
+    }
+
+    fn emit(&mut self, event: SimulatorEvent) {
+        // Forget about receivers that were dropped
+        self.event_senders.retain(|tx| tx.send(event).is_ok());
+    }
+}
+
+impl<U: Universe2D, D: GenerationDifference<Universe = U>> SyncSimulator<U, D>
+where
+    U::Cell: Hash,
+{
+    /// Starts looking for cycles from the current generation. A `SimulatorEvent::CycleDetected`
+    /// is emitted the first time the universe repeats a prior state.
+    pub fn detect_cycles(&mut self, mode: CycleMode) {
+        let mut detector = CycleDetector::new(mode);
+        detector.observe(self.max_gen, &self.current_gen);
+        self.cycle_detector = Some(detector);
+    }
+}
+
+impl<U: Universe2D, D: Difference2D<Universe = U>> SyncSimulator<U, D> {
+    /// Starts computing statistics for every generation from the current one. Statistics start
+    /// over when switching branches.
+    pub fn collect_statistics(&mut self) {
+        let mut collector = StatisticsCollector::new();
+        collector.start(self.max_gen, &self.current_gen);
+        self.statistics = Some(collector);
+    }
 }
 
 impl<U: Universe, D: GenerationDifference<Universe = U>> Simulator for SyncSimulator<U, D> {
     type Universe = U;
+    type Diff = D;
 
     fn run(&mut self, n_gens: usize) {
         let mut universe = self.current_gen.clone();
-        let evolve_once = self.evolve_fn;
         for _ in 0..n_gens {
-            universe = evolve_once(universe);
-            self.history.push(universe.clone());
+            universe = self.evolve_once(universe);
         }
         self.current_gen = universe;
-        self.max_gen += n_gens;
+    }
+
+    fn run_until(&mut self, mut conditions: StopConditions<U>) -> Stop {
+        let mut universe = self.current_gen.clone();
+        loop {
+            if let Some(reason) = conditions.check(self.max_gen, &universe) {
+                self.current_gen = universe;
+                return Stop {
+                    reason,
+                    generation: self.max_gen,
+                };
+            }
+            universe = self.evolve_once(universe);
+        }
     }
 
     fn get_highest_generation(&self) -> usize {
@@ -44,14 +212,87 @@ impl<U: Universe, D: GenerationDifference<Universe = U>> Simulator for SyncSimul
     fn get_generation(&self, gen: usize) -> Option<Self::Universe> {
         self.history.get_gen(gen)
     }
+
+    fn get_difference(&self, ref_gen: usize, target_gen: usize) -> Option<Self::Diff> {
+        self.history.get_diff(ref_gen, target_gen)
+    }
+
+    fn edit(&mut self, edits: &[CellEdit<U>]) {
+        for (coords, cell) in edits {
+            self.current_gen.set(coords.clone(), *cell);
+        }
+        self.history.intervene(self.current_gen.clone());
+
+        let gen = self.max_gen;
+        if let Some(detector) = &mut self.cycle_detector {
+            detector.reset();
+            detector.observe(gen, &self.current_gen);
+        }
+        if let Some(collector) = &mut self.statistics {
+            collector.edit(gen, &self.current_gen);
+        }
+        self.emit(SimulatorEvent::Intervention(gen));
+        let universe = &self.current_gen;
+        self.observers
+            .retain_mut(|observer| observer.observe(gen, universe, None));
+    }
+
+    fn fork_at(&mut self, gen: usize, edits: &[CellEdit<U>]) -> Option<BranchId> {
+        let mut universe = self.history.get_gen(gen)?;
+        for (coords, cell) in edits {
+            universe.set(coords.clone(), *cell);
+        }
+        let id = self.branches.fork(&mut self.history, gen, universe)?;
+        self.enter_branch();
+        Some(id)
+    }
+
+    fn branches(&self) -> Vec<BranchInfo> {
+        self.branches.list(&self.history)
+    }
+
+    fn current_branch(&self) -> BranchId {
+        self.branches.current()
+    }
+
+    fn switch_branch(&mut self, id: BranchId) -> bool {
+        if id == self.branches.current() {
+            return true;
+        }
+        if !self.branches.switch(&mut self.history, id) {
+            return false;
+        }
+        self.enter_branch();
+        true
+    }
+}
+
+impl<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>> SyncSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    pub fn cpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, cpu_evolve_once)
+    }
+}
+
+impl<U: CPUDiffUniverse<D> + Storable, D: GenerationDifference<Universe = U> + Storable>
+    SyncSimulator<U, D>
+where
+    U::Cell: CPUCell,
+{
+    pub fn cpu_backend_from(snapshot: SimulatorSnapshot<U, D>) -> Self {
+        Self::from_snapshot(snapshot, cpu_evolve_once)
+    }
 }
 
-impl<U: CPUUniverse, D: GenerationDifference<Universe = U>> SyncSimulator<U, D>
+impl<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>> SyncSimulator<U, D>
 where
     U::Cell: CPUCell,
 {
-    pub fn cpu_backend(start_universe: U, f_check: usize) -> Self {
-        Self::new(start_universe, f_check, U::cpu_evolve_once)
+    /// Evolves universes on as many threads as the machine can run in parallel.
+    pub fn parallel_cpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, par_cpu_evolve_once)
     }
 }
 
@@ -59,7 +300,48 @@ impl<U: GPUUniverse, D: GenerationDifference<Universe = U>> SyncSimulator<U, D>
 where
     U::Cell: GPUCell,
 {
-    pub fn gpu_backend(start_universe: U, f_check: usize) -> Self {
-        Self::new(start_universe, f_check, U::gpu_evolve_once)
+    pub fn gpu_backend(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        Self::new(start_universe, config, gpu_evolve_once)
+    }
+}
+
+impl<U: GPUUniverse + Storable, D: GenerationDifference<Universe = U> + Storable>
+    SyncSimulator<U, D>
+where
+    U::Cell: GPUCell,
+{
+    pub fn gpu_backend_from(snapshot: SimulatorSnapshot<U, D>) -> Self {
+        Self::from_snapshot(snapshot, gpu_evolve_once)
     }
 }
+
+/// Evolves a universe for one generation, along with the difference if it was produced directly.
+type EvolveFn<U, D> = fn(U) -> (U, Option<D>);
+
+fn cpu_evolve_once<U: CPUDiffUniverse<D>, D: GenerationDifference<Universe = U>>(
+    universe: U,
+) -> (U, Option<D>)
+where
+    U::Cell: CPUCell,
+{
+    let (universe, diff) = universe.cpu_evolve_once_diff();
+    (universe, Some(diff))
+}
+
+fn par_cpu_evolve_once<U: ParallelCPUUniverse<D>, D: GenerationDifference<Universe = U>>(
+    universe: U,
+) -> (U, Option<D>)
+where
+    U::Cell: CPUCell,
+{
+    let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let (universe, diff) = universe.par_cpu_evolve_once_diff(n_threads);
+    (universe, Some(diff))
+}
+
+fn gpu_evolve_once<U: GPUUniverse, D>(universe: U) -> (U, Option<D>)
+where
+    U::Cell: GPUCell,
+{
+    (universe.gpu_evolve_once(), None)
+}
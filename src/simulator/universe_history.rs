@@ -1,96 +1,570 @@
 // Standard library
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
 // Local
+use super::{
+    async_simulator::QueueSlot,
+    branch::{BranchId, BranchInfo, Branches},
+    history_file::{Codec, HistoryWriter},
+    SimulatorSnapshot,
+};
 use crate::{
     advanced_channels::{MailType, SlaveEndpoint},
-    universe::{GenerationDifference, Universe},
+    universe::{load_capacity, read_u64, write_u64, GenerationDifference, Storable, Universe},
 };
 
+/// HistoryConfig
+
+pub struct HistoryConfig<U: Universe, D: GenerationDifference<Universe = U>> {
+    /// Number of generations between two checkpoints (0 means that only the initial universe is
+    /// kept as a checkpoint). With adaptive checkpoints, this is the maximum interval instead.
+    pub f_check: usize,
+    pub budget: Option<MemoryBudget<U, D>>,
+    /// Number of reconstructed generations kept in memory to speed up lookups (these don't count
+    /// towards the memory budget).
+    pub cache_size: usize,
+    adaptive: Option<(AdaptiveCheckpoints, Codec<U, D>)>,
+    persist: Option<(PathBuf, Codec<U, D>)>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> HistoryConfig<U, D> {
+    pub fn new(f_check: usize) -> Self {
+        Self {
+            f_check,
+            budget: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+            adaptive: None,
+            persist: None,
+        }
+    }
+
+    pub fn memory_budget(mut self, budget: MemoryBudget<U, D>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> HistoryConfig<U, D> {
+    /// Writes the history to a file as the simulation runs, so that it can be reopened later with
+    /// `HistoryFile`. The file is overwritten if it already exists.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.persist = Some((path.as_ref().to_path_buf(), Codec::new()));
+        self
+    }
+
+    /// Places checkpoints depending on how much the universe changes, in addition to the fixed
+    /// interval (which can be set to 0 to rely only on adaptive checkpoints).
+    pub fn adaptive_checkpoints(mut self, adaptive: AdaptiveCheckpoints) -> Self {
+        self.adaptive = Some((adaptive, Codec::new()));
+        self
+    }
+}
+
+/// AdaptiveCheckpoints
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveCheckpoints {
+    /// A checkpoint is placed when the differences accumulated since the last checkpoint use more
+    /// memory than this fraction of the last checkpoint.
+    pub max_diff_fraction: f64,
+    /// A checkpoint is placed when reconstructing a generation from the last checkpoint took
+    /// longer than this.
+    pub max_reconstruction_time: Option<Duration>,
+}
+
+impl AdaptiveCheckpoints {
+    pub fn new(max_diff_fraction: f64) -> Self {
+        if max_diff_fraction <= 0.0 {
+            panic!("{}", ERR_NON_POSITIVE_FRACTION);
+        }
+        Self {
+            max_diff_fraction,
+            max_reconstruction_time: None,
+        }
+    }
+
+    pub fn max_reconstruction_time(mut self, max_time: Duration) -> Self {
+        self.max_reconstruction_time = Some(max_time);
+        self
+    }
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> From<usize> for HistoryConfig<U, D> {
+    fn from(f_check: usize) -> Self {
+        Self::new(f_check)
+    }
+}
+
+/// EvictionPolicy
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EvictionPolicy {
+    /// Removes checkpoints between the shortest pairs of adjacent segments. No generation is lost,
+    /// but reconstructing old generations requires stacking more differences.
+    ThinCheckpoints,
+    /// Stacks pairs of consecutive differences in the oldest segment. Generations in the middle
+    /// of a merged pair become unavailable.
+    MergeDiffs,
+    /// Moves the oldest segment to a temporary directory, from which it is reloaded when needed.
+    SpillToDisk,
+}
+
+/// MemoryBudget
+
+pub struct MemoryBudget<U: Universe, D: GenerationDifference<Universe = U>> {
+    max_bytes: usize,
+    policies: Vec<EvictionPolicy>,
+    codec: Codec<U, D>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Clone for MemoryBudget<U, D> {
+    fn clone(&self) -> Self {
+        Self {
+            max_bytes: self.max_bytes,
+            policies: self.policies.clone(),
+            codec: self.codec.clone(),
+        }
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable> MemoryBudget<U, D> {
+    /// Creates a budget of `max_bytes` for checkpoints and differences, enforced with `policy`
+    /// whenever it is exceeded. The most recent segment of history is never evicted.
+    pub fn new(max_bytes: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            max_bytes,
+            policies: vec![policy],
+            codec: Codec::new(),
+        }
+    }
+
+    /// Adds a policy that is used when the previous ones can't evict anything anymore.
+    pub fn then(mut self, policy: EvictionPolicy) -> Self {
+        self.policies.push(policy);
+        self
+    }
+}
+
+/// UniverseHistory
+
 pub struct UniverseHistory<U: Universe, D: GenerationDifference<Universe = U>> {
-    diffs: Vec<D>,
-    checkpoints: Vec<U>,
+    /// Segments may be shared with forked histories, except for the last one which is the only one
+    /// that changes as generations are pushed.
+    segments: Vec<Arc<Segment<U, D>>>,
     f_check: usize,
     last: U,
+    max_gen: usize,
+    budget: Option<MemoryBudget<U, D>>,
+    memory_usage: usize,
+    spill_dir: Option<PathBuf>,
+    writer: Option<HistoryWriter<U, D>>,
+    adaptive: Option<(AdaptiveCheckpoints, Codec<U, D>)>,
+    /// Footprint of the last checkpoint and of the differences that follow it, when checkpoints
+    /// are adaptive.
+    segment_footprint: (usize, usize),
+    /// Longest time taken to reconstruct a generation from the last checkpoint.
+    reconstruction_time: Cell<Duration>,
+    cache: RefCell<GenerationCache<U>>,
 }
 
 impl<U: Universe, D: GenerationDifference<Universe = U>> UniverseHistory<U, D> {
-    pub fn new(start_universe: U, f_check: usize) -> Self {
+    pub fn new(start_universe: U, config: impl Into<HistoryConfig<U, D>>) -> Self {
+        let config = config.into();
+        let memory_usage = match &config.budget {
+            Some(budget) => (budget.codec.universe_footprint)(&start_universe),
+            None => 0,
+        };
+        let writer = config.persist.map(|(path, codec)| {
+            HistoryWriter::create(&path, &start_universe, codec)
+                .unwrap_or_else(|_| panic!("{}", ERR_PERSIST_WRITE))
+        });
+        let segment_footprint = match &config.adaptive {
+            Some((_, codec)) => ((codec.universe_footprint)(&start_universe), 0),
+            None => (0, 0),
+        };
         Self {
-            diffs: vec![],
-            checkpoints: vec![start_universe.clone()],
-            f_check,
+            segments: vec![Arc::new(Segment::new(0, start_universe.clone()))],
+            f_check: config.f_check,
             last: start_universe,
+            max_gen: 0,
+            budget: config.budget,
+            memory_usage,
+            spill_dir: None,
+            writer,
+            adaptive: config.adaptive,
+            segment_footprint,
+            reconstruction_time: Cell::new(Duration::from_secs(0)),
+            cache: RefCell::new(GenerationCache::new(config.cache_size)),
         }
     }
 
     pub fn push(&mut self, universe: U) {
         let diff = D::get_diff(&self.last, &universe);
-        self.diffs.push(diff);
-        if self.f_check != 0 && self.diffs.len() % self.f_check == 0 {
-            self.checkpoints.push(universe.clone());
+        self.push_with_diff(universe, diff);
+    }
+
+    /// Pushes the next generation as its difference to the highest one.
+    pub fn push_diff(&mut self, diff: D) {
+        let universe = diff.apply_to(self.last.clone());
+        self.push_with_diff(universe, diff);
+    }
+
+    /// Pushes the next generation along with its difference to the highest one, when evolving
+    /// produced both.
+    pub fn push_with_diff(&mut self, universe: U, diff: D) {
+        let gen = self.max_gen + 1;
+        if let Some(writer) = &mut self.writer {
+            writer
+                .write_diff(gen - 1, gen, &diff)
+                .unwrap_or_else(|_| panic!("{}", ERR_PERSIST_WRITE));
+        }
+        if let Some(budget) = &self.budget {
+            self.memory_usage += (budget.codec.diff_footprint)(&diff);
+        }
+        if let Some((_, codec)) = &self.adaptive {
+            self.segment_footprint.1 += (codec.diff_footprint)(&diff);
+        }
+        let last_segment = Arc::get_mut(self.segments.last_mut().unwrap())
+            .unwrap_or_else(|| panic!("{}", ERR_LAST_SEGMENT_SHARED));
+        match &mut last_segment.data {
+            SegmentData::InMemory { steps, .. } => steps.push(Step {
+                from: gen - 1,
+                to: gen,
+                diff,
+            }),
+            SegmentData::OnDisk(_) => panic!("{}", ERR_LAST_SEGMENT_SPILLED),
+        }
+
+        if self.checkpoint_due(gen) {
+            if let Some(writer) = &mut self.writer {
+                writer
+                    .write_checkpoint(gen, &universe)
+                    .unwrap_or_else(|_| panic!("{}", ERR_PERSIST_WRITE));
+            }
+            if let Some(budget) = &self.budget {
+                self.memory_usage += (budget.codec.universe_footprint)(&universe);
+            }
+            if let Some((_, codec)) = &self.adaptive {
+                self.segment_footprint = ((codec.universe_footprint)(&universe), 0);
+            }
+            self.reconstruction_time.set(Duration::from_secs(0));
+            self.segments
+                .push(Arc::new(Segment::new(gen, universe.clone())));
+        }
+        self.last = universe;
+        self.max_gen = gen;
+        self.enforce_budget();
+    }
+
+    /// Replaces the highest generation with an edited version of it. The edit is recorded as an
+    /// intervention step, distinct from the difference that the evolution produced.
+    pub fn intervene(&mut self, universe: U) {
+        let diff = D::get_diff(&self.last, &universe);
+        let gen = self.max_gen;
+        if let Some(writer) = &mut self.writer {
+            writer
+                .write_intervention(gen, &diff)
+                .unwrap_or_else(|_| panic!("{}", ERR_PERSIST_WRITE));
+        }
+        if let Some(budget) = &self.budget {
+            self.memory_usage += (budget.codec.diff_footprint)(&diff);
+        }
+        if let Some((_, codec)) = &self.adaptive {
+            self.segment_footprint.1 += (codec.diff_footprint)(&diff);
+        }
+        let last_segment = Arc::get_mut(self.segments.last_mut().unwrap())
+            .unwrap_or_else(|| panic!("{}", ERR_LAST_SEGMENT_SHARED));
+        match &mut last_segment.data {
+            SegmentData::InMemory { checkpoint, steps } => {
+                // Reconstructions start from the edited generation when it is a checkpoint
+                if last_segment.start == gen {
+                    if let Some(budget) = &self.budget {
+                        self.memory_usage -= (budget.codec.universe_footprint)(checkpoint);
+                        self.memory_usage += (budget.codec.universe_footprint)(&universe);
+                    }
+                    *checkpoint = universe.clone();
+                }
+                steps.push(Step {
+                    from: gen,
+                    to: gen,
+                    diff,
+                });
+            }
+            SegmentData::OnDisk(_) => panic!("{}", ERR_LAST_SEGMENT_SPILLED),
         }
         self.last = universe;
+        self.enforce_budget();
     }
 
     pub fn get_gen(&self, gen: usize) -> Option<U> {
-        if self.diffs.len() < gen {
+        if self.max_gen < gen {
             // We don't have that generation
-            None
-        } else {
-            // We have the generation
-            if self.f_check != 0 {
-                let idx = gen / self.f_check;
-                let shift = gen % self.f_check;
+            return None;
+        } else if gen == self.max_gen {
+            return Some(self.last.clone());
+        }
+        if let Some(universe) = self.cache.borrow_mut().get(gen) {
+            return Some(universe);
+        }
+
+        let start = Instant::now();
+        let idx = self.find_segment(gen);
+        let segment_start = self.segments[idx].start;
+        let segment_end = match self.segments.get(idx + 1) {
+            Some(next) => next.start,
+            None => self.max_gen,
+        };
+        let before = self.cache.borrow_mut().closest_before(gen, segment_start);
+        let after = self.cache.borrow_mut().closest_after(gen, segment_end);
+        let universe = self.with_segment(idx, |checkpoint, steps| {
+            // Undo differences from a later generation when fewer of them separate it from the
+            // requested one (e.g., when scrubbing backwards from the highest generation)
+            let before_gen = before
+                .as_ref()
+                .map_or(segment_start, |(base_gen, _)| *base_gen);
+            let after_gen = after
+                .as_ref()
+                .map_or(segment_end, |(base_gen, _)| *base_gen);
+            if count_steps(steps, gen, after_gen) < count_steps(steps, before_gen, gen) {
+                let universe = match after {
+                    Some((base_gen, base)) => reconstruct_backwards(base_gen, base, steps, gen),
+                    None => self
+                        .segment_end_universe(idx)
+                        .and_then(|base| reconstruct_backwards(segment_end, base, steps, gen)),
+                };
+                if universe.is_some() {
+                    return universe;
+                }
+            }
 
-                // Accumulate differences between reference grid and target generation
-                let stacked_diffs = D::stack_mul(&self.diffs[(gen - shift)..gen]);
-                Some(stacked_diffs.apply_to(self.checkpoints[idx as usize].clone()))
-            } else {
-                // Accumulate differences between initial grid and target generation
-                let stacked_diffs = D::stack_mul(&self.diffs[0..gen]);
-                Some(stacked_diffs.apply_to(self.checkpoints[0].clone()))
+            // Prefer applying a few differences to a cached generation (e.g., the previous one
+            // when generations are accessed sequentially) over starting from the checkpoint
+            before
+                .and_then(|(base_gen, base)| reconstruct(base_gen, base, steps, gen))
+                .or_else(|| reconstruct(segment_start, checkpoint.clone(), steps, gen))
+        });
+
+        // Keep track of reconstruction costs for adaptive checkpoints
+        if idx == self.segments.len() - 1 {
+            let elapsed = start.elapsed();
+            if self.reconstruction_time.get() < elapsed {
+                self.reconstruction_time.set(elapsed);
             }
         }
+        if let Some(universe) = &universe {
+            self.cache.borrow_mut().insert(gen, universe.clone());
+        }
+        universe
     }
 
     pub fn get_diff(&self, ref_gen: usize, target_gen: usize) -> Option<D> {
         if target_gen < ref_gen {
             panic!("{}", ERR_INCORRECT_DIFF);
         }
-        if self.diffs.len() < target_gen {
-            None
-        } else {
-            Some(D::stack_mul(&self.diffs[ref_gen..target_gen]))
+        if self.max_gen < target_gen {
+            return None;
+        }
+
+        // Accumulate differences from the reference generation, across segments if needed, up to
+        // the interventions made at the target generation
+        let mut acc_diff: Option<D> = None;
+        let mut cursor = ref_gen;
+        for idx in self.find_segment(ref_gen)..self.segments.len() {
+            if target_gen < self.segments[idx].start {
+                break;
+            }
+            let mut done = false;
+            let available = self.with_segment(idx, |_, steps| {
+                for step in steps.iter().skip_while(|step| step.to <= ref_gen) {
+                    if target_gen < step.to {
+                        done = true;
+                        break;
+                    }
+                    if step.from != cursor {
+                        return false;
+                    }
+                    stack_into(&mut acc_diff, &step.diff);
+                    cursor = step.to;
+                }
+                true
+            });
+            if !available {
+                return None;
+            }
+            if done {
+                break;
+            }
+        }
+        if cursor != target_gen {
+            // One of the generations was lost when merging differences
+            return None;
+        }
+        Some(acc_diff.unwrap_or_else(D::empty_diff))
+    }
+
+    /// Returns the estimated number of bytes used by checkpoints and differences kept in memory.
+    /// Memory usage is only tracked when the history has a budget.
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    #[inline]
+    pub fn highest_generation(&self) -> usize {
+        self.max_gen
+    }
+
+    /// Creates a history that shares this one's generations before `gen`, and continues from
+    /// `universe` instead of the generation it replaces. Segments before the fork are shared
+    /// rather than copied, except for the one leading to `gen`. Forked histories are never
+    /// persisted to a file. Returns `None` if the generation isn't available.
+    pub fn fork(&self, gen: usize, universe: U) -> Option<Self> {
+        if self.max_gen < gen {
+            return None;
+        }
+
+        let mut segments = vec![];
+        if 0 < gen {
+            // The segment leading to the fork needs its own difference to the new universe
+            let idx = self.find_segment(gen - 1);
+            let start = self.segments[idx].start;
+            let mut steps: Vec<_> = self.with_segment(idx, |_, steps| {
+                steps.iter().filter(|step| step.to < gen).cloned().collect()
+            });
+            let prev_gen = steps.last().map_or(start, |step: &Step<D>| step.to);
+            let prev = self.get_gen(prev_gen)?;
+            steps.push(Step {
+                from: prev_gen,
+                to: gen,
+                diff: D::get_diff(&prev, &universe),
+            });
+            let checkpoint = self.with_segment(idx, |checkpoint, _| checkpoint.clone());
+            segments.extend(self.segments[..idx].iter().cloned());
+            segments.push(Arc::new(Segment {
+                start,
+                data: SegmentData::InMemory { checkpoint, steps },
+            }));
+        }
+        segments.push(Arc::new(Segment::new(gen, universe.clone())));
+
+        // Shared segments count towards the memory usage of every history they belong to
+        let memory_usage = match &self.budget {
+            Some(budget) => segments
+                .iter()
+                .map(|segment| segment.footprint(&budget.codec))
+                .sum(),
+            None => 0,
+        };
+        let segment_footprint = match &self.adaptive {
+            Some((_, codec)) => ((codec.universe_footprint)(&universe), 0),
+            None => (0, 0),
+        };
+        let mut forked = Self {
+            segments,
+            f_check: self.f_check,
+            last: universe,
+            max_gen: gen,
+            budget: self.budget.clone(),
+            memory_usage,
+            spill_dir: None,
+            writer: None,
+            adaptive: self.adaptive.clone(),
+            segment_footprint,
+            reconstruction_time: Cell::new(Duration::from_secs(0)),
+            cache: RefCell::new(GenerationCache::new(self.cache.borrow().capacity)),
+        };
+        forked.enforce_budget();
+        Some(forked)
+    }
+
+    /// Copies the history's generations and configuration, reloading spilled segments. Segments
+    /// shared with the history of the parent branch are left out of the snapshot, which only
+    /// records how many there are.
+    pub fn snapshot(&self, parent: Option<&Self>) -> HistorySnapshot<U, D> {
+        let n_shared = match parent {
+            Some(parent) => self
+                .segments
+                .iter()
+                .zip(parent.segments.iter())
+                .take_while(|(segment, shared)| Arc::ptr_eq(segment, shared))
+                .count(),
+            None => 0,
+        };
+        let segments = (n_shared..self.segments.len())
+            .map(|idx| {
+                let start = self.segments[idx].start;
+                self.with_segment(idx, |checkpoint, steps| {
+                    (start, checkpoint.clone(), steps.to_vec())
+                })
+            })
+            .collect();
+        HistorySnapshot {
+            f_check: self.f_check,
+            cache_size: self.cache.borrow().capacity,
+            budget: self
+                .budget
+                .as_ref()
+                .map(|budget| (budget.max_bytes, budget.policies.clone())),
+            adaptive: self.adaptive.as_ref().map(|(adaptive, _)| *adaptive),
+            max_gen: self.max_gen,
+            n_shared,
+            segments,
         }
     }
 
-    pub fn detach(mut self, endpoint: SlaveEndpoint<HistoryResponse<U, D>, HistoryRequest<U>>) {
-        thread::spawn(move || loop {
+    /// Starts a thread that creates a history and serves requests for it and for its branches.
+    pub fn spawn(
+        start_universe: U,
+        config: impl Into<HistoryConfig<U, D>>,
+        endpoint: SlaveEndpoint<HistoryResponse<U, D>, HistoryRequest<U, D>>,
+    ) {
+        // Histories are built in their thread since shared segments can't be sent between threads
+        let config = config.into();
+        thread::spawn(move || {
+            let history = Self::new(start_universe, config);
+            history.serve(Branches::new(), endpoint)
+        });
+    }
+
+    fn serve(
+        mut self,
+        mut branches: Branches<U, D>,
+        endpoint: SlaveEndpoint<HistoryResponse<U, D>, HistoryRequest<U, D>>,
+    ) {
+        loop {
             match endpoint.wait_for_mail() {
-                MailType::Message(msg, None) => match msg {
-                    HistoryRequest::Push(grid) => self.push(grid),
-                    _ => panic!("{}", ERR_INCOMPATIBLE_MAIL_TYPE),
-                },
+                MailType::Message(msg, None) => {
+                    self.serve_message(msg);
+                }
                 MailType::Message(msg, Some(req)) => match msg {
                     HistoryRequest::GetGen(gen, blocking) => match self.get_gen(gen) {
                         Some(grid) => {
                             req.respond(HistoryResponse::GetGen(Some(grid)));
                         }
                         None => {
-                            if blocking {
+                            if blocking && self.max_gen < gen {
                                 loop {
-                                    match endpoint.wait_for_msg() {
-                                        HistoryRequest::Push(grid) => {
-                                            self.push(grid);
-                                            if let Some(response_grid) = self.get_gen(gen) {
-                                                req.respond(HistoryResponse::GetGen(Some(
-                                                    response_grid,
-                                                )));
-                                                break;
-                                            }
+                                    if self.serve_message(endpoint.wait_for_msg()) {
+                                        if let Some(response_grid) = self.get_gen(gen) {
+                                            req.respond(HistoryResponse::GetGen(Some(
+                                                response_grid,
+                                            )));
+                                            break;
                                         }
-                                        _ => panic!("{}", ERR_INCOMPATIBLE_MAIL_TYPE),
                                     }
                                 }
                             } else {
@@ -104,48 +578,1176 @@ impl<U: Universe, D: GenerationDifference<Universe = U>> UniverseHistory<U, D> {
                                 req.respond(HistoryResponse::GetDiff(Some(diff)));
                             }
                             None => {
-                                if blocking {
+                                if blocking && self.max_gen < target_gen {
                                     loop {
-                                        match endpoint.wait_for_msg() {
-                                            HistoryRequest::Push(grid) => {
-                                                self.push(grid);
-                                                if let Some(response_diff) =
-                                                    self.get_diff(ref_gen, target_gen)
-                                                {
-                                                    req.respond(HistoryResponse::GetDiff(Some(
-                                                        response_diff,
-                                                    )));
-                                                    break;
-                                                }
+                                        if self.serve_message(endpoint.wait_for_msg()) {
+                                            if let Some(response_diff) =
+                                                self.get_diff(ref_gen, target_gen)
+                                            {
+                                                req.respond(HistoryResponse::GetDiff(Some(
+                                                    response_diff,
+                                                )));
+                                                break;
                                             }
-                                            _ => panic!("{}", ERR_INCOMPATIBLE_MAIL_TYPE),
                                         }
                                     }
                                 } else {
-                                    req.respond(HistoryResponse::GetGen(None));
+                                    req.respond(HistoryResponse::GetDiff(None));
                                 }
                             }
                         }
                     }
+                    HistoryRequest::Fork(gen, universe) => {
+                        let id = branches.fork(&mut self, gen, universe);
+                        req.respond(HistoryResponse::Forked(id));
+                    }
+                    HistoryRequest::Switch(id) => {
+                        let switched = if branches.switch(&mut self, id) {
+                            Some((self.max_gen, self.last.clone()))
+                        } else {
+                            None
+                        };
+                        req.respond(HistoryResponse::Switched(switched));
+                    }
+                    HistoryRequest::Branches => {
+                        req.respond(HistoryResponse::Branches(
+                            branches.list(&self),
+                            branches.current(),
+                        ));
+                    }
+                    HistoryRequest::Snapshot => {
+                        req.respond(HistoryResponse::Snapshot(branches.snapshot(&self)));
+                    }
                     _ => panic!("{}", ERR_INCOMPATIBLE_MAIL_TYPE),
                 },
                 MailType::DeadChannel => break,
             }
+        }
+    }
+
+    /// Handles a message that doesn't expect a response. Returns whether a generation was pushed.
+    fn serve_message(&mut self, msg: HistoryRequest<U, D>) -> bool {
+        match msg {
+            HistoryRequest::Push(grid) => self.push(grid),
+            HistoryRequest::PushDiff(diff) => self.push_diff(diff),
+            HistoryRequest::PushWithDiff(grid, diff) => self.push_with_diff(grid, diff),
+            HistoryRequest::Queued(request, slot) => {
+                let pushed = self.serve_message(*request);
+                drop(slot);
+                return pushed;
+            }
+            HistoryRequest::Intervene(grid) => {
+                self.intervene(grid);
+                return false;
+            }
+            HistoryRequest::Fetch(gen, respond) => {
+                respond(self.get_gen(gen));
+                return false;
+            }
+            _ => panic!("{}", ERR_INCOMPATIBLE_MAIL_TYPE),
+        }
+        true
+    }
+
+    /// Returns whether a checkpoint should be placed at a newly pushed generation.
+    fn checkpoint_due(&self, gen: usize) -> bool {
+        let interval = gen - self.segments.last().unwrap().start;
+        if self.f_check != 0 && self.f_check <= interval {
+            return true;
+        }
+        match &self.adaptive {
+            Some((adaptive, _)) => {
+                let (checkpoint_footprint, diffs_footprint) = self.segment_footprint;
+                let too_large = adaptive.max_diff_fraction * (checkpoint_footprint as f64)
+                    < diffs_footprint as f64;
+                let too_slow = match adaptive.max_reconstruction_time {
+                    Some(max_time) => max_time < self.reconstruction_time.get(),
+                    None => false,
+                };
+                too_large || too_slow
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the index of the segment containing a generation.
+    fn find_segment(&self, gen: usize) -> usize {
+        match self
+            .segments
+            .binary_search_by_key(&gen, |segment| segment.start)
+        {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        }
+    }
+
+    /// Calls a function on a segment's checkpoint and differences, reloading them from disk if
+    /// the segment was spilled.
+    fn with_segment<R>(&self, idx: usize, f: impl FnOnce(&U, &[Step<D>]) -> R) -> R {
+        match &self.segments[idx].data {
+            SegmentData::InMemory { checkpoint, steps } => f(checkpoint, steps),
+            SegmentData::OnDisk(file) => {
+                let budget = self.budget.as_ref().unwrap();
+                let (checkpoint, steps) =
+                    read_segment(&file.0, budget).unwrap_or_else(|_| panic!("{}", ERR_SPILL_READ));
+                f(&checkpoint, &steps)
+            }
+        }
+    }
+
+    /// Returns the universe that a segment's differences lead to, which is the next segment's
+    /// checkpoint without the interventions made on it (or the highest generation for the last
+    /// segment). Returns `None` if the next segment was spilled to disk.
+    fn segment_end_universe(&self, idx: usize) -> Option<U> {
+        if idx == self.segments.len() - 1 {
+            return Some(self.last.clone());
+        } else if !self.segments[idx + 1].in_memory() {
+            return None;
+        }
+        Some(self.with_segment(idx + 1, |checkpoint, steps| {
+            let interventions: Vec<_> = steps
+                .iter()
+                .take_while(|step| step.is_intervention())
+                .collect();
+            interventions
+                .iter()
+                .rev()
+                .fold(checkpoint.clone(), |universe, step| {
+                    step.diff.unapply_from(universe)
+                })
+        }))
+    }
+
+    fn enforce_budget(&mut self) {
+        let (max_bytes, n_policies) = match &self.budget {
+            Some(budget) => (budget.max_bytes, budget.policies.len()),
+            None => return,
+        };
+        while max_bytes < self.memory_usage() {
+            let evicted = (0..n_policies).any(|i| {
+                let policy = self.budget.as_ref().unwrap().policies[i];
+                match policy {
+                    EvictionPolicy::ThinCheckpoints => self.thin_checkpoints(),
+                    EvictionPolicy::MergeDiffs => self.merge_diffs(),
+                    EvictionPolicy::SpillToDisk => self.spill_to_disk(),
+                }
+            });
+            if !evicted {
+                break;
+            }
+        }
+    }
+
+    fn thin_checkpoints(&mut self) -> bool {
+        // Find the shortest pair of adjacent in-memory segments, the last one excluded
+        let n_segments = self.segments.len();
+        let candidate = (0..n_segments.saturating_sub(2))
+            .filter(|&i| self.is_evictable(i) && self.is_evictable(i + 1))
+            .min_by_key(|&i| self.segments[i + 2].start - self.segments[i].start);
+        let idx = match candidate {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        // Merge the second segment into the first one
+        let removed = Arc::try_unwrap(self.segments.remove(idx + 1));
+        if let Ok(Segment {
+            data:
+                SegmentData::InMemory {
+                    checkpoint,
+                    steps: removed_steps,
+                },
+            ..
+        }) = removed
+        {
+            let budget = self.budget.as_ref().unwrap();
+            self.memory_usage -= (budget.codec.universe_footprint)(&checkpoint);
+            if let Some(segment) = Arc::get_mut(&mut self.segments[idx]) {
+                if let SegmentData::InMemory { steps, .. } = &mut segment.data {
+                    steps.extend(removed_steps);
+                }
+            }
+        }
+        true
+    }
+
+    fn merge_diffs(&mut self) -> bool {
+        // Find the oldest in-memory segment with differences left to merge, the last one excluded
+        let n_segments = self.segments.len();
+        let budget = self.budget.as_ref().unwrap();
+        for segment in self.segments[..(n_segments - 1)].iter_mut() {
+            let segment = match Arc::get_mut(segment) {
+                Some(segment) => segment,
+                None => continue,
+            };
+            if let SegmentData::InMemory { steps, .. } = &mut segment.data {
+                // Interventions are never merged with the step that follows them, otherwise the
+                // generation they edited would become unavailable
+                if !steps.windows(2).any(|pair| !pair[0].is_intervention()) {
+                    continue;
+                }
+                let mut merged = Vec::with_capacity(steps.len() / 2 + 1);
+                let mut steps_iter = steps.drain(..);
+                while let Some(mut step) = steps_iter.next() {
+                    if step.is_intervention() {
+                        merged.push(step);
+                        continue;
+                    }
+                    self.memory_usage -= (budget.codec.diff_footprint)(&step.diff);
+                    if let Some(next) = steps_iter.next() {
+                        self.memory_usage -= (budget.codec.diff_footprint)(&next.diff);
+                        step.diff.stack(&next.diff);
+                        step.to = next.to;
+                    }
+                    self.memory_usage += (budget.codec.diff_footprint)(&step.diff);
+                    merged.push(step);
+                }
+                drop(steps_iter);
+                *steps = merged;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn spill_to_disk(&mut self) -> bool {
+        // Find the oldest in-memory segment, the last one excluded
+        let n_segments = self.segments.len();
+        let idx = match (0..(n_segments - 1)).find(|&i| self.is_evictable(i)) {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        let dir = match &self.spill_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = std::env::temp_dir().join(format!(
+                    "cell_history_{}_{}",
+                    process::id(),
+                    SPILL_DIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+                ));
+                fs::create_dir_all(&dir).unwrap_or_else(|_| panic!("{}", ERR_SPILL_WRITE));
+                self.spill_dir = Some(dir.clone());
+                dir
+            }
+        };
+        let segment = Arc::get_mut(&mut self.segments[idx]).unwrap();
+        let path = dir.join(format!("segment_{}.bin", segment.start));
+        let budget = self.budget.as_ref().unwrap();
+        let data = std::mem::replace(&mut segment.data, SegmentData::OnDisk(SpillFile(path)));
+        if let (SegmentData::InMemory { checkpoint, steps }, SegmentData::OnDisk(file)) =
+            (&data, &segment.data)
+        {
+            write_segment(&file.0, checkpoint, steps, budget)
+                .unwrap_or_else(|_| panic!("{}", ERR_SPILL_WRITE));
+            self.memory_usage -= (budget.codec.universe_footprint)(checkpoint);
+            for step in steps.iter() {
+                self.memory_usage -= (budget.codec.diff_footprint)(&step.diff);
+            }
+        }
+        true
+    }
+
+    /// Returns whether a segment is in memory and only belongs to this history, in which case
+    /// eviction policies may modify it.
+    fn is_evictable(&self, idx: usize) -> bool {
+        self.segments[idx].in_memory() && Arc::strong_count(&self.segments[idx]) == 1
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable>
+    UniverseHistory<U, D>
+{
+    /// Recreates the histories of a simulator's branches from their snapshots, ordered by
+    /// identifier. The histories aren't persisted to a file anymore, and their segments are back
+    /// in memory until the budget (if any) evicts them again. Branches still share the segments
+    /// before their fork with their parent.
+    pub fn restore_branches(
+        snapshots: Vec<(BranchInfo, HistorySnapshot<U, D>)>,
+    ) -> Vec<(BranchInfo, Self)> {
+        let mut histories: Vec<(BranchInfo, Self)> = Vec::with_capacity(snapshots.len());
+        for (info, snapshot) in snapshots {
+            let parent = info.parent.map(|id| &histories[id.0].1);
+            let history = Self::restore(snapshot, parent);
+            histories.push((info, history));
+        }
+
+        // Evicting earlier would move the segments that the next histories share
+        for (_, history) in histories.iter_mut() {
+            history.enforce_budget();
+        }
+        histories
+    }
+
+    /// Recreates a history from its snapshot, along with the parent branch's history whose
+    /// segments the snapshot shares. The budget isn't enforced yet.
+    fn restore(snapshot: HistorySnapshot<U, D>, parent: Option<&Self>) -> Self {
+        let last = snapshot.last_universe();
+        let codec = Codec::new();
+        let budget = snapshot.budget.map(|(max_bytes, policies)| MemoryBudget {
+            max_bytes,
+            policies,
+            codec: codec.clone(),
+        });
+        let adaptive = snapshot.adaptive.map(|adaptive| (adaptive, codec.clone()));
+        let shared = match parent {
+            Some(parent) => &parent.segments[..snapshot.n_shared],
+            None => &[],
+        };
+        let segments: Vec<_> = shared
+            .iter()
+            .cloned()
+            .chain(
+                snapshot
+                    .segments
+                    .into_iter()
+                    .map(|(start, checkpoint, steps)| {
+                        Arc::new(Segment {
+                            start,
+                            data: SegmentData::InMemory { checkpoint, steps },
+                        })
+                    }),
+            )
+            .collect();
+
+        let memory_usage = match &budget {
+            Some(budget) => segments
+                .iter()
+                .map(|segment| segment.footprint(&budget.codec))
+                .sum(),
+            None => 0,
+        };
+        let segment_footprint = match (&adaptive, &segments.last().unwrap().data) {
+            (Some((_, codec)), SegmentData::InMemory { checkpoint, steps }) => (
+                (codec.universe_footprint)(checkpoint),
+                steps
+                    .iter()
+                    .map(|step| (codec.diff_footprint)(&step.diff))
+                    .sum(),
+            ),
+            _ => (0, 0),
+        };
+
+        Self {
+            segments,
+            f_check: snapshot.f_check,
+            last,
+            max_gen: snapshot.max_gen,
+            budget,
+            memory_usage,
+            spill_dir: None,
+            writer: None,
+            adaptive,
+            segment_footprint,
+            reconstruction_time: Cell::new(Duration::from_secs(0)),
+            cache: RefCell::new(GenerationCache::new(snapshot.cache_size)),
+        }
+    }
+
+    /// Starts a thread that restores the histories of a simulator's branches and serves requests
+    /// for them.
+    pub fn spawn_restored(
+        snapshot: SimulatorSnapshot<U, D>,
+        endpoint: SlaveEndpoint<HistoryResponse<U, D>, HistoryRequest<U, D>>,
+    ) {
+        thread::spawn(move || {
+            let (branches, history) = Branches::restore(snapshot);
+            history.serve(branches, endpoint)
         });
     }
 }
 
-pub enum HistoryRequest<U: Universe> {
+impl<U: Universe, D: GenerationDifference<Universe = U>> Drop for UniverseHistory<U, D> {
+    fn drop(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            let _ = writer.finish();
+        }
+    }
+}
+
+/// Segment
+
+/// A checkpoint along with the differences leading to the next checkpoint.
+struct Segment<U: Universe, D: GenerationDifference<Universe = U>> {
+    start: usize,
+    data: SegmentData<U, D>,
+}
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> Segment<U, D> {
+    fn new(start: usize, checkpoint: U) -> Self {
+        Self {
+            start,
+            data: SegmentData::InMemory {
+                checkpoint,
+                steps: vec![],
+            },
+        }
+    }
+
+    fn in_memory(&self) -> bool {
+        match self.data {
+            SegmentData::InMemory { .. } => true,
+            SegmentData::OnDisk(_) => false,
+        }
+    }
+
+    /// Returns the number of bytes the segment uses in memory.
+    fn footprint(&self, codec: &Codec<U, D>) -> usize {
+        match &self.data {
+            SegmentData::InMemory { checkpoint, steps } => {
+                (codec.universe_footprint)(checkpoint)
+                    + steps
+                        .iter()
+                        .map(|step| (codec.diff_footprint)(&step.diff))
+                        .sum::<usize>()
+            }
+            SegmentData::OnDisk(_) => 0,
+        }
+    }
+}
+
+enum SegmentData<U: Universe, D: GenerationDifference<Universe = U>> {
+    InMemory { checkpoint: U, steps: Vec<Step<D>> },
+    OnDisk(SpillFile),
+}
+
+/// A spilled segment's file, deleted along with its directory (once empty) when no history uses
+/// the segment anymore.
+struct SpillFile(PathBuf);
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        if let Some(dir) = self.0.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+/// Step
+
+/// The difference between generations `from` and `to`, which are consecutive unless differences
+/// have been merged.
+/// Interventions (cell edits) are steps from a generation to itself.
+#[derive(Clone)]
+struct Step<D: GenerationDifference> {
+    from: usize,
+    to: usize,
+    diff: D,
+}
+
+impl<D: GenerationDifference> Step<D> {
+    fn is_intervention(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// HistorySnapshot
+
+/// A history's configuration and generations, all in memory.
+pub struct HistorySnapshot<U: Universe, D: GenerationDifference<Universe = U>> {
+    f_check: usize,
+    cache_size: usize,
+    /// Maximum number of bytes and eviction policies.
+    budget: Option<(usize, Vec<EvictionPolicy>)>,
+    adaptive: Option<AdaptiveCheckpoints>,
+    max_gen: usize,
+    /// Number of leading segments shared with the parent branch's history, which are only part of
+    /// the parent's snapshot.
+    n_shared: usize,
+    /// Start, checkpoint and steps of each of the other segments.
+    segments: Vec<SegmentSnapshot<U, D>>,
+}
+
+type SegmentSnapshot<U, D> = (usize, U, Vec<Step<D>>);
+
+impl<U: Universe, D: GenerationDifference<Universe = U>> HistorySnapshot<U, D> {
+    #[inline]
+    pub fn highest_generation(&self) -> usize {
+        self.max_gen
+    }
+
+    /// Returns the universe at the highest generation.
+    pub fn last_universe(&self) -> U {
+        let (start, checkpoint, steps) = self.segments.last().unwrap();
+        reconstruct(*start, checkpoint.clone(), steps, self.max_gen)
+            .unwrap_or_else(|| panic!("{}", ERR_INVALID_SNAPSHOT))
+    }
+
+    /// Returns every segment of the history, given the snapshots of the branch's ancestors from
+    /// its parent to the root. Returns `None` if the parent doesn't have the shared segments.
+    fn all_segments<'a>(
+        &'a self,
+        ancestors: &[&'a Self],
+    ) -> Option<Vec<&'a SegmentSnapshot<U, D>>> {
+        let mut segments = match ancestors.split_first() {
+            Some((parent, ancestors)) => parent.all_segments(ancestors)?,
+            None => vec![],
+        };
+        if segments.len() < self.n_shared {
+            return None;
+        }
+        segments.truncate(self.n_shared);
+        segments.extend(self.segments.iter());
+        Some(segments)
+    }
+}
+
+impl<U: Universe + Storable, D: GenerationDifference<Universe = U> + Storable>
+    HistorySnapshot<U, D>
+{
+    /// Returns the approximate number of bytes used in memory, shared segments excluded.
+    pub fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+            + self
+                .segments
+                .iter()
+                .map(|(_, checkpoint, steps)| {
+                    checkpoint.footprint()
+                        + steps
+                            .iter()
+                            .map(|step| step.diff.footprint() + 2 * mem::size_of::<usize>())
+                            .sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    /// Writes the snapshot, shared segments excluded.
+    pub fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.f_check as u64)?;
+        write_u64(writer, self.cache_size as u64)?;
+        match &self.budget {
+            Some((max_bytes, policies)) => {
+                writer.write_all(&[1])?;
+                write_u64(writer, *max_bytes as u64)?;
+                write_u64(writer, policies.len() as u64)?;
+                for policy in policies.iter() {
+                    let code = match policy {
+                        EvictionPolicy::ThinCheckpoints => 0,
+                        EvictionPolicy::MergeDiffs => 1,
+                        EvictionPolicy::SpillToDisk => 2,
+                    };
+                    writer.write_all(&[code])?;
+                }
+            }
+            None => writer.write_all(&[0])?,
+        }
+        match &self.adaptive {
+            Some(adaptive) => {
+                writer.write_all(&[1])?;
+                write_u64(writer, adaptive.max_diff_fraction.to_bits())?;
+                match adaptive.max_reconstruction_time {
+                    Some(max_time) => {
+                        writer.write_all(&[1])?;
+                        write_u64(writer, max_time.as_nanos() as u64)?;
+                    }
+                    None => writer.write_all(&[0])?,
+                }
+            }
+            None => writer.write_all(&[0])?,
+        }
+        write_u64(writer, self.max_gen as u64)?;
+        write_u64(writer, self.n_shared as u64)?;
+        write_u64(writer, self.segments.len() as u64)?;
+        for (start, checkpoint, steps) in self.segments.iter() {
+            write_u64(writer, *start as u64)?;
+            checkpoint.store(writer)?;
+            write_u64(writer, steps.len() as u64)?;
+            for step in steps.iter() {
+                write_u64(writer, step.from as u64)?;
+                write_u64(writer, step.to as u64)?;
+                step.diff.store(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads back what `store` wrote, given the snapshots of the branch's ancestors from its
+    /// parent to the root. Corrupted data is reported as an `InvalidData` error.
+    pub fn load(reader: &mut dyn Read, ancestors: &[&Self]) -> io::Result<Self> {
+        let f_check = read_u64(reader)? as usize;
+        let cache_size = read_u64(reader)? as usize;
+        let budget = match read_flag(reader)? {
+            true => {
+                let max_bytes = read_u64(reader)? as usize;
+                let n_policies = read_u64(reader)? as usize;
+                let mut policies = Vec::with_capacity(load_capacity(n_policies));
+                for _ in 0..n_policies {
+                    let mut code = [0];
+                    reader.read_exact(&mut code)?;
+                    policies.push(match code[0] {
+                        0 => EvictionPolicy::ThinCheckpoints,
+                        1 => EvictionPolicy::MergeDiffs,
+                        2 => EvictionPolicy::SpillToDisk,
+                        _ => return Err(invalid_data(ERR_INVALID_SNAPSHOT)),
+                    });
+                }
+                Some((max_bytes, policies))
+            }
+            false => None,
+        };
+        let adaptive = match read_flag(reader)? {
+            true => {
+                let max_diff_fraction = f64::from_bits(read_u64(reader)?);
+                let max_reconstruction_time = match read_flag(reader)? {
+                    true => Some(Duration::from_nanos(read_u64(reader)?)),
+                    false => None,
+                };
+                Some(AdaptiveCheckpoints {
+                    max_diff_fraction,
+                    max_reconstruction_time,
+                })
+            }
+            false => None,
+        };
+        let max_gen = read_u64(reader)? as usize;
+
+        let n_shared = read_u64(reader)? as usize;
+        let n_segments = read_u64(reader)? as usize;
+        let mut segments = Vec::with_capacity(load_capacity(n_segments));
+        for _ in 0..n_segments {
+            let start = read_u64(reader)? as usize;
+            let checkpoint = U::load(reader)?;
+            let n_steps = read_u64(reader)? as usize;
+            let mut steps = Vec::with_capacity(load_capacity(n_steps));
+            for _ in 0..n_steps {
+                let from = read_u64(reader)? as usize;
+                let to = read_u64(reader)? as usize;
+                let diff = D::load(reader)?;
+                if !diff.fits(&checkpoint) {
+                    return Err(invalid_data(ERR_INVALID_SNAPSHOT));
+                }
+                steps.push(Step { from, to, diff });
+            }
+            segments.push((start, checkpoint, steps));
+        }
+
+        // Generations are looked up by segment start, and reconstructed by following steps
+        let snapshot = Self {
+            f_check,
+            cache_size,
+            budget,
+            adaptive,
+            max_gen,
+            n_shared,
+            segments,
+        };
+        let contiguous = match snapshot.all_segments(ancestors) {
+            Some(segments) => !snapshot.segments.is_empty() && contiguous(&segments, max_gen),
+            None => false,
+        };
+        if !contiguous {
+            return Err(invalid_data(ERR_INVALID_SNAPSHOT));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Returns whether segments cover every generation up to `max_gen`: the first one starts at
+/// generation 0, each step continues from the previous one, and each segment starts where the
+/// previous one ended, after its own start.
+fn contiguous<U: Universe, D: GenerationDifference<Universe = U>>(
+    segments: &[&SegmentSnapshot<U, D>],
+    max_gen: usize,
+) -> bool {
+    let mut end = 0;
+    let mut prev_start = None;
+    for (start, _, steps) in segments.iter() {
+        if *start != end || prev_start.is_some_and(|prev_start| *start <= prev_start) {
+            return false;
+        }
+        for step in steps.iter() {
+            if step.from != end || step.to < step.from {
+                return false;
+            }
+            end = step.to;
+        }
+        prev_start = Some(*start);
+    }
+    end == max_gen
+}
+
+fn read_flag(reader: &mut dyn Read) -> io::Result<bool> {
+    let mut flag = [0];
+    reader.read_exact(&mut flag)?;
+    Ok(flag[0] != 0)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reconstructs a generation by applying the differences that follow a base generation. Returns
+/// `None` if one of the generations is in the middle of merged differences.
+fn reconstruct<U: Universe, D: GenerationDifference<Universe = U>>(
+    base_gen: usize,
+    base: U,
+    steps: &[Step<D>],
+    gen: usize,
+) -> Option<U> {
+    let mut acc_diff: Option<D> = None;
+    let mut cursor = base_gen;
+    for step in steps
+        .iter()
+        .skip_while(|step| step.to <= base_gen)
+        .take_while(|step| step.to <= gen)
+    {
+        if step.from != cursor {
+            return None;
+        }
+        stack_into(&mut acc_diff, &step.diff);
+        cursor = step.to;
+    }
+    if cursor != gen {
+        return None;
+    }
+    match acc_diff {
+        Some(diff) => Some(diff.apply_to(base)),
+        None => Some(base),
+    }
+}
+
+/// Reconstructs a generation by undoing the differences that lead from it to a later base
+/// generation, most recent first. Returns `None` if the generation was lost when merging
+/// differences.
+fn reconstruct_backwards<U: Universe, D: GenerationDifference<Universe = U>>(
+    base_gen: usize,
+    base: U,
+    steps: &[Step<D>],
+    gen: usize,
+) -> Option<U> {
+    let mut universe = base;
+    let mut cursor = base_gen;
+    for step in steps
+        .iter()
+        .rev()
+        .skip_while(|step| base_gen < step.to)
+        .take_while(|step| gen < step.to)
+    {
+        if step.to != cursor {
+            return None;
+        }
+        universe = step.diff.unapply_from(universe);
+        cursor = step.from;
+    }
+    if cursor != gen {
+        return None;
+    }
+    Some(universe)
+}
+
+/// Returns the number of differences between two generations.
+fn count_steps<D: GenerationDifference>(steps: &[Step<D>], from: usize, to: usize) -> usize {
+    steps
+        .iter()
+        .filter(|step| from < step.to && step.to <= to)
+        .count()
+}
+
+fn stack_into<D: GenerationDifference>(acc_diff: &mut Option<D>, diff: &D) {
+    match acc_diff {
+        Some(acc_diff) => acc_diff.stack(diff),
+        None => *acc_diff = Some(diff.clone()),
+    }
+}
+
+fn write_segment<U: Universe, D: GenerationDifference<Universe = U>>(
+    path: &PathBuf,
+    checkpoint: &U,
+    steps: &[Step<D>],
+    budget: &MemoryBudget<U, D>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    (budget.codec.store_universe)(checkpoint, &mut writer)?;
+    write_u64(&mut writer, steps.len() as u64)?;
+    for step in steps.iter() {
+        write_u64(&mut writer, step.from as u64)?;
+        write_u64(&mut writer, step.to as u64)?;
+        (budget.codec.store_diff)(&step.diff, &mut writer)?;
+    }
+    writer.flush()
+}
+
+fn read_segment<U: Universe, D: GenerationDifference<Universe = U>>(
+    path: &PathBuf,
+    budget: &MemoryBudget<U, D>,
+) -> io::Result<(U, Vec<Step<D>>)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let checkpoint = (budget.codec.load_universe)(&mut reader)?;
+    let n_steps = read_u64(&mut reader)? as usize;
+    let mut steps = Vec::with_capacity(load_capacity(n_steps));
+    for _ in 0..n_steps {
+        let from = read_u64(&mut reader)? as usize;
+        let to = read_u64(&mut reader)? as usize;
+        let diff = (budget.codec.load_diff)(&mut reader)?;
+        steps.push(Step { from, to, diff });
+    }
+    Ok((checkpoint, steps))
+}
+
+/// GenerationCache
+
+/// Least recently used reconstructed generations.
+struct GenerationCache<U: Universe> {
+    capacity: usize,
+    /// Most recently used entries first.
+    entries: VecDeque<(usize, U)>,
+}
+
+impl<U: Universe> GenerationCache<U> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, gen: usize) -> Option<U> {
+        let pos = self.entries.iter().position(|(g, _)| *g == gen)?;
+        self.touch(pos);
+        Some(self.entries[0].1.clone())
+    }
+
+    /// Returns the closest cached generation before `gen`, but not before `min_gen`.
+    fn closest_before(&mut self, gen: usize, min_gen: usize) -> Option<(usize, U)> {
+        let pos = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (g, _))| min_gen <= *g && *g < gen)
+            .max_by_key(|(_, (g, _))| *g)
+            .map(|(pos, _)| pos)?;
+        self.touch(pos);
+        Some(self.entries[0].clone())
+    }
+
+    /// Returns the closest cached generation after `gen`, but before `max_gen`.
+    fn closest_after(&mut self, gen: usize, max_gen: usize) -> Option<(usize, U)> {
+        let pos = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (g, _))| gen < *g && *g < max_gen)
+            .min_by_key(|(_, (g, _))| *g)
+            .map(|(pos, _)| pos)?;
+        self.touch(pos);
+        Some(self.entries[0].clone())
+    }
+
+    fn insert(&mut self, gen: usize, universe: U) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front((gen, universe));
+    }
+
+    fn touch(&mut self, pos: usize) {
+        let entry = self.entries.remove(pos).unwrap();
+        self.entries.push_front(entry);
+    }
+}
+
+pub enum HistoryRequest<U: Universe, D: GenerationDifference<Universe = U>> {
     Push(U),
+    /// Pushes the next generation as its difference to the highest one.
+    PushDiff(D),
+    /// Pushes the next generation along with its difference to the highest one.
+    PushWithDiff(U, D),
+    /// Replaces the highest generation with an edited version of it.
+    Intervene(U),
     GetDiff(usize, usize, bool),
     GetGen(usize, bool),
+    /// Gets a generation without the requester waiting for the answer, which is handed to the
+    /// callback instead.
+    Fetch(usize, Box<dyn FnOnce(Option<U>) + Send>),
+    /// Forks the active branch at a generation, replaced by the given universe.
+    Fork(usize, U),
+    Switch(BranchId),
+    Branches,
+    Snapshot,
+    /// A request from an asynchronous runner's queue, whose place is freed once it is handled.
+    Queued(Box<HistoryRequest<U, D>>, QueueSlot),
 }
 
 pub enum HistoryResponse<U: Universe, D: GenerationDifference<Universe = U>> {
     GetDiff(Option<D>),
     GetGen(Option<U>),
+    Forked(Option<BranchId>),
+    /// Highest generation of the branch switched to, and the universe at that generation.
+    Switched(Option<(usize, U)>),
+    Branches(Vec<BranchInfo>, BranchId),
+    Snapshot(SimulatorSnapshot<U, D>),
 }
 
+const DEFAULT_CACHE_SIZE: usize = 4;
+
+static SPILL_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 const ERR_INCORRECT_DIFF: &str = "Base generation should be smaller than target generation.";
 const ERR_INCOMPATIBLE_MAIL_TYPE: &str =
     "The received HistoryRequest is incompatible with the MailType it's included in.";
+const ERR_LAST_SEGMENT_SPILLED: &str = "The most recent history segment should never be spilled.";
+const ERR_LAST_SEGMENT_SHARED: &str =
+    "The most recent history segment should never be shared with another history.";
+const ERR_SPILL_WRITE: &str = "Failed to spill history segment to disk.";
+const ERR_SPILL_READ: &str = "Failed to reload history segment from disk.";
+const ERR_NON_POSITIVE_FRACTION: &str =
+    "The maximum difference fraction must be strictly positive.";
+const ERR_PERSIST_WRITE: &str = "Failed to write to the history file.";
+const ERR_INVALID_SNAPSHOT: &str = "The snapshot's history is corrupted.";
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{
+        AdaptiveCheckpoints, Branches, EvictionPolicy, GenerationCache, HistoryConfig,
+        HistorySnapshot, MemoryBudget, UniverseHistory,
+    };
+    use crate::{
+        automaton::game_of_life::{self, catalogue, GameOfLife},
+        simulator::SimulatorSnapshot,
+        universe::{
+            grid2d::{
+                static_grid2d::{GridDiff, StaticGrid2D},
+                Coordinates2D, Size2D,
+            },
+            CPUUniverse, GenerationDifference, Storable, Universe,
+        },
+    };
+
+    type Grid = StaticGrid2D<GameOfLife>;
+
+    fn run_history(
+        config: HistoryConfig<Grid, GridDiff<GameOfLife>>,
+        n_gens: usize,
+    ) -> (UniverseHistory<Grid, GridDiff<GameOfLife>>, Vec<Grid>) {
+        run_history_from(game_of_life::r_pentomino(), config, n_gens)
+    }
+
+    fn run_history_from(
+        mut universe: Grid,
+        config: HistoryConfig<Grid, GridDiff<GameOfLife>>,
+        n_gens: usize,
+    ) -> (UniverseHistory<Grid, GridDiff<GameOfLife>>, Vec<Grid>) {
+        let mut history = UniverseHistory::new(universe.clone(), config);
+        let mut gens = vec![universe.clone()];
+        for _ in 0..n_gens {
+            universe = universe.cpu_evolve_once();
+            history.push(universe.clone());
+            gens.push(universe.clone());
+        }
+        (history, gens)
+    }
+
+    fn same(g1: &Grid, g2: &Grid) -> bool {
+        g1.iter().flatten().eq(g2.iter().flatten())
+    }
+
+    #[test]
+    fn lossless_policies() {
+        for &policy in [EvictionPolicy::ThinCheckpoints, EvictionPolicy::SpillToDisk].iter() {
+            let max_bytes = 4 * game_of_life::r_pentomino().footprint();
+            let budget = MemoryBudget::new(max_bytes, policy);
+            let (history, gens) = run_history(HistoryConfig::new(4).memory_budget(budget), 60);
+            assert!(history.memory_usage() <= max_bytes);
+            for (gen, universe) in gens.iter().enumerate() {
+                assert!(same(&history.get_gen(gen).unwrap(), universe));
+            }
+            let diff = history.get_diff(3, 57).unwrap();
+            assert!(same(&diff.apply_to(gens[3].clone()), &gens[57]));
+        }
+    }
+
+    #[test]
+    fn merged_diffs() {
+        // Leave room for all checkpoints, but not for all differences
+        let max_bytes = 8 * game_of_life::r_pentomino().footprint() + 40_000;
+        let budget = MemoryBudget::new(max_bytes, EvictionPolicy::MergeDiffs);
+        let (history, gens) = run_history(HistoryConfig::new(8).memory_budget(budget), 60);
+        assert!(history.memory_usage() <= max_bytes);
+
+        // Checkpoints and the most recent segment are always available
+        for gen in (0..=60).filter(|gen| gen % 8 == 0 || *gen > 56) {
+            assert!(same(&history.get_gen(gen).unwrap(), &gens[gen]));
+        }
+        let missing = (0..=60)
+            .filter(|gen| history.get_gen(*gen).is_none())
+            .count();
+        assert!(missing > 0);
+        for gen in (0..=60).filter(|gen| history.get_gen(*gen).is_some()) {
+            assert!(same(&history.get_gen(gen).unwrap(), &gens[gen]));
+        }
+    }
+
+    #[test]
+    fn forks() {
+        let max_bytes = 4 * game_of_life::r_pentomino().footprint();
+        let budget = MemoryBudget::new(max_bytes, EvictionPolicy::SpillToDisk);
+        let (history, gens) = run_history(HistoryConfig::new(4).memory_budget(budget), 40);
+
+        // Replaying the same generations on a fork gives back the same history
+        let mut fork = history.fork(22, gens[22].clone()).unwrap();
+        for universe in gens[23..].iter() {
+            fork.push(universe.clone());
+        }
+        assert!(history.fork(41, gens[40].clone()).is_none());
+
+        // Spilled segments outlive the history that created them as long as they are shared
+        drop(history);
+        for (gen, universe) in gens.iter().enumerate() {
+            assert!(same(&fork.get_gen(gen).unwrap(), universe));
+        }
+        let diff = fork.get_diff(3, 37).unwrap();
+        assert!(same(&diff.apply_to(gens[3].clone()), &gens[37]));
+    }
+
+    #[test]
+    fn snapshots() {
+        let (mut history, gens) = run_history(HistoryConfig::new(4), 30);
+        let mut branches = Branches::new();
+        branches.fork(&mut history, 22, gens[22].clone()).unwrap();
+        for universe in gens[23..].iter() {
+            history.push(universe.clone());
+        }
+
+        // The segments before the fork are stored once, and shared again once restored
+        let mut bytes = vec![];
+        branches.snapshot(&history).store(&mut bytes).unwrap();
+        let snapshot: SimulatorSnapshot<Grid, GridDiff<GameOfLife>> =
+            SimulatorSnapshot::load(&mut &bytes[..]).unwrap();
+        assert_eq!(snapshot.branches[1].1.n_shared, 5);
+        let histories = UniverseHistory::restore_branches(snapshot.branches);
+        let (root, fork) = (&histories[0].1, &histories[1].1);
+        assert!((0..5).all(|idx| Arc::ptr_eq(&root.segments[idx], &fork.segments[idx])));
+        assert!(!Arc::ptr_eq(&root.segments[5], &fork.segments[5]));
+        for (gen, universe) in gens.iter().enumerate() {
+            assert!(same(&fork.get_gen(gen).unwrap(), universe));
+        }
+
+        // Segments must follow each other from generation 0
+        let load = |snapshot: &HistorySnapshot<_, _>, ancestors: &[&HistorySnapshot<_, _>]| {
+            let mut bytes = vec![];
+            snapshot.store(&mut bytes).unwrap();
+            HistorySnapshot::load(&mut &bytes[..], ancestors)
+        };
+        let root = histories[0].1.snapshot(None);
+        assert!(load(&root, &[]).is_ok());
+        let mut corrupted = histories[0].1.snapshot(None);
+        corrupted.segments.swap(1, 2);
+        assert!(load(&corrupted, &[]).is_err());
+        let mut corrupted = histories[0].1.snapshot(None);
+        corrupted.segments[0].0 = 1;
+        assert!(load(&corrupted, &[]).is_err());
+        let mut corrupted = histories[0].1.snapshot(None);
+        corrupted.segments[3].2.remove(1);
+        assert!(load(&corrupted, &[]).is_err());
+
+        // Branches can only share segments their parent has
+        let fork = histories[1].1.snapshot(Some(&histories[0].1));
+        assert!(load(&fork, &[&root]).is_ok());
+        assert!(load(&fork, &[]).is_err());
+        let mut corrupted = histories[1].1.snapshot(Some(&histories[0].1));
+        corrupted.n_shared = root.segments.len() + 1;
+        assert!(load(&corrupted, &[&root]).is_err());
+    }
+
+    #[test]
+    fn adaptive_checkpoints() {
+        let adaptive = AdaptiveCheckpoints::new(0.5);
+        let blinker = catalogue::find("Blinker")
+            .unwrap()
+            .to_static_grid(Size2D(201, 201), Coordinates2D(100, 100));
+
+        // A nearly static pattern needs a lot less checkpoints than a chaotic one
+        let config = HistoryConfig::new(0).adaptive_checkpoints(adaptive);
+        let (quiet, gens) = run_history_from(blinker, config, 60);
+        for (gen, universe) in gens.iter().enumerate() {
+            assert!(same(&quiet.get_gen(gen).unwrap(), universe));
+        }
+        let config = HistoryConfig::new(0).adaptive_checkpoints(adaptive);
+        let (chaotic, gens) = run_history(config, 60);
+        for (gen, universe) in gens.iter().enumerate() {
+            assert!(same(&chaotic.get_gen(gen).unwrap(), universe));
+        }
+        assert!(quiet.segments.len() < chaotic.segments.len());
+
+        // The fixed interval is still an upper bound
+        let config = HistoryConfig::new(10).adaptive_checkpoints(adaptive);
+        let (bounded, _) = run_history(config, 60);
+        assert!(bounded
+            .segments
+            .windows(2)
+            .all(|w| w[1].start - w[0].start <= 10));
+
+        // Slow reconstructions trigger a checkpoint at the next push
+        let adaptive =
+            AdaptiveCheckpoints::new(1000.0).max_reconstruction_time(Duration::from_nanos(1));
+        let config = HistoryConfig::new(0).adaptive_checkpoints(adaptive);
+        let (mut history, gens) = run_history(config, 10);
+        assert_eq!(history.segments.len(), 1);
+        history.get_gen(5).unwrap();
+        history.push(gens[10].clone().cpu_evolve_once());
+        assert_eq!(history.segments.len(), 2);
+        assert_eq!(history.segments[1].start, 11);
+    }
+
+    #[test]
+    fn backward_walks() {
+        // Edits at a checkpoint and in the middle of a segment must be undone as well
+        let mut universe = game_of_life::r_pentomino();
+        let mut history: UniverseHistory<Grid, GridDiff<GameOfLife>> =
+            UniverseHistory::new(universe.clone(), HistoryConfig::new(10).cache_size(0));
+        let mut gens = vec![universe.clone()];
+        for gen in 1..=45 {
+            universe = universe.cpu_evolve_once();
+            history.push(universe.clone());
+            if gen == 20 || gen == 27 {
+                universe.set(Coordinates2D(gen, 3), GameOfLife::Alive);
+                history.intervene(universe.clone());
+            }
+            gens.push(universe.clone());
+        }
+        for gen in (0..=45).rev() {
+            assert!(same(&history.get_gen(gen).unwrap(), &gens[gen]));
+        }
+        assert!(same(
+            &history.segment_end_universe(1).unwrap(),
+            &gens[19].clone().cpu_evolve_once()
+        ));
+    }
+
+    #[test]
+    fn cached_lookups() {
+        let universe = game_of_life::r_pentomino();
+        let mut cache = GenerationCache::new(2);
+        cache.insert(3, universe.clone());
+        cache.insert(7, universe.clone());
+        assert!(cache.get(3).is_some());
+        cache.insert(9, universe.clone());
+        assert!(cache.get(7).is_none());
+        assert_eq!(cache.closest_before(12, 0).map(|(gen, _)| gen), Some(9));
+        assert_eq!(cache.closest_before(9, 4).map(|(gen, _)| gen), None);
+        assert_eq!(cache.closest_after(2, 12).map(|(gen, _)| gen), Some(3));
+        assert_eq!(cache.closest_after(3, 9).map(|(gen, _)| gen), None);
+
+        // Sequential, backward and random lookups, including with merged differences
+        let max_bytes = 4 * universe.footprint() + 30_000;
+        let budget = MemoryBudget::new(max_bytes, EvictionPolicy::MergeDiffs);
+        let config = HistoryConfig::new(16).memory_budget(budget).cache_size(3);
+        let (history, gens) = run_history(config, 60);
+        let lookups = (0..60)
+            .chain((0..60).rev())
+            .chain((0..60).map(|i| (i * 37) % 60));
+        for gen in lookups {
+            match history.get_gen(gen) {
+                Some(universe) => assert!(same(&universe, &gens[gen])),
+                None => assert!(gen < 48 && gen % 16 != 0),
+            }
+        }
+    }
+}
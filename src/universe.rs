@@ -1,9 +1,11 @@
 // Standard library
+use std::io::{self, Read, Write};
 use std::sync::Arc;
 
 // External libraries
 use vulkano::descriptor::descriptor_set::UnsafeDescriptorSetLayout;
 use vulkano::device::Device;
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice};
 use vulkano::pipeline::ComputePipelineAbstract;
 
 // Local
@@ -12,7 +14,7 @@ use crate::automaton::{AutomatonCell, CPUCell, GPUCell};
 
 pub trait Universe: Clone + Sized + Send + 'static {
     type Cell: AutomatonCell;
-    type Coordinates: Clone;
+    type Coordinates: Clone + Send;
 
     fn get(&self, coords: Self::Coordinates) -> Self::Cell;
 
@@ -51,6 +53,31 @@ where
     }
 }
 
+/// Universes that can produce a difference while evolving, which saves comparing every cell of
+/// the two generations afterwards.
+pub trait CPUDiffUniverse<D: GenerationDifference<Universe = Self>>: CPUUniverse
+where
+    Self::Cell: CPUCell,
+{
+    /// Returns the next generation along with its difference to this one.
+    fn cpu_evolve_once_diff(self) -> (Self, D) {
+        let next = self.clone().cpu_evolve_once();
+        let diff = D::get_diff(&self, &next);
+        (next, diff)
+    }
+}
+
+/// Universes that can spread the update of their cells over several threads.
+pub trait ParallelCPUUniverse<D: GenerationDifference<Universe = Self>>:
+    CPUDiffUniverse<D>
+where
+    Self::Cell: CPUCell,
+{
+    /// Returns the next generation along with its difference to this one, using at most
+    /// `n_threads` threads.
+    fn par_cpu_evolve_once_diff(self, n_threads: usize) -> (Self, D);
+}
+
 pub trait GPUUniverse: Universe
 where
     Self::Cell: GPUCell,
@@ -77,6 +104,16 @@ where
     }
 }
 
+/// Returns whether there is a Vulkan device able to run compute shaders, which GPU universes need
+/// to evolve.
+pub fn gpu_available() -> bool {
+    match Instance::new(None, &InstanceExtensions::none(), None) {
+        Ok(instance) => PhysicalDevice::enumerate(&instance)
+            .any(|physical| physical.queue_families().any(|q| q.supports_compute())),
+        Err(_) => false,
+    }
+}
+
 #[derive(Clone)]
 pub struct ShaderInfo {
     pub layout: Arc<UnsafeDescriptorSetLayout>,
@@ -95,6 +132,15 @@ pub trait GenerationDifference: Clone + Send + 'static {
 
     fn apply_to(&self, base: Self::Universe) -> Self::Universe;
 
+    /// Returns whether the difference only concerns cells that exist in a universe. Differences
+    /// read back from storage are checked before being applied.
+    fn fits(&self, _universe: &Self::Universe) -> bool {
+        true
+    }
+
+    /// Undoes the difference, turning the target universe back into the base one.
+    fn unapply_from(&self, target: Self::Universe) -> Self::Universe;
+
     fn stack(&mut self, other: &Self);
 
     fn stack_mul(diffs: &[Self]) -> Self {
@@ -109,3 +155,50 @@ pub trait GenerationDifference: Clone + Send + 'static {
         }
     }
 }
+
+/// Universes and differences that can be written to and read back from a byte stream (e.g., to
+/// move parts of a simulation's history to disk), and whose memory usage can be estimated.
+pub trait Storable: Sized {
+    /// Returns the approximate number of bytes used in memory.
+    fn footprint(&self) -> usize;
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Reads back what `store` wrote. Corrupted data is reported as an `InvalidData` error.
+    fn load(reader: &mut dyn Read) -> io::Result<Self>;
+}
+
+pub(crate) fn write_u64(writer: &mut dyn Write, val: u64) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub(crate) fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn write_u32(writer: &mut dyn Write, val: u32) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub(crate) fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_cell<C: AutomatonCell<Encoded = u32>>(reader: &mut dyn Read) -> io::Result<C> {
+    C::try_decode(&read_u32(reader)?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, ERR_UNKNOWN_CELL))
+}
+
+/// Returns how many items to reserve room for when reading a number of them from storage, so that
+/// corrupted lengths fail when reading runs out of data rather than when allocating.
+pub(crate) fn load_capacity(len: usize) -> usize {
+    len.min(MAX_LOAD_CAPACITY)
+}
+
+const MAX_LOAD_CAPACITY: usize = 1 << 16;
+
+const ERR_UNKNOWN_CELL: &str = "Unknown cell encoding.";
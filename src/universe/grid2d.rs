@@ -1,10 +1,98 @@
 // Standard library
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::mem;
 
 // Local
 pub mod infinite_grid2d;
 pub mod static_grid2d;
+use crate::universe::{read_u64, write_u64, GenerationDifference, Storable, Universe};
+
+/// Universe2D
+
+pub trait Universe2D: Universe {
+    /// Returns the cell at the given signed coordinates. Coordinates that fall outside of the
+    /// universe yield the default cell.
+    fn get_signed(&self, coords: SCoordinates2D) -> Self::Cell;
+
+    /// Returns the smallest window containing every cell of interest in the universe.
+    fn bounds(&self) -> Window2D;
+}
+
+/// Difference2D
+
+pub trait Difference2D: GenerationDifference {
+    /// Returns the coordinates of all cells the difference modifies when applied to `base`.
+    /// Coordinates may be listed even if the cell's value is unchanged.
+    fn modified_coords(&self, base: &Self::Universe) -> Vec<SCoordinates2D>;
+
+    /// Returns the coordinates, old value and new value of every cell the difference changes,
+    /// given the universe it leads to.
+    fn changed_cells(&self, target: &Self::Universe) -> Vec<CellChange<Self::Universe>>;
+}
+
+/// Coordinates, old value and new value of a cell that changed.
+pub type CellChange<U> = (SCoordinates2D, <U as Universe>::Cell, <U as Universe>::Cell);
+
+/// Window2D
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Window2D {
+    pub origin: SCoordinates2D,
+    pub size: Size2D,
+}
+
+impl Window2D {
+    pub fn new(origin: SCoordinates2D, size: Size2D) -> Self {
+        Self { origin, size }
+    }
+
+    /// Returns the smallest window containing all the given coordinates.
+    pub fn enclosing(coords: impl Iterator<Item = SCoordinates2D>) -> Self {
+        let mut bounds: Option<(SCoordinates2D, SCoordinates2D)> = None;
+        for c in coords {
+            bounds = match bounds {
+                Some((min, max)) => Some((
+                    SCoordinates2D(min.x().min(c.x()), min.y().min(c.y())),
+                    SCoordinates2D(max.x().max(c.x()), max.y().max(c.y())),
+                )),
+                None => Some((c, c)),
+            };
+        }
+        match bounds {
+            Some((min, max)) => Self {
+                origin: min,
+                size: Size2D(
+                    (max.x() - min.x() + 1) as usize,
+                    (max.y() - min.y() + 1) as usize,
+                ),
+            },
+            None => Self {
+                origin: SCoordinates2D(0, 0),
+                size: Size2D(0, 0),
+            },
+        }
+    }
+
+    /// Returns the window's coordinates corresponding to the given coordinates relative to the
+    /// window's origin.
+    #[inline]
+    pub fn to_absolute(&self, rel_coords: Coordinates2D) -> SCoordinates2D {
+        SCoordinates2D(
+            self.origin.x() + rel_coords.x() as isize,
+            self.origin.y() + rel_coords.y() as isize,
+        )
+    }
+
+    #[inline]
+    pub fn contains(&self, coords: SCoordinates2D) -> bool {
+        self.origin.x() <= coords.x()
+            && coords.x() < self.origin.x() + self.size.columns() as isize
+            && self.origin.y() <= coords.y()
+            && coords.y() < self.origin.y() + self.size.lines() as isize
+    }
+}
 
 /// Size2D
 
@@ -55,6 +143,21 @@ impl Coordinates2D {
     }
 }
 
+impl Storable for Coordinates2D {
+    fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.0 as u64)?;
+        write_u64(writer, self.1 as u64)
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok(Self(read_u64(reader)? as usize, read_u64(reader)? as usize))
+    }
+}
+
 /// RectangleIterator
 
 pub struct RectangleIterator {
@@ -111,7 +214,7 @@ impl Iterator for LineIterator {
 
 /// SCoordinates2D
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]  
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SCoordinates2D(pub isize, pub isize);
 
 impl SCoordinates2D {
@@ -142,6 +245,21 @@ impl SCoordinates2D {
     }
 }
 
+impl Storable for SCoordinates2D {
+    fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.0 as u64)?;
+        write_u64(writer, self.1 as u64)
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        Ok(Self(read_u64(reader)? as isize, read_u64(reader)? as isize))
+    }
+}
+
 /// Neighbor2D
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -10,7 +10,7 @@ use crate::{
     universe::{CPUUniverse, GPUUniverse, Universe},
 };
 
-use super::{Coordinates2D, Neighbor2D, SCoordinates2D};
+use super::{Coordinates2D, Neighbor2D, SCoordinates2D, Universe2D, Window2D};
 
 // Assumption : a cell in the default state whose neighborhood only consists of cells in the
 //              default state will remain in the default state in the next generation
@@ -57,6 +57,29 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> InfiniteGrid2D<C> {
         }
     }
 
+    pub fn non_default_cells(&self) -> Vec<(SCoordinates2D, C)> {
+        let default_cell = C::default();
+        let mut cells = Vec::new();
+        for (coords, chunk) in self.chunks.iter() {
+            if chunk.inner.borrow().is_empty {
+                continue;
+            }
+            let world_coords = coords.to_universe_coordinates(self.chunk_size_pow2);
+            for line in chunk.iter() {
+                for (local_coords, cell) in line {
+                    if cell != default_cell {
+                        let cell_coords = SCoordinates2D(
+                            world_coords.x() + local_coords.x() as isize,
+                            world_coords.y() + local_coords.y() as isize,
+                        );
+                        cells.push((cell_coords, cell));
+                    }
+                }
+            }
+        }
+        cells
+    }
+
     #[inline]
     fn create_chunk(&self, coords: SCoordinates2D) -> Option<Chunk<C>> {
         // TODO We should never create a chunk near the isize underflow/overflow boundary
@@ -125,6 +148,21 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> Universe for InfiniteGrid2D<C> {
     }
 }
 
+impl<C: AutomatonCell<Neighbor = Neighbor2D>> Universe2D for InfiniteGrid2D<C> {
+    #[inline]
+    fn get_signed(&self, coords: SCoordinates2D) -> Self::Cell {
+        self.get(coords)
+    }
+
+    fn bounds(&self) -> Window2D {
+        Window2D::enclosing(
+            self.non_default_cells()
+                .into_iter()
+                .map(|(coords, _)| coords),
+        )
+    }
+}
+
 impl<C: CPUCell<Neighbor = Neighbor2D>> CPUUniverse for InfiniteGrid2D<C> {
     fn cpu_evolve_once(mut self) -> Self {
         let mut all_adjacent_chunks = HashSet::new();
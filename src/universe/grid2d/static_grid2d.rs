@@ -1,6 +1,9 @@
 // Standard library
 use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::mem;
 use std::sync::Arc;
+use std::thread;
 
 // External library
 use vulkano::{
@@ -13,14 +16,20 @@ use vulkano::{
 };
 
 // Local
-use super::{Coordinates2D, Neighbor2D, Size2D};
+mod compact_diff;
+use super::{
+    CellChange, Coordinates2D, Difference2D, Neighbor2D, SCoordinates2D, Size2D, Universe2D,
+    Window2D,
+};
 use crate::{
     automaton::{AutomatonCell, CPUCell, GPUCell},
     universe::{
-        CPUUniverse, GPUUniverse, GenerationDifference, ShaderInfo, Universe,
+        load_capacity, read_cell, read_u64, write_u32, write_u64, CPUDiffUniverse, CPUUniverse,
+        GPUUniverse, GenerationDifference, ParallelCPUUniverse, ShaderInfo, Storable, Universe,
         UniverseAutomatonShader,
     },
 };
+pub use compact_diff::CompactGridDiff;
 
 const DISPATCH_LAYOUT: (usize, usize, usize) = (8, 8, 1);
 
@@ -96,6 +105,28 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> StaticGrid2D<C> {
         }
     }
 
+    /// Creates a random soup in which each cell is in `state` with probability `density`, and in
+    /// the default state otherwise. The same seed always gives the same soup.
+    pub fn soup(size: Size2D, density: f64, seed: u64, state: C) -> Self {
+        let mut rng = seed;
+        let data = (0..size.total())
+            .map(|_| {
+                // SplitMix64, good enough for soups
+                rng = rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = rng;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^= z >> 31;
+                if ((z >> 11) as f64 / (1u64 << 53) as f64) < density {
+                    state
+                } else {
+                    C::default()
+                }
+            })
+            .collect();
+        Self::new(data, size)
+    }
+
     pub fn encode(&self) -> Vec<C::Encoded> {
         let mut encoded = Vec::with_capacity(self.size.total());
         for cell in self.data.iter() {
@@ -138,6 +169,24 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> StaticGrid2D<C> {
     pub fn iter(&self) -> StaticGrid2DIterator<C> {
         StaticGrid2DIterator::new(self)
     }
+
+    /// Whether an index into the data is that of a cell, rather than in the margin or beyond.
+    fn is_cell_idx(&self, idx: usize) -> bool {
+        let columns = self.size_with_margin.columns();
+        let (x, y) = (idx % columns, idx / columns);
+        (self.margin..self.margin + self.size.columns()).contains(&x)
+            && (self.margin..self.margin + self.size.lines()).contains(&y)
+    }
+
+    /// Converts an index in the data (margins included) to the cell's coordinates.
+    fn idx_to_coords(&self, idx: usize) -> SCoordinates2D {
+        let columns = self.size_with_margin.columns();
+        let margin = self.margin as isize;
+        SCoordinates2D(
+            (idx % columns) as isize - margin,
+            (idx / columns) as isize - margin,
+        )
+    }
 }
 
 impl<C: AutomatonCell<Neighbor = Neighbor2D>> Universe for StaticGrid2D<C> {
@@ -174,23 +223,115 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> Universe for StaticGrid2D<C> {
     }
 }
 
-impl<C: CPUCell<Neighbor = Neighbor2D>> CPUUniverse for StaticGrid2D<C> {
-    fn cpu_evolve_once(mut self) -> Self {
-        // Compute new grid
+impl<C: AutomatonCell<Neighbor = Neighbor2D>> Universe2D for StaticGrid2D<C> {
+    fn get_signed(&self, coords: SCoordinates2D) -> Self::Cell {
+        if 0 <= coords.x()
+            && (coords.x() as usize) < self.size.columns()
+            && 0 <= coords.y()
+            && (coords.y() as usize) < self.size.lines()
+        {
+            self.get(Coordinates2D(coords.x() as usize, coords.y() as usize))
+        } else {
+            C::default()
+        }
+    }
+
+    fn bounds(&self) -> Window2D {
+        Window2D::new(SCoordinates2D(0, 0), self.size)
+    }
+}
+
+impl<C: CPUCell<Neighbor = Neighbor2D>> StaticGrid2D<C> {
+    /// Computes the data of the next generation, calling `on_change` with the index, old value and
+    /// new value of every cell that changes.
+    fn cpu_next_data(&self, mut on_change: impl FnMut(usize, C, C)) -> Vec<C> {
         let mut new_data = vec![C::default(); self.size_with_margin.total()];
         for line_iter in self.iter() {
             for (coords, cell) in line_iter {
-                let new_cell = cell.update(&self, coords);
+                let new_cell = cell.update(self, coords);
                 let real_coords = Coordinates2D(coords.x() + self.margin, coords.y() + self.margin);
-                new_data[real_coords.to_idx(&self.size_with_margin)] = new_cell;
+                let idx = real_coords.to_idx(&self.size_with_margin);
+                if new_cell != cell {
+                    on_change(idx, cell, new_cell);
+                }
+                new_data[idx] = new_cell;
             }
         }
+        new_data
+    }
 
-        self.data = new_data;
+    /// Computes the data of the next generation like `cpu_next_data`, with lines split into bands
+    /// that are updated by different threads. Also returns the index, old value and new value of
+    /// every cell that changes, ordered by index.
+    fn par_cpu_next_data(&self, n_threads: usize) -> (Vec<C>, Vec<(usize, C, C)>) {
+        let width = self.size_with_margin.columns();
+        let first_idx = self.margin * width;
+        let band_lines = self.size.lines().div_ceil(n_threads.max(1)).max(1);
+        let mut new_data = vec![C::default(); self.size_with_margin.total()];
+        let mut changes = vec![];
+        thread::scope(|scope| {
+            let bands: Vec<_> = new_data[first_idx..first_idx + self.size.lines() * width]
+                .chunks_mut(band_lines * width)
+                .enumerate()
+                .map(|(band_idx, band)| {
+                    scope.spawn(move || {
+                        let mut changes = vec![];
+                        for (i, line) in band.chunks_mut(width).enumerate() {
+                            let line_idx = band_idx * band_lines + i;
+                            for (coords, cell) in StaticGrid2DLineIterator::new(self, line_idx) {
+                                let new_cell = cell.update(self, coords);
+                                let x = coords.x() + self.margin;
+                                if new_cell != cell {
+                                    changes.push((
+                                        first_idx + line_idx * width + x,
+                                        cell,
+                                        new_cell,
+                                    ));
+                                }
+                                line[x] = new_cell;
+                            }
+                        }
+                        changes
+                    })
+                })
+                .collect();
+            for band in bands {
+                changes.extend(band.join().unwrap());
+            }
+        });
+        (new_data, changes)
+    }
+}
+
+impl<C: CPUCell<Neighbor = Neighbor2D>> CPUUniverse for StaticGrid2D<C> {
+    fn cpu_evolve_once(mut self) -> Self {
+        self.data = self.cpu_next_data(|_, _, _| ());
         self
     }
 }
 
+impl<C: CPUCell<Neighbor = Neighbor2D>> CPUDiffUniverse<GridDiff<C>> for StaticGrid2D<C> {
+    fn cpu_evolve_once_diff(mut self) -> (Self, GridDiff<C>) {
+        let mut modifs = HashMap::new();
+        self.data = self.cpu_next_data(|idx, old, new| {
+            modifs.insert(idx, (old, new));
+        });
+        (self, GridDiff { modifs })
+    }
+}
+
+impl<C: CPUCell<Neighbor = Neighbor2D>> ParallelCPUUniverse<GridDiff<C>> for StaticGrid2D<C> {
+    fn par_cpu_evolve_once_diff(mut self, n_threads: usize) -> (Self, GridDiff<C>) {
+        let (data, changes) = self.par_cpu_next_data(n_threads);
+        self.data = data;
+        let modifs = changes
+            .into_iter()
+            .map(|(idx, old, new)| (idx, (old, new)))
+            .collect();
+        (self, GridDiff { modifs })
+    }
+}
+
 impl<C: GPUCell<Neighbor = Neighbor2D>> StaticGrid2D<C>
 where
     StaticGrid2D<C>: UniverseAutomatonShader<C>,
@@ -216,6 +357,45 @@ where
     }
 }
 
+impl<C: AutomatonCell<Neighbor = Neighbor2D, Encoded = u32>> Storable for StaticGrid2D<C> {
+    fn footprint(&self) -> usize {
+        mem::size_of::<Self>() + self.data.capacity() * mem::size_of::<C>()
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.size.columns() as u64)?;
+        write_u64(writer, self.size.lines() as u64)?;
+        for line_iter in self.iter() {
+            for (_, cell) in line_iter {
+                write_u32(writer, cell.encode())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let columns = read_u64(reader)? as usize;
+        let lines = read_u64(reader)? as usize;
+
+        // The margin must fit as well
+        let margin = Neighbor2D::max_one_axis_manhattan_distance(C::neighborhood());
+        let total = columns
+            .checked_add(margin << 1)
+            .zip(lines.checked_add(margin << 1))
+            .and_then(|(columns, lines)| columns.checked_mul(lines));
+        if total.is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, ERR_LOADED_SIZE));
+        }
+
+        let size = Size2D(columns, lines);
+        let mut data = Vec::with_capacity(load_capacity(size.total()));
+        for _ in 0..size.total() {
+            data.push(read_cell(reader)?);
+        }
+        Ok(Self::new(data, size))
+    }
+}
+
 impl<C: AutomatonCell> Clone for StaticGrid2D<C> {
     fn clone(&self) -> Self {
         Self {
@@ -291,14 +471,18 @@ impl<'a, C: AutomatonCell<Neighbor = Neighbor2D>> Iterator for StaticGrid2DLineI
 
 /// GridDiff
 
+/// The old and new values of the cells that changed, so that the difference can be undone.
 #[derive(Debug, Clone)]
 pub struct GridDiff<C: AutomatonCell> {
-    modifs: HashMap<usize, C>,
+    modifs: HashMap<usize, (C, C)>,
 }
 
 impl<C: AutomatonCell<Neighbor = Neighbor2D>> GridDiff<C> {
+    /// Iterates over the indices and new values of the cells that changed.
     pub fn iter(&self) -> impl Iterator<Item = (&usize, &C)> {
-        self.modifs.iter()
+        self.modifs
+            .iter()
+            .map(|(idx, (_, new_cell))| (idx, new_cell))
     }
 }
 
@@ -315,7 +499,7 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> GenerationDifference for GridDiff<
             let prev = &base.data[idx];
             let next = &target.data[idx];
             if prev != next {
-                modifs.insert(idx, *next);
+                modifs.insert(idx, (*prev, *next));
             }
         }
 
@@ -323,15 +507,23 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> GenerationDifference for GridDiff<
     }
 
     fn apply_to(&self, mut base: Self::Universe) -> Self::Universe {
-        let mut new_data = base.data.clone();
         for (idx, new_cell) in self.iter() {
-            new_data[*idx] = *new_cell
+            base.data[*idx] = *new_cell
         }
-
-        base.data = new_data;
         base
     }
 
+    fn fits(&self, universe: &Self::Universe) -> bool {
+        self.modifs.keys().all(|idx| universe.is_cell_idx(*idx))
+    }
+
+    fn unapply_from(&self, mut target: Self::Universe) -> Self::Universe {
+        for (idx, (old_cell, _)) in self.modifs.iter() {
+            target.data[*idx] = *old_cell
+        }
+        target
+    }
+
     fn empty_diff() -> Self {
         Self {
             modifs: HashMap::new(),
@@ -339,17 +531,67 @@ impl<C: AutomatonCell<Neighbor = Neighbor2D>> GenerationDifference for GridDiff<
     }
 
     fn stack(&mut self, other: &Self) {
-        for (coords, new_cell) in other.modifs.iter() {
-            match self.modifs.get_mut(coords) {
-                Some(old_cell) => *old_cell = *new_cell,
+        for (idx, (old_cell, new_cell)) in other.modifs.iter() {
+            match self.modifs.get(idx) {
+                // Cells that end up in their original state are unchanged
+                Some((first_cell, _)) if first_cell == new_cell => {
+                    self.modifs.remove(idx);
+                }
+                Some((first_cell, _)) => {
+                    self.modifs.insert(*idx, (*first_cell, *new_cell));
+                }
                 None => {
-                    self.modifs.insert(*coords, *new_cell);
+                    self.modifs.insert(*idx, (*old_cell, *new_cell));
                 }
             }
         }
     }
 }
 
+impl<C: AutomatonCell<Neighbor = Neighbor2D, Encoded = u32>> Storable for GridDiff<C> {
+    fn footprint(&self) -> usize {
+        // Each hash map entry also carries one control byte
+        mem::size_of::<Self>() + self.modifs.capacity() * (mem::size_of::<(usize, (C, C))>() + 1)
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.modifs.len() as u64)?;
+        for (idx, (old_cell, new_cell)) in self.modifs.iter() {
+            write_u64(writer, *idx as u64)?;
+            write_u32(writer, old_cell.encode())?;
+            write_u32(writer, new_cell.encode())?;
+        }
+        Ok(())
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let len = read_u64(reader)? as usize;
+        let mut modifs = HashMap::with_capacity(load_capacity(len));
+        for _ in 0..len {
+            let idx = read_u64(reader)? as usize;
+            let old_cell = read_cell(reader)?;
+            modifs.insert(idx, (old_cell, read_cell(reader)?));
+        }
+        Ok(Self { modifs })
+    }
+}
+
+impl<C: AutomatonCell<Neighbor = Neighbor2D>> Difference2D for GridDiff<C> {
+    fn modified_coords(&self, base: &Self::Universe) -> Vec<SCoordinates2D> {
+        self.modifs
+            .keys()
+            .map(|idx| base.idx_to_coords(*idx))
+            .collect()
+    }
+
+    fn changed_cells(&self, target: &Self::Universe) -> Vec<CellChange<Self::Universe>> {
+        self.modifs
+            .iter()
+            .map(|(idx, (old_cell, new_cell))| (target.idx_to_coords(*idx), *old_cell, *new_cell))
+            .collect()
+    }
+}
+
 /// GPUCompute
 
 #[derive(Clone)]
@@ -724,3 +966,66 @@ const ERR_DECODED_SIZE: &str =
     "The size of decoded data doesn't correspond to the indicated grid size.";
 const ERR_WRONG_DIMENSIONS: &str = "Both grids should be the same dimensions!";
 const ERR_DIMENSIONS_SIZE: &str = "Vector length does not correspond to Size2D.";
+const ERR_LOADED_SIZE: &str = "The grid is too large to be loaded.";
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CPUDiffUniverse, CPUUniverse, GenerationDifference, GridDiff, ParallelCPUUniverse,
+        StaticGrid2D, Storable,
+    };
+    use crate::automaton::game_of_life::{self, GameOfLife};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn cpu_evolution_diffs() {
+        let mut grid = game_of_life::r_pentomino();
+        for _ in 0..50 {
+            let expected = grid.clone().cpu_evolve_once();
+            let (next, diff): (_, GridDiff<GameOfLife>) = grid.clone().cpu_evolve_once_diff();
+            assert_eq!(next.data, expected.data);
+            assert_eq!(diff.modifs, GridDiff::get_diff(&grid, &next).modifs);
+            let (par_next, par_diff): (_, GridDiff<GameOfLife>) =
+                grid.clone().par_cpu_evolve_once_diff(3);
+            assert_eq!(par_next.data, next.data);
+            assert_eq!(par_diff.modifs, diff.modifs);
+            assert_eq!(diff.unapply_from(next.clone()).data, grid.data);
+            assert_eq!(diff.apply_to(grid).data, next.data);
+            grid = next;
+        }
+    }
+
+    #[test]
+    fn corrupted_loads() {
+        type Grid = StaticGrid2D<GameOfLife>;
+        type Diff = GridDiff<GameOfLife>;
+        let invalid = |err: std::io::Error| err.kind() == ErrorKind::InvalidData;
+        let grid = game_of_life::r_pentomino();
+        let mut bytes = vec![];
+        grid.store(&mut bytes).unwrap();
+        assert_eq!(Grid::load(&mut &bytes[..]).unwrap().data, grid.data);
+
+        // Unknown cell encodings
+        let mut tampered = bytes.clone();
+        tampered[16] = 7;
+        assert!(invalid(Grid::load(&mut &tampered[..]).err().unwrap()));
+
+        // Sizes that can't be allocated, or whose data is missing
+        let mut huge = vec![0xff; 16];
+        huge.extend_from_slice(&bytes[16..]);
+        assert!(invalid(Grid::load(&mut &huge[..]).err().unwrap()));
+        let mut large = 1u64.to_le_bytes().to_vec();
+        large.extend_from_slice(&(1u64 << 40).to_le_bytes());
+        assert!(Grid::load(&mut &large[..]).is_err());
+
+        // Differences must stay inside the grid
+        let diff = Diff::get_diff(&grid, &grid.clone().cpu_evolve_once());
+        assert!(diff.fits(&grid));
+        let mut bytes = vec![];
+        diff.store(&mut bytes).unwrap();
+        bytes[8..16].copy_from_slice(&0u64.to_le_bytes());
+        assert!(!Diff::load(&mut &bytes[..]).unwrap().fits(&grid));
+        bytes[0..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Diff::load(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,756 @@
+// Standard library
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+
+// Local
+use super::StaticGrid2D;
+use crate::{
+    automaton::{AutomatonCell, CPUCell},
+    universe::{
+        grid2d::{CellChange, Difference2D, Neighbor2D, SCoordinates2D},
+        load_capacity, read_u32, read_u64, write_u32, write_u64, CPUDiffUniverse,
+        GenerationDifference, ParallelCPUUniverse, Storable,
+    },
+};
+
+/// CompactGridDiff
+
+/// A difference between two `StaticGrid2D`s that uses much less memory than a `GridDiff`. Each
+/// difference picks the smallest encoding for the indices of the cells that changed (a bitset
+/// over the whole grid, or run-length encoded gaps between changed cells) and stores the XOR of
+/// their old and new encoded states packed on as few bits as needed, so that the difference can
+/// be undone as easily as it is applied. Cells that flip the lowest bit of their encoded state
+/// don't need anything stored at all, so differences of two-state automata boil down to their
+/// indices.
+#[derive(Debug, Clone)]
+pub struct CompactGridDiff<C: AutomatonCell> {
+    /// Number of cells in the grid, margins included.
+    len: usize,
+    indices: Indices,
+    values: Values,
+    _marker: PhantomData<C>,
+}
+
+impl<C: AutomatonCell<Encoded = u32>> CompactGridDiff<C> {
+    /// Returns the number of changed cells.
+    pub fn n_changes(&self) -> usize {
+        self.indices.decode(self.len).len()
+    }
+
+    /// Returns whether the changed cells are stored as a bitset rather than as runs.
+    pub fn is_bitset(&self) -> bool {
+        matches!(self.indices, Indices::Bitset(_))
+    }
+
+    /// Returns whether every changed cell flips, in which case no delta is stored.
+    pub fn is_xor_delta(&self) -> bool {
+        matches!(self.values, Values::Flips)
+    }
+
+    fn from_cells(len: usize, cells: impl Iterator<Item = (usize, C, C)>) -> Self {
+        let changes = cells
+            .filter(|(_, old, new)| old != new)
+            .map(|(idx, old, new)| (idx, Change::xor(old.encode() ^ new.encode())))
+            .collect();
+        Self::from_changes(len, changes)
+    }
+
+    /// Encodes changes sorted by index.
+    fn from_changes(len: usize, changes: Vec<(usize, Change)>) -> Self {
+        let indices: Vec<_> = changes.iter().map(|(idx, _)| *idx).collect();
+        Self {
+            len,
+            indices: Indices::encode(len, &indices),
+            values: Values::encode(changes.iter().map(|(_, change)| *change)),
+            _marker: PhantomData,
+        }
+    }
+
+    fn changes(&self) -> Vec<(usize, Change)> {
+        self.indices
+            .decode(self.len)
+            .into_iter()
+            .zip(self.values.decode())
+            .collect()
+    }
+}
+
+impl<C: AutomatonCell<Neighbor = Neighbor2D, Encoded = u32>> GenerationDifference
+    for CompactGridDiff<C>
+{
+    type Universe = StaticGrid2D<C>;
+
+    fn empty_diff() -> Self {
+        Self::from_changes(0, vec![])
+    }
+
+    fn get_diff(base: &Self::Universe, target: &Self::Universe) -> Self {
+        if base.size() != target.size() {
+            panic!("{}", ERR_WRONG_DIMENSIONS)
+        }
+        let cells = base
+            .data
+            .iter()
+            .zip(target.data.iter())
+            .enumerate()
+            .map(|(idx, (old, new))| (idx, *old, *new));
+        Self::from_cells(base.data.len(), cells)
+    }
+
+    fn apply_to(&self, mut base: Self::Universe) -> Self::Universe {
+        for (idx, change) in self.changes() {
+            base.data[idx] = C::decode(&(base.data[idx].encode() ^ change.delta()));
+        }
+        base
+    }
+
+    fn fits(&self, universe: &Self::Universe) -> bool {
+        // Indices are only meaningful for grids of the same length
+        self.len == universe.data.len() || self.n_changes() == 0
+    }
+
+    fn unapply_from(&self, target: Self::Universe) -> Self::Universe {
+        // XOR-deltas are their own inverse
+        self.apply_to(target)
+    }
+
+    fn stack(&mut self, other: &Self) {
+        // Merge both sorted lists of changes
+        let mut stacked = Vec::new();
+        let mut mine = self.changes().into_iter().peekable();
+        let mut theirs = other.changes().into_iter().peekable();
+        loop {
+            let next = match (mine.peek(), theirs.peek()) {
+                (Some((idx, _)), Some((other_idx, _))) if idx < other_idx => mine.next(),
+                (Some((idx, _)), Some((other_idx, _))) if other_idx < idx => theirs.next(),
+                (Some(_), Some(_)) => {
+                    let (idx, change) = mine.next().unwrap();
+                    match change.delta() ^ theirs.next().unwrap().1.delta() {
+                        // The cell ends up in its original state
+                        0 => continue,
+                        delta => Some((idx, Change::xor(delta))),
+                    }
+                }
+                (Some(_), None) => mine.next(),
+                (None, Some(_)) => theirs.next(),
+                (None, None) => break,
+            };
+            stacked.extend(next);
+        }
+        *self = Self::from_changes(self.len.max(other.len), stacked);
+    }
+}
+
+impl<C: CPUCell<Neighbor = Neighbor2D, Encoded = u32>> CPUDiffUniverse<CompactGridDiff<C>>
+    for StaticGrid2D<C>
+{
+    fn cpu_evolve_once_diff(mut self) -> (Self, CompactGridDiff<C>) {
+        let mut cells = vec![];
+        self.data = self.cpu_next_data(|idx, old, new| cells.push((idx, old, new)));
+        let diff = CompactGridDiff::from_cells(self.data.len(), cells.into_iter());
+        (self, diff)
+    }
+}
+
+impl<C: CPUCell<Neighbor = Neighbor2D, Encoded = u32>> ParallelCPUUniverse<CompactGridDiff<C>>
+    for StaticGrid2D<C>
+{
+    fn par_cpu_evolve_once_diff(mut self, n_threads: usize) -> (Self, CompactGridDiff<C>) {
+        let (data, cells) = self.par_cpu_next_data(n_threads);
+        self.data = data;
+        let diff = CompactGridDiff::from_cells(self.data.len(), cells.into_iter());
+        (self, diff)
+    }
+}
+
+impl<C: AutomatonCell<Neighbor = Neighbor2D, Encoded = u32>> Storable for CompactGridDiff<C> {
+    fn footprint(&self) -> usize {
+        let indices = match &self.indices {
+            Indices::Bitset(words) => words.capacity() * mem::size_of::<u64>(),
+            Indices::Runs(bytes) => bytes.capacity(),
+        };
+        let values = match &self.values {
+            Values::Flips => 0,
+            Values::States(states) => states.footprint(),
+            Values::Mixed { flips, states } => {
+                flips.capacity() * mem::size_of::<u64>() + states.footprint()
+            }
+        };
+        mem::size_of::<Self>() + indices + values
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u64(writer, self.len as u64)?;
+        match &self.indices {
+            Indices::Bitset(words) => {
+                writer.write_all(&[0])?;
+                write_words(writer, words)?;
+            }
+            Indices::Runs(bytes) => {
+                writer.write_all(&[1])?;
+                write_u64(writer, bytes.len() as u64)?;
+                writer.write_all(bytes)?;
+            }
+        }
+        match &self.values {
+            Values::Flips => writer.write_all(&[0]),
+            Values::States(states) => {
+                writer.write_all(&[1])?;
+                states.store(writer)
+            }
+            Values::Mixed { flips, states } => {
+                writer.write_all(&[2])?;
+                write_words(writer, flips)?;
+                states.store(writer)
+            }
+        }
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let len = read_u64(reader)? as usize;
+        let indices = match read_tag(reader)? {
+            0 => {
+                let words = read_words(reader)?;
+                if words.len() < n_words(len) {
+                    return Err(invalid_data());
+                }
+                Indices::Bitset(words)
+            }
+            1 => {
+                let n_bytes = read_u64(reader)?;
+                let mut bytes = Vec::with_capacity(load_capacity(n_bytes as usize));
+                if reader.take(n_bytes).read_to_end(&mut bytes)? as u64 != n_bytes {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Indices::Runs(bytes)
+            }
+            _ => return Err(invalid_data()),
+        };
+        let n_changes = indices.count(len).ok_or_else(invalid_data)?;
+        let values = match read_tag(reader)? {
+            0 => Values::Flips,
+            1 => Values::States(PackedStates::load(reader)?),
+            2 => Values::Mixed {
+                flips: read_words(reader)?,
+                states: PackedStates::load(reader)?,
+            },
+            _ => return Err(invalid_data()),
+        };
+        if !values.holds(n_changes) {
+            return Err(invalid_data());
+        }
+        Ok(Self {
+            len,
+            indices,
+            values,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<C: AutomatonCell<Neighbor = Neighbor2D, Encoded = u32>> Difference2D for CompactGridDiff<C> {
+    fn modified_coords(&self, base: &Self::Universe) -> Vec<SCoordinates2D> {
+        self.indices
+            .decode(self.len)
+            .into_iter()
+            .map(|idx| base.idx_to_coords(idx))
+            .collect()
+    }
+
+    fn changed_cells(&self, target: &Self::Universe) -> Vec<CellChange<Self::Universe>> {
+        self.changes()
+            .into_iter()
+            .map(|(idx, change)| {
+                let new_cell = target.data[idx];
+                let old_cell = C::decode(&(new_cell.encode() ^ change.delta()));
+                (target.idx_to_coords(idx), old_cell, new_cell)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Change {
+    /// The cell's encoded state is XORed with a delta.
+    Xor(u32),
+    /// The lowest bit of the cell's encoded state flips.
+    Flip,
+}
+
+impl Change {
+    fn xor(delta: u32) -> Self {
+        if delta == 1 {
+            Change::Flip
+        } else {
+            Change::Xor(delta)
+        }
+    }
+
+    fn delta(self) -> u32 {
+        match self {
+            Change::Xor(delta) => delta,
+            Change::Flip => 1,
+        }
+    }
+}
+
+/// Indices
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Indices {
+    /// One bit per cell of the grid.
+    Bitset(Vec<u64>),
+    /// Alternating gaps between runs of changed cells and lengths of those runs, as variable-length
+    /// integers.
+    Runs(Vec<u8>),
+}
+
+impl Indices {
+    /// Picks the smallest encoding for sorted indices.
+    fn encode(len: usize, indices: &[usize]) -> Self {
+        let mut runs = vec![];
+        let mut cursor = 0;
+        let mut i = 0;
+        while i < indices.len() {
+            let start = indices[i];
+            let mut end = start + 1;
+            i += 1;
+            while i < indices.len() && indices[i] == end {
+                end += 1;
+                i += 1;
+            }
+            write_varint(&mut runs, start - cursor);
+            write_varint(&mut runs, end - start);
+            cursor = end;
+        }
+
+        if runs.len() <= n_words(len) * mem::size_of::<u64>() {
+            Indices::Runs(runs)
+        } else {
+            let mut words = vec![0; n_words(len)];
+            for idx in indices {
+                words[idx / 64] |= 1 << (idx % 64);
+            }
+            Indices::Bitset(words)
+        }
+    }
+
+    fn decode(&self, len: usize) -> Vec<usize> {
+        match self {
+            Indices::Bitset(words) => (0..len)
+                .filter(|idx| words[idx / 64] & (1 << (idx % 64)) != 0)
+                .collect(),
+            Indices::Runs(bytes) => decode_runs(bytes)
+                .unwrap_or_else(|| panic!("{}", ERR_INVALID_DIFF))
+                .into_iter()
+                .flat_map(|(start, end)| start..end)
+                .collect(),
+        }
+    }
+
+    /// Returns the number of changed cells, or `None` if the indices can't be decoded or don't
+    /// all belong to a grid of `len` cells.
+    fn count(&self, len: usize) -> Option<usize> {
+        match self {
+            Indices::Bitset(words) if n_words(len) <= words.len() => Some(self.decode(len).len()),
+            Indices::Bitset(_) => None,
+            Indices::Runs(bytes) => {
+                let runs = decode_runs(bytes)?;
+                match runs.last() {
+                    Some((_, end)) if len < *end => None,
+                    _ => Some(runs.iter().map(|(start, end)| end - start).sum()),
+                }
+            }
+        }
+    }
+}
+
+/// Returns the start and end of each run of changed cells, or `None` if the variable-length
+/// integers are truncated or overflow.
+fn decode_runs(bytes: &[u8]) -> Option<Vec<(usize, usize)>> {
+    let mut runs = vec![];
+    let mut pos = 0;
+    let mut cursor: usize = 0;
+    while pos < bytes.len() {
+        let start = cursor.checked_add(read_varint(bytes, &mut pos)?)?;
+        let end = start.checked_add(read_varint(bytes, &mut pos)?)?;
+        runs.push((start, end));
+        cursor = end;
+    }
+    Some(runs)
+}
+
+/// Values
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Values {
+    /// Every changed cell flips.
+    Flips,
+    /// The delta of every changed cell.
+    States(PackedStates),
+    /// One bit per change telling whether it is a flip, and the deltas of the other ones.
+    Mixed {
+        flips: Vec<u64>,
+        states: PackedStates,
+    },
+}
+
+impl Values {
+    fn encode(changes: impl Iterator<Item = Change>) -> Self {
+        let changes: Vec<_> = changes.collect();
+        let states: Vec<_> = changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::Xor(delta) => Some(*delta),
+                Change::Flip => None,
+            })
+            .collect();
+        if states.is_empty() && !changes.is_empty() {
+            Values::Flips
+        } else if states.len() == changes.len() {
+            Values::States(PackedStates::pack(&states))
+        } else {
+            let mut flips = vec![0; n_words(changes.len())];
+            for (i, change) in changes.iter().enumerate() {
+                if *change == Change::Flip {
+                    flips[i / 64] |= 1 << (i % 64);
+                }
+            }
+            Values::Mixed {
+                flips,
+                states: PackedStates::pack(&states),
+            }
+        }
+    }
+
+    /// Returns whether there is a value for each of `n_changes` changes.
+    fn holds(&self, n_changes: usize) -> bool {
+        match self {
+            Values::Flips => true,
+            Values::States(states) => states.len == n_changes,
+            Values::Mixed { flips, states } => {
+                n_words(n_changes) <= flips.len()
+                    && (0..n_changes)
+                        .filter(|i| flips[i / 64] & (1 << (i % 64)) == 0)
+                        .count()
+                        == states.len
+            }
+        }
+    }
+
+    /// Returns an endless iterator over the changes, to be zipped with the indices.
+    fn decode(&self) -> Box<dyn Iterator<Item = Change> + '_> {
+        match self {
+            Values::Flips => Box::new(std::iter::repeat(Change::Flip)),
+            Values::States(states) => {
+                Box::new((0..states.len).map(move |i| Change::Xor(states.get(i))))
+            }
+            Values::Mixed { flips, states } => {
+                let mut next_state = 0;
+                Box::new((0..).map(move |i: usize| {
+                    if flips[i / 64] & (1 << (i % 64)) != 0 {
+                        Change::Flip
+                    } else {
+                        next_state += 1;
+                        Change::Xor(states.get(next_state - 1))
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// PackedStates
+
+/// Encoded states (or deltas between them) packed on as many bits as the largest one needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PackedStates {
+    bits: u32,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl PackedStates {
+    fn pack(states: &[u32]) -> Self {
+        let max = states.iter().copied().max().unwrap_or(0);
+        let bits = (32 - max.leading_zeros()).max(1);
+        let mut words = vec![0; n_words(states.len() * bits as usize)];
+        for (i, state) in states.iter().enumerate() {
+            let bit = i * bits as usize;
+            let (word, offset) = (bit / 64, bit % 64);
+            words[word] |= (*state as u64) << offset;
+            if 64 < offset + bits as usize {
+                words[word + 1] |= (*state as u64) >> (64 - offset);
+            }
+        }
+        Self {
+            bits,
+            len: states.len(),
+            words,
+        }
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        let bit = i * self.bits as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut state = self.words[word] >> offset;
+        if 64 < offset + self.bits as usize {
+            state |= self.words[word + 1] << (64 - offset);
+        }
+        (state & ((1 << self.bits) - 1)) as u32
+    }
+
+    fn footprint(&self) -> usize {
+        self.words.capacity() * mem::size_of::<u64>()
+    }
+
+    fn store(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_u32(writer, self.bits)?;
+        write_u64(writer, self.len as u64)?;
+        write_words(writer, &self.words)
+    }
+
+    fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let bits = read_u32(reader)?;
+        let len = read_u64(reader)? as usize;
+        let words = read_words(reader)?;
+        let n_bits = len.checked_mul(bits as usize).ok_or_else(invalid_data)?;
+        if bits == 0 || 32 < bits || words.len() < n_words(n_bits) {
+            return Err(invalid_data());
+        }
+        Ok(Self { bits, len, words })
+    }
+}
+
+/// Returns the number of 64-bit words needed to hold some bits.
+fn n_words(n_bits: usize) -> usize {
+    n_bits.div_ceil(64)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut val: usize) {
+    while 0x80 <= val {
+        bytes.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+    bytes.push(val as u8);
+}
+
+/// Reads a variable-length integer, or returns `None` if it is truncated or overflows.
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut val: usize = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        let bits = ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if bits >> shift != (byte & 0x7f) as usize {
+            return None;
+        }
+        val |= bits;
+        if byte < 0x80 {
+            return Some(val);
+        }
+        shift += 7;
+    }
+}
+
+fn write_words(writer: &mut dyn Write, words: &[u64]) -> io::Result<()> {
+    write_u64(writer, words.len() as u64)?;
+    for word in words {
+        write_u64(writer, *word)?;
+    }
+    Ok(())
+}
+
+fn read_words(reader: &mut dyn Read) -> io::Result<Vec<u64>> {
+    let len = read_u64(reader)? as usize;
+    let mut words = Vec::with_capacity(load_capacity(len));
+    for _ in 0..len {
+        words.push(read_u64(reader)?);
+    }
+    Ok(words)
+}
+
+fn read_tag(reader: &mut dyn Read) -> io::Result<u8> {
+    let mut tag = [0];
+    reader.read_exact(&mut tag)?;
+    Ok(tag[0])
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, ERR_INVALID_DIFF)
+}
+
+const ERR_WRONG_DIMENSIONS: &str = "Both grids should be the same dimensions!";
+const ERR_INVALID_DIFF: &str = "The compact difference is corrupted.";
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use super::{Change, CompactGridDiff, Indices, PackedStates, Values};
+    use crate::{
+        automaton::game_of_life::{self, GameOfLife},
+        simulator::{Simulator, SyncSimulator},
+        universe::{
+            grid2d::static_grid2d::{GridDiff, StaticGrid2D},
+            CPUDiffUniverse, GenerationDifference, ParallelCPUUniverse, Storable,
+        },
+    };
+
+    #[test]
+    fn encodings() {
+        // Sparse changes are stored as runs, dense ones as a bitset
+        let sparse = [3, 4, 5, 100, 900];
+        assert!(matches!(Indices::encode(1024, &sparse), Indices::Runs(_)));
+        assert_eq!(Indices::encode(1024, &sparse).decode(1024), sparse.to_vec());
+        let dense: Vec<_> = (0..1024).filter(|idx| idx % 3 == 0).collect();
+        assert!(matches!(Indices::encode(1024, &dense), Indices::Bitset(_)));
+        assert_eq!(Indices::encode(1024, &dense).decode(1024), dense);
+
+        // States straddle word boundaries
+        let states: Vec<_> = (0..100).map(|i| (i * 7) % 23).collect();
+        let packed = PackedStates::pack(&states);
+        assert_eq!(packed.bits, 5);
+        assert_eq!((0..100).map(|i| packed.get(i)).collect::<Vec<_>>(), states);
+
+        // Flips stacked with other deltas
+        let mut diff: CompactGridDiff<GameOfLife> =
+            CompactGridDiff::from_changes(16, vec![(1, Change::Xor(2)), (2, Change::Flip)]);
+        assert!(matches!(diff.values, Values::Mixed { .. }));
+        let other = CompactGridDiff::from_changes(
+            16,
+            vec![(1, Change::Flip), (2, Change::Flip), (3, Change::Xor(6))],
+        );
+        diff.stack(&other);
+        assert_eq!(
+            diff.changes(),
+            vec![(1, Change::Xor(3)), (3, Change::Xor(6))]
+        );
+    }
+
+    #[test]
+    fn corrupted_loads() {
+        let reload = |diff: &CompactGridDiff<GameOfLife>| {
+            let mut bytes = vec![];
+            diff.store(&mut bytes).unwrap();
+            CompactGridDiff::<GameOfLife>::load(&mut &bytes[..])
+        };
+        let with = |indices, values| CompactGridDiff {
+            len: 16,
+            indices,
+            values,
+            _marker: PhantomData,
+        };
+
+        // Runs must decode to indices of the grid
+        assert!(reload(&with(Indices::Runs(vec![3, 2]), Values::Flips)).is_ok());
+        assert!(reload(&with(Indices::Runs(vec![3, 0x80]), Values::Flips)).is_err());
+        assert!(reload(&with(Indices::Runs(vec![15, 2]), Values::Flips)).is_err());
+        let mut overflowing = vec![0xff; 10];
+        overflowing.extend([0x7f, 1]);
+        assert!(reload(&with(Indices::Runs(overflowing), Values::Flips)).is_err());
+
+        // Every change needs a value
+        let states = PackedStates::pack(&[2, 6]);
+        assert!(reload(&with(
+            Indices::Runs(vec![3, 2]),
+            Values::States(states.clone())
+        ))
+        .is_ok());
+        assert!(reload(&with(Indices::Runs(vec![3, 3]), Values::States(states))).is_err());
+        let changes: Vec<_> = (0..100)
+            .map(|i| {
+                (
+                    i,
+                    if i % 2 == 0 {
+                        Change::Flip
+                    } else {
+                        Change::Xor(2)
+                    },
+                )
+            })
+            .collect();
+        let mut mixed = CompactGridDiff::<GameOfLife>::from_changes(128, changes);
+        assert!(reload(&mixed).is_ok());
+        if let Values::Mixed { flips, .. } = &mut mixed.values {
+            flips.pop();
+        }
+        assert!(reload(&mixed).is_err());
+
+        // Differences only fit grids of their length
+        let grid = game_of_life::r_pentomino();
+        let diff = with(Indices::Runs(vec![3, 2]), Values::Flips);
+        assert!(!diff.fits(&grid));
+        let diff: CompactGridDiff<GameOfLife> = GenerationDifference::get_diff(&grid, &grid);
+        assert!(diff.fits(&grid));
+        assert!(CompactGridDiff::empty_diff().fits(&grid));
+    }
+
+    #[test]
+    fn game_of_life_diffs() {
+        type Grid = StaticGrid2D<GameOfLife>;
+        let start = game_of_life::r_pentomino();
+        let mut grid: Grid = start.clone();
+        let mut stacked = CompactGridDiff::empty_diff();
+        let mut footprints = (0, 0);
+        for _ in 0..60 {
+            let (next, diff): (Grid, CompactGridDiff<GameOfLife>) =
+                grid.clone().cpu_evolve_once_diff();
+            let reference: GridDiff<GameOfLife> = GridDiff::get_diff(&grid, &next);
+            assert!(diff.is_xor_delta() || diff.n_changes() == 0);
+            assert_eq!(diff.n_changes(), reference.iter().count());
+            footprints.0 += diff.footprint();
+            footprints.1 += reference.footprint();
+
+            // Stored differences apply the same way
+            let mut bytes = vec![];
+            diff.store(&mut bytes).unwrap();
+            let loaded = CompactGridDiff::load(&mut &bytes[..]).unwrap();
+            let (_, par_diff): (Grid, CompactGridDiff<GameOfLife>) =
+                grid.clone().par_cpu_evolve_once_diff(4);
+            let mut par_bytes = vec![];
+            par_diff.store(&mut par_bytes).unwrap();
+            assert_eq!(par_bytes, bytes);
+            assert_eq!(
+                loaded
+                    .apply_to(grid.clone())
+                    .iter()
+                    .flatten()
+                    .collect::<Vec<_>>(),
+                next.iter().flatten().collect::<Vec<_>>()
+            );
+
+            stacked.stack(&diff);
+            grid = next;
+        }
+        assert!(footprints.0 * 2 < footprints.1);
+        let reconstructed = stacked.apply_to(start.clone());
+        assert_eq!(
+            reconstructed.iter().flatten().collect::<Vec<_>>(),
+            grid.iter().flatten().collect::<Vec<_>>()
+        );
+        let undone = stacked.unapply_from(grid);
+        assert_eq!(
+            undone.iter().flatten().collect::<Vec<_>>(),
+            start.iter().flatten().collect::<Vec<_>>()
+        );
+
+        // Simulators store compact differences like any other
+        let mut simulator: SyncSimulator<Grid, CompactGridDiff<GameOfLife>> =
+            SyncSimulator::cpu_backend(game_of_life::r_pentomino(), 10);
+        simulator.run(60);
+        let diff = simulator.get_difference(13, 47).unwrap();
+        let target = diff.apply_to(simulator.get_generation(13).unwrap());
+        assert_eq!(
+            target.iter().flatten().collect::<Vec<_>>(),
+            simulator
+                .get_generation(47)
+                .unwrap()
+                .iter()
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+    }
+}